@@ -0,0 +1,68 @@
+//! Minimal glob matching for filtering directories
+
+/// Match a `/`-separated relative `path` against `pattern`.
+///
+/// | Pattern   | Matches                                                   |
+/// | --------- | --------------------------------------------------------- |
+/// | `?`       | Any single character except `/`                           |
+/// | `*`       | Any number of characters except `/`                       |
+/// | `**/`     | Zero or more directories                                  |
+/// | `**`      | Anything, including `/`                                   |
+///
+/// Patterns without any `/` are matched against the file name alone, so `*.png` matches PNGs in any subdirectory.
+pub(crate) fn matches(pattern: &str, path: &str) -> bool {
+    if pattern.contains('/') {
+        glob(pattern.as_bytes(), path.as_bytes())
+    } else {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        glob(pattern.as_bytes(), name.as_bytes())
+    }
+}
+
+/// Include `path` if it matches any non-`!` pattern (or there are none), and doesn't match any `!` pattern.
+pub(crate) fn filter(patterns: &[String], path: &str) -> bool {
+    let mut includes = patterns.iter().filter(|p| !p.starts_with('!')).peekable();
+    let mut excludes = patterns.iter().filter_map(|p| p.strip_prefix('!'));
+    let included = includes.peek().is_none() || includes.any(|p| matches(p, path));
+    included && !excludes.any(|p| matches(p, path))
+}
+
+fn glob(p: &[u8], t: &[u8]) -> bool {
+    match p {
+        []                          => t.is_empty(),
+        [b'*', b'*', b'/', rest @ ..] => glob(rest, t) || (0 .. t.len()).any(|i| t[i] == b'/' && glob(rest, &t[i+1..])),
+        [b'*', b'*', rest @ ..]     => (0 ..= t.len()).any(|i| glob(rest, &t[i..])),
+        [b'*', rest @ ..]           => (0 ..= t.len()).take_while(|&i| i == 0 || t[i-1] != b'/').any(|i| glob(rest, &t[i..])),
+        [b'?', rest @ ..]           => t.first().is_some_and(|&c| c != b'/') && glob(rest, &t[1..]),
+        [c, rest @ ..]              => t.first() == Some(c) && glob(rest, &t[1..]),
+    }
+}
+
+
+
+#[test] fn matching() {
+    assert!( matches("*.png",            "a.png"));
+    assert!( matches("*.png",            "sprites/a.png"));
+    assert!(!matches("*.png",            "a.png.bak"));
+    assert!( matches("sprites/*.png",    "sprites/a.png"));
+    assert!(!matches("sprites/*.png",    "sprites/ui/a.png"));
+    assert!( matches("sprites/**/*.png", "sprites/a.png"));
+    assert!( matches("sprites/**/*.png", "sprites/ui/icons/a.png"));
+    assert!( matches("sprites/**",       "sprites/ui/icons/a.png"));
+    assert!(!matches("sprites/**",       "levels/a.png"));
+    assert!( matches("level?.txt",       "level1.txt"));
+    assert!(!matches("level?.txt",       "level10.txt"));
+    assert!(!matches("a?b",              "a/b"));
+}
+
+#[test] fn filtering() {
+    let none = Vec::new();
+    let pngs = vec!["*.png".to_string(), "!unused/**".to_string()];
+    let exclude_only = vec!["!*.txt".to_string()];
+    assert!( filter(&none, "anything"));
+    assert!( filter(&pngs, "a.png"));
+    assert!(!filter(&pngs, "a.txt"));
+    assert!(!filter(&pngs, "unused/a.png"));
+    assert!( filter(&exclude_only, "a.png"));
+    assert!(!filter(&exclude_only, "a.txt"));
+}
@@ -0,0 +1,245 @@
+//! Procedural macros for [kakistocracy](https://docs.rs/kakistocracy).
+//!
+//! These are meant to be invoked via kakistocracy's `macro_rules!` wrappers (e.g. `kakistocracy::include_dir!`),
+//! which pass `$crate` as the first argument, followed by a `;`.
+
+extern crate proc_macro;
+
+mod glob;
+
+use proc_macro::*;
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+
+
+#[doc(hidden)]
+#[proc_macro]
+pub fn include_dir(input: TokenStream) -> TokenStream {
+    match include_dir_impl(input) {
+        Ok(output)  => output,
+        Err(err)    => err.into_compile_error(),
+    }
+}
+
+#[doc(hidden)]
+#[proc_macro]
+pub fn include_file_compressed(input: TokenStream) -> TokenStream {
+    match include_file_compressed_impl(input) {
+        Ok(output)  => output,
+        Err(err)    => err.into_compile_error(),
+    }
+}
+
+fn include_dir_impl(input: TokenStream) -> Result<TokenStream, Error> {
+    let args = Args::parse(input, "include_dir!", true)?;
+    if !args.resolved.is_dir() { return Err(Error::new(args.span, format!("{} is not a directory", args.resolved.display()))) }
+    let root = args.path.trim_end_matches('/');
+    let dir = Dir::walk(&args.resolved, "", &args.patterns)?;
+
+    let mut code = String::new();
+    dir.emit(&mut code, root);
+    Ok(args.substitute_crate(code.parse().unwrap()))
+}
+
+fn include_file_compressed_impl(input: TokenStream) -> Result<TokenStream, Error> {
+    let args = Args::parse(input, "include_file_compressed!", false)?;
+    let data = std::fs::read(&args.resolved).map_err(|err| Error::new(args.span, format!("unable to read {}: {}", args.resolved.display(), err)))?;
+    let compressed = miniz_oxide::deflate::compress_to_vec(&data, 10);
+
+    let mut literal = String::with_capacity(3 + 4 * compressed.len());
+    literal.push_str("b\"");
+    for b in compressed.iter() { write!(literal, "\\x{:02x}", b).unwrap(); }
+    literal.push('"');
+
+    let abs = args.resolved.to_str().ok_or_else(|| Error::new(args.span, "path is not valid UTF-8"))?;
+    let code = format!(concat!(
+        "{{",
+            // Not emitted into the final binary, but ensures changes to the file trigger a rebuild
+            "const _ : &[u8] = ::std::include_bytes!({abs:?});",
+            "static FILE : __kakistocracy__::io::CompressedStaticFile = __kakistocracy__::io::CompressedStaticFile {{",
+                "path: {path:?}, compressed: {literal}, len: {len}, ",
+                "decompressed: ::std::sync::OnceLock::new(), ",
+                "_non_exhaustive_init_via_macros_only: (),",
+            "}};",
+            "&FILE",
+        "}}",
+    ), abs = abs, path = args.path, literal = literal, len = data.len());
+    Ok(args.substitute_crate(code.parse().unwrap()))
+}
+
+
+
+/// `$crate ; [CARGO_MANIFEST_DIR /] "path" [, "pattern"]* [,]`
+struct Args {
+    krate:      TokenStream,
+    /// The path as written, for diagnostics
+    path:       String,
+    span:       Span,
+    /// The absolute path on disk
+    resolved:   PathBuf,
+    patterns:   Vec<String>,
+}
+
+impl Args {
+    fn parse(input: TokenStream, macro_name: &str, allow_patterns: bool) -> Result<Self, Error> {
+        let mut tokens = input.into_iter().peekable();
+        let mut krate = TokenStream::new();
+        loop {
+            match tokens.next() {
+                Some(TokenTree::Punct(p)) if p.as_char() == ';' => break,
+                Some(tt) => krate.extend(Some(tt)),
+                None => return Err(Error::new(Span::call_site(), format!("{} expected `$crate;` prefix - use the kakistocracy macro instead of invoking kakistocracy-macros directly", macro_name))),
+            }
+        }
+
+        let manifest_relative = match tokens.peek() {
+            Some(TokenTree::Ident(i)) if i.to_string() == "CARGO_MANIFEST_DIR" => {
+                let _ = tokens.next();
+                match tokens.next() {
+                    Some(TokenTree::Punct(p)) if p.as_char() == '/' => {},
+                    other => return Err(Error::new(span_of(other.as_ref()), "expected `/` after `CARGO_MANIFEST_DIR`")),
+                }
+                true
+            },
+            _ => false,
+        };
+
+        let (path, span) = match tokens.next() {
+            Some(TokenTree::Literal(lit)) => (string_literal(&lit)?, lit.span()),
+            other => return Err(Error::new(span_of(other.as_ref()), format!("{} expected a string literal path", macro_name))),
+        };
+
+        let mut patterns = Vec::new();
+        loop {
+            match tokens.next() {
+                None => break,
+                Some(TokenTree::Punct(p)) if p.as_char() == ',' => {},
+                other => return Err(Error::new(span_of(other.as_ref()), "expected `,`")),
+            }
+            match tokens.next() {
+                None => break,
+                Some(TokenTree::Literal(lit)) if allow_patterns => patterns.push(string_literal(&lit)?),
+                Some(TokenTree::Literal(lit)) => return Err(Error::new(lit.span(), format!("{} doesn't accept glob patterns", macro_name))),
+                other => return Err(Error::new(span_of(other.as_ref()), format!("{} expected a string literal glob pattern", macro_name))),
+            }
+        }
+
+        let base = if manifest_relative {
+            PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").ok_or_else(|| Error::new(span, "CARGO_MANIFEST_DIR is not set"))?)
+        } else {
+            // Like include_bytes!, relative to the file containing the invocation
+            let file = span.local_file().ok_or_else(|| Error::new(span, format!("unable to determine which file {} was invoked from - try `CARGO_MANIFEST_DIR / {:?}` instead", macro_name, path)))?;
+            let file = std::env::current_dir().map_or(file.clone(), |cwd| cwd.join(&file)); // include_bytes! would resolve a relative path relative to the invoking file
+            file.parent().map_or_else(PathBuf::new, Path::to_path_buf)
+        };
+        let resolved = base.join(&path);
+        Ok(Self { krate, path, span, resolved, patterns })
+    }
+
+    /// Replace every `__kakistocracy__` identifier in `tokens` with `$crate`.
+    fn substitute_crate(&self, tokens: TokenStream) -> TokenStream {
+        tokens.into_iter().flat_map(|tt| match tt {
+            TokenTree::Ident(i) if i.to_string() == "__kakistocracy__" => self.krate.clone(),
+            TokenTree::Group(g) => {
+                let mut group = Group::new(g.delimiter(), self.substitute_crate(g.stream()));
+                group.set_span(g.span());
+                TokenStream::from(TokenTree::Group(group))
+            },
+            other => TokenStream::from(other),
+        }).collect()
+    }
+}
+
+#[derive(Default)]
+struct Dir {
+    /// (name, absolute path)
+    files:  Vec<(String, PathBuf)>,
+    /// (name, contents)
+    dirs:   Vec<(String, Dir)>,
+}
+
+impl Dir {
+    fn walk(dir: &Path, relative: &str, patterns: &[String]) -> Result<Self, Error> {
+        let io_err = |err: std::io::Error| Error::new(Span::call_site(), format!("unable to read {}: {}", dir.display(), err));
+        let mut entries = std::fs::read_dir(dir).map_err(io_err)?.collect::<Result<Vec<_>, _>>().map_err(io_err)?;
+        entries.sort_by_key(|e| e.file_name());
+
+        let mut result = Dir::default();
+        for entry in entries {
+            let path = entry.path();
+            let name = entry.file_name().into_string().map_err(|name| Error::new(Span::call_site(), format!("{:?} is not valid UTF-8", name)))?;
+            let rel = if relative.is_empty() { name.clone() } else { format!("{}/{}", relative, name) };
+            if path.is_dir() {
+                let sub = Dir::walk(&path, &rel, patterns)?;
+                if !sub.is_empty() { result.dirs.push((name, sub)); }
+            } else if glob::filter(patterns, &rel) {
+                result.files.push((name, path));
+            }
+        }
+        Ok(result)
+    }
+
+    fn is_empty(&self) -> bool { self.files.is_empty() && self.dirs.is_empty() }
+
+    fn emit(&self, code: &mut String, path: &str) {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        write!(code, "__kakistocracy__::io::StaticDir {{ path: {:?}, name: {:?}, files: &[", path, name).unwrap();
+        for (name, abs) in self.files.iter() {
+            let file_path = format!("{}/{}", path, name);
+            let abs = abs.to_str().expect("path should be UTF-8");
+            write!(code, "__kakistocracy__::io::StaticFile {{ path: {:?}, data: ::std::include_bytes!({:?}), _non_exhaustive_init_via_macros_only: () }},", file_path, abs).unwrap();
+        }
+        code.push_str("], dirs: &[");
+        for (name, dir) in self.dirs.iter() {
+            dir.emit(code, &format!("{}/{}", path, name));
+            code.push(',');
+        }
+        code.push_str("], _non_exhaustive_init_via_macros_only: () }");
+    }
+}
+
+struct Error {
+    span:       Span,
+    message:    String,
+}
+
+impl Error {
+    fn new(span: Span, message: impl Into<String>) -> Self { Self { span, message: message.into() } }
+
+    fn into_compile_error(self) -> TokenStream {
+        let tokens : TokenStream = format!("::std::compile_error!({:?})", self.message).parse().unwrap();
+        tokens.into_iter().map(|mut tt| { tt.set_span(self.span); tt }).collect()
+    }
+}
+
+fn span_of(tt: Option<&TokenTree>) -> Span { tt.map_or_else(Span::call_site, TokenTree::span) }
+
+/// Parse a `"string"` or `r#"raw string"#` literal.
+fn string_literal(lit: &Literal) -> Result<String, Error> {
+    let s = lit.to_string();
+    let err = || Error::new(lit.span(), "expected a string literal");
+    if let Some(raw) = s.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let raw = &raw[hashes .. raw.len().checked_sub(hashes).ok_or_else(err)?];
+        return raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')).map(String::from).ok_or_else(err);
+    }
+
+    let s = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')).ok_or_else(err)?;
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' { out.push(ch); continue }
+        match chars.next() {
+            Some('\\')  => out.push('\\'),
+            Some('"')   => out.push('"'),
+            Some('\'')  => out.push('\''),
+            Some('n')   => out.push('\n'),
+            Some('t')   => out.push('\t'),
+            Some('0')   => out.push('\0'),
+            _           => return Err(Error::new(lit.span(), "unsupported escape sequence in path")),
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,4 @@
+//! A backend-neutral [`App`] lifecycle, and an [`AppRunner`] to host it on Direct3D 9, Direct3D 11, or headless
+
+mod lifecycle;                  pub use lifecycle::*;
+mod runner;                     pub use runner::*;
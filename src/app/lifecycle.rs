@@ -0,0 +1,133 @@
+use crate::app::Backend;
+use crate::frame::EachFrameArgs;
+use crate::headless::CpuRenderTarget;
+use crate::sprite::{Instance, private};
+use crate::texture::TextureSource;
+
+#[cfg(windows)] use crate::windows::*;
+#[cfg(all(windows, feature = "d3d9"))] use winapi::shared::d3d9::IDirect3DDevice9;
+#[cfg(all(windows, feature = "d3d11"))] use winapi::um::d3d11::{ID3D11DeviceContext, ID3D11RenderTargetView};
+
+use std::cell::Cell;
+use std::time::Duration;
+
+
+
+/// A game or tool, written once and hosted on any [`Backend`] by an [`AppRunner`](crate::app::AppRunner).
+///
+/// Each frame, the runner calls [`fixed_update`](Self::fixed_update) zero or more times (see [`FixedTimestep`](crate::time::FixedTimestep)),
+/// then [`update`](Self::update) once, then [`render`](Self::render) once per window.
+pub trait App : 'static {
+    /// Called once, before the first frame.
+    fn init(&mut self, _ctx: &AppContext) {}
+
+    /// Advance the simulation by one fixed step of `dt`.
+    fn fixed_update(&mut self, _ctx: &AppContext, _dt: Duration) {}
+
+    /// Called once per frame, after any fixed updates.
+    fn update(&mut self, _ctx: &AppContext, _args: &EachFrameArgs) {}
+
+    /// Render the frame.  `args` is a [`sprite::RenderTarget`](crate::sprite::RenderTarget).
+    fn render(&mut self, args: &mut RenderArgs);
+
+    /// Called when the render target changes size (but not for its initial size, which is available from [`AppContext::size`] in [`init`](Self::init).)
+    fn resize(&mut self, _ctx: &AppContext, _size: (u32, u32)) {}
+
+    /// Called once, after the last frame.
+    fn shutdown(&mut self, _ctx: &AppContext) {}
+}
+
+/// Passed to [`App`] callbacks: what the app is running on, and how to stop it.
+#[derive(Debug)]
+pub struct AppContext {
+    backend:    Backend,
+    size:       Cell<(u32, u32)>,
+    quit:       Cell<Option<i32>>,
+}
+
+/// Passed to [`App::render`]: a [`sprite::RenderTarget`](crate::sprite::RenderTarget), already bound and ready to draw to.
+///
+/// ```
+/// # use kakistocracy::app::*;
+/// struct Game;
+/// impl App for Game {
+///     fn render(&mut self, args: &mut RenderArgs) {
+///         args.clear([0.1, 0.2, 0.3, 1.0]);
+///         let (w, h) = args.size();
+///         // args.draw(&kakistocracy::include_file!("player.png"), &[...]);
+///     }
+/// }
+/// ```
+pub struct RenderArgs<'a> {
+    target: Target<'a>,
+    size:   (u32, u32),
+    alpha:  f64,
+}
+
+pub(crate) enum Target<'a> {
+    Cpu(&'a mut CpuRenderTarget),
+    #[cfg(all(windows, feature = "d3d9"))]  D3D9(&'a mcom::Rc<IDirect3DDevice9>),
+    #[cfg(all(windows, feature = "d3d11"))] D3D11(&'a mcom::Rc<ID3D11DeviceContext>, &'a mcom::Rc<ID3D11RenderTargetView>),
+}
+
+impl AppContext {
+    pub(crate) fn new(backend: Backend, size: (u32, u32)) -> Self { Self { backend, size: Cell::new(size), quit: Cell::new(None) } }
+
+    pub fn backend(&self) -> Backend { self.backend }
+
+    /// The size of the (main) render target.
+    pub fn size(&self) -> (u32, u32) { self.size.get() }
+
+    /// Stop running after this frame, with the given exit code.
+    pub fn quit(&self, exit_code: i32) { self.quit.set(Some(exit_code)); }
+
+    #[cfg_attr(not(windows), allow(dead_code))] // only windowed backends resize
+    pub(crate) fn set_size(&self, size: (u32, u32)) -> bool { self.size.replace(size) != size }
+    pub(crate) fn take_quit(&self) -> Option<i32> { self.quit.take() }
+}
+
+impl<'a> RenderArgs<'a> {
+    pub(crate) fn new(target: Target<'a>, size: (u32, u32), alpha: f64) -> Self { Self { target, size, alpha } }
+
+    /// The size of the render target, in pixels.
+    pub fn size(&self) -> (u32, u32) { self.size }
+
+    /// How far between the last two [`fixed_update`](App::fixed_update)s to interpolate rendering (see [`FixedFrame::alpha`](crate::time::FixedFrame::alpha).)
+    pub fn alpha(&self) -> f64 { self.alpha }
+
+    /// Fill the render target with `rgba`.
+    pub fn clear(&mut self, rgba: [f32; 4]) {
+        let byte = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        match &mut self.target {
+            Target::Cpu(target) => target.clear([byte(rgba[0]), byte(rgba[1]), byte(rgba[2]), byte(rgba[3])]),
+            #[cfg(all(windows, feature = "d3d9"))] Target::D3D9(device) => {
+                let [r, g, b, a] = [byte(rgba[0]), byte(rgba[1]), byte(rgba[2]), byte(rgba[3])].map(u32::from);
+                let _hr = unsafe { device.Clear(0, std::ptr::null(), winapi::shared::d3d9types::D3DCLEAR_TARGET, (a << 24) | (r << 16) | (g << 8) | b, 0.0, 0) };
+            },
+            #[cfg(all(windows, feature = "d3d11"))] Target::D3D11(context, rtv) => unsafe { context.ClearRenderTargetView(rtv.as_ptr(), &rgba) },
+        }
+    }
+
+    /// Render `instances` of `texture`.  Safe equivalent of [`sprite::render1`](crate::sprite::render1), since the target's already bound.
+    pub fn draw<'t>(&mut self, texture: impl Into<TextureSource<'t>>, instances: &[Instance]) {
+        unsafe { crate::sprite::render1(self, texture, instances) }
+    }
+
+    /// The software render target, if running on [`Backend::Headless`].
+    pub fn cpu(&mut self) -> Option<&mut CpuRenderTarget> {
+        match &mut self.target {
+            Target::Cpu(target) => Some(target),
+            #[allow(unreachable_patterns)] _ => None,
+        }
+    }
+}
+
+impl private::RenderTarget for &mut RenderArgs<'_> {
+    unsafe fn render1(&mut self, texture: TextureSource, instances: &[Instance]) {
+        match &mut self.target {
+            Target::Cpu(target) => private::RenderTarget::render1(target, texture, instances),
+            #[cfg(all(windows, feature = "d3d9"))]  Target::D3D9(device) => private::RenderTarget::render1(device, texture, instances),
+            #[cfg(all(windows, feature = "d3d11"))] Target::D3D11(context, _rtv) => private::RenderTarget::render1(context, texture, instances),
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use crate::app::*;
+use crate::frame::EachFrameArgs;
+use crate::headless::{self, CpuRenderTarget};
+use crate::time::FixedTimestep;
+
+#[cfg(windows)] use crate::windows::*;
+#[cfg(windows)] use winapi::shared::minwindef::*;
+#[cfg(windows)] use winapi::shared::windef::*;
+#[cfg(windows)] use winapi::um::winuser::*;
+
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::Duration;
+
+
+
+/// What an [`AppRunner`] hosts an [`App`] on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// A Direct3D 9 window (Windows only.)
+    D3D9,
+    /// A Direct3D 11 window (Windows only.)
+    D3D11,
+    /// No window: sprites are rendered in software, to a [`CpuRenderTarget`].
+    Headless,
+}
+
+/// The error returned when parsing an unrecognized [`Backend`] name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownBackend(pub String);
+
+/// Hosts an [`App`] on a [`Backend`] chosen at startup.
+///
+/// ```
+/// # use kakistocracy::app::*;
+/// struct Game { frames: u32 }
+/// impl App for Game {
+///     fn update(&mut self, ctx: &AppContext, _args: &kakistocracy::frame::EachFrameArgs) {
+///         self.frames += 1;
+///         if self.frames == 3 { ctx.quit(42); }
+///     }
+///     fn render(&mut self, args: &mut RenderArgs) { args.clear([0.0, 0.0, 0.0, 1.0]); }
+/// }
+///
+/// let backend = std::env::var("BACKEND").ok().and_then(|b| b.parse().ok()).unwrap_or(Backend::Headless);
+/// # let backend = Backend::Headless;
+/// let mut runner = AppRunner::new("example");
+/// runner.set_backend(backend);
+/// assert_eq!(runner.run(Game { frames: 0 }).unwrap(), 42);
+/// ```
+#[derive(Clone, Debug)]
+pub struct AppRunner {
+    title:          String,
+    backend:        Backend,
+    size:           (u32, u32),
+    fixed_step:     Duration,
+    frame_limit:    Option<u64>,
+}
+
+/// The [`App`] and its per-frame state, shared between callbacks.
+struct Host<A: App> {
+    app:            RefCell<A>,
+    ctx:            AppContext,
+    fixed:          RefCell<FixedTimestep>,
+    alpha:          Cell<f64>,
+    frames:         Cell<u64>,  // frames run by this AppRunner::run, unlike the thread's EachFrameArgs::frame
+    frame_limit:    Option<u64>,
+    running:        Cell<bool>, // false once the loop has quit, so callbacks outliving it don't touch the shut down app
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::D3D9       => "d3d9",
+            Backend::D3D11      => "d3d11",
+            Backend::Headless   => "headless",
+        }
+    }
+
+    /// `true` if this backend was compiled in (Direct3D backends require Windows and their feature.)
+    pub fn is_available(self) -> bool {
+        match self {
+            Backend::D3D9       => cfg!(all(windows, feature = "d3d9")),
+            Backend::D3D11      => cfg!(all(windows, feature = "d3d11")),
+            Backend::Headless   => true,
+        }
+    }
+}
+
+impl Default for Backend {
+    /// The first [available](Self::is_available) of [`D3D11`](Self::D3D11), [`D3D9`](Self::D3D9), or [`Headless`](Self::Headless).
+    fn default() -> Self {
+        [Backend::D3D11, Backend::D3D9].iter().copied().find(|b| b.is_available()).unwrap_or(Backend::Headless)
+    }
+}
+
+impl Display for Backend {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result { fmt.write_str(self.name()) }
+}
+
+impl FromStr for Backend {
+    type Err = UnknownBackend;
+    /// Parses `"d3d9"`, `"d3d11"`, or `"headless"` (a.k.a. `"cpu"`), ignoring case.
+    fn from_str(s: &str) -> Result<Self, UnknownBackend> {
+        match s.to_ascii_lowercase().as_str() {
+            "d3d9"              => Ok(Backend::D3D9),
+            "d3d11"             => Ok(Backend::D3D11),
+            "headless" | "cpu"  => Ok(Backend::Headless),
+            _                   => Err(UnknownBackend(s.into())),
+        }
+    }
+}
+
+impl Display for UnknownBackend {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result { write!(fmt, "unknown backend {:?} (expected d3d9, d3d11, or headless)", self.0) }
+}
+
+impl Error for UnknownBackend {}
+
+impl AppRunner {
+    /// Run with the [default](Backend::default) backend, in an 800x600 window titled `title`, with 60 fixed updates per second.
+    pub fn new(title: impl Into<String>) -> Self {
+        Self { title: title.into(), backend: Backend::default(), size: (800, 600), fixed_step: Duration::from_secs_f64(1.0 / 60.0), frame_limit: None }
+    }
+
+    /// The window title.
+    pub fn set_title(&mut self, title: impl Into<String>) { self.title = title.into(); }
+    pub fn title(&self) -> &str { &self.title }
+
+    pub fn set_backend(&mut self, backend: Backend) { self.backend = backend; }
+    pub fn backend(&self) -> Backend { self.backend }
+
+    /// The initial window (or headless render target) size.
+    pub fn set_size(&mut self, size: (u32, u32)) { self.size = size; }
+    pub fn size(&self) -> (u32, u32) { self.size }
+
+    /// How much time each [`App::fixed_update`] simulates.
+    pub fn set_fixed_step(&mut self, step: Duration) { self.fixed_step = step; }
+    pub fn fixed_step(&self) -> Duration { self.fixed_step }
+
+    /// Quit (with exit code `0`) after `frames` frames, e.g. for smoke tests in CI.
+    pub fn set_frame_limit(&mut self, frames: Option<u64>) { self.frame_limit = frames; }
+    pub fn frame_limit(&self) -> Option<u64> { self.frame_limit }
+
+    /// Run `app` on the current thread until it [quit](AppContext::quit)s (or its window is closed), returning the exit code.
+    /// The frame it quits on is still rendered.  Any window is destroyed before [`App::shutdown`].
+    ///
+    /// Runs can be repeated on the same thread: each starts from frame 0, for the purposes of [`set_frame_limit`](Self::set_frame_limit).
+    ///
+    /// Errors if the backend isn't [available](Backend::is_available) (before calling [`App::init`]), or fails to initialize (after calling [`App::init`] - and [`App::shutdown`].)
+    pub fn run<A: App>(&self, app: A) -> Result<i32, Box<dyn Error>> {
+        if !self.backend.is_available() { return Err(format!("AppRunner::run: the {} backend isn't available on this platform", self.backend).into()) }
+        let host = Rc::new(Host {
+            app:            RefCell::new(app),
+            ctx:            AppContext::new(self.backend, self.size),
+            fixed:          RefCell::new(FixedTimestep::new(self.fixed_step)),
+            alpha:          Cell::new(0.0),
+            frames:         Cell::new(0),
+            frame_limit:    self.frame_limit,
+            running:        Cell::new(true),
+        });
+        host.app.borrow_mut().init(&host.ctx);
+
+        let exit = match self.backend {
+            Backend::Headless   => Ok(self.run_headless(&host)),
+            #[cfg(windows)] _   => self.run_windowed(&host),
+            #[cfg(not(windows))] _ => unreachable!("unavailable backends rejected above"),
+        };
+
+        host.running.set(false); // even if the window failed to open, so the update callback unregisters itself instead of updating a shut down app
+        host.app.borrow_mut().shutdown(&host.ctx);
+        exit
+    }
+
+    fn run_headless<A: App>(&self, host: &Rc<Host<A>>) -> i32 {
+        let host = host.clone();
+        let (w, h) = self.size;
+        let mut target = CpuRenderTarget::new(w, h);
+        headless::message::each_frame(move |args| {
+            if !host.running.get() { return false }
+            let quit = host.update(args);
+            host.app.borrow_mut().render(&mut RenderArgs::new(Target::Cpu(&mut target), (w, h), host.alpha.get())); // including the last frame, like windowed backends
+            target.textures().next_frame();
+            match quit {
+                Some(exit)  => { headless::message::post_quit(exit); false },
+                None        => true,
+            }
+        });
+        headless::message::loop_until_quit()
+    }
+
+    #[cfg(windows)]
+    fn run_windowed<A: App>(&self, host: &Rc<Host<A>>) -> Result<i32, Box<dyn Error>> {
+        // the d3d each_frame callback renders windows, possibly before this one if a previous run registered it first,
+        // so quit a frame after the app asks to - once its last update has certainly been rendered.
+        let updates = host.clone();
+        let mut quitting = None;
+        message::each_frame(move |args| {
+            if !updates.running.get() { return false }
+            if let Some(exit) = quitting {
+                updates.running.set(false);
+                message::post_quit(exit);
+                return false;
+            }
+            quitting = updates.update(args);
+            true
+        });
+
+        let (w, h) = (self.size.0 as i32, self.size.1 as i32);
+        let area = [100 .. 100 + w, 100 .. 100 + h];
+        let window = Window(host.clone());
+        let hwnd = match self.backend {
+            #[cfg(feature = "d3d9")]  Backend::D3D9   => d3d9 ::create_window_hwnd_at(&self.title, area, window)?,
+            #[cfg(feature = "d3d11")] Backend::D3D11  => d3d11::create_window_hwnd_at(&self.title, area, window)?,
+            _ => unreachable!("unavailable backends rejected above"),
+        };
+        let exit = message::loop_until_wm_quit();
+
+        // destroy the window (dropping its handler) before App::shutdown, so nothing renders the app afterwards
+        host.running.set(false);
+        if hwnd::assoc::valid_window(hwnd) { unsafe { DestroyWindow(hwnd) }; }
+        Ok(exit)
+    }
+}
+
+impl<A: App> Host<A> {
+    /// Run fixed and frame updates.  Returns the exit code if the app is quitting after this frame.
+    fn update(&self, args: &EachFrameArgs) -> Option<i32> {
+        let frame = self.fixed.borrow_mut().advance(args.dt);
+        let step = self.fixed.borrow().step();
+        let mut app = self.app.borrow_mut();
+        for _ in 0 .. frame.steps { app.fixed_update(&self.ctx, step); }
+        app.update(&self.ctx, args);
+        self.alpha.set(frame.alpha);
+
+        self.frames.set(self.frames.get() + 1);
+        if self.frame_limit.is_some_and(|limit| self.frames.get() >= limit) { self.ctx.quit(0); }
+        self.ctx.take_quit()
+    }
+
+    #[cfg(windows)]
+    fn resized(&self, size: (u32, u32)) {
+        if self.ctx.set_size(size) { self.app.borrow_mut().resize(&self.ctx, size); }
+    }
+}
+
+
+
+/// Hosts an [`App`] in a Direct3D window.
+#[cfg(windows)]
+struct Window<A: App>(Rc<Host<A>>);
+
+#[cfg(windows)]
+impl<A: App> message::Handler for Window<A> {
+    unsafe fn wndproc(&self, hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        match msg {
+            WM_CLOSE    => { message::post_quit(0); 0 },
+            WM_DESTROY  => { if self.0.running.get() { message::post_quit(0); } 0 }, // destroyed out from under us
+            _other      => DefWindowProcW(hwnd, msg, wparam, lparam),
+        }
+    }
+}
+
+#[cfg(all(windows, feature = "d3d9"))]
+impl<A: App> d3d9::Render for Window<A> {
+    fn render(&self, args: &d3d9::RenderArgs) {
+        if !self.0.running.get() { return }
+        self.0.resized(args.client_size());
+        if args.bind().is_err() { return }
+        let device = &args.device;
+        let _hr = unsafe { device.BeginScene() };
+        self.0.app.borrow_mut().render(&mut RenderArgs::new(Target::D3D9(device), args.client_size(), self.0.alpha.get()));
+        let _hr = unsafe { device.EndScene() };
+        let _hr = unsafe { args.swap_chain.Present(std::ptr::null(), std::ptr::null(), std::ptr::null_mut(), std::ptr::null(), 0) };
+    }
+}
+
+#[cfg(all(windows, feature = "d3d11"))]
+impl<A: App> d3d11::Render for Window<A> {
+    fn render(&self, args: &d3d11::RenderArgs) -> d3d11::RenderResult {
+        if !self.0.running.get() { return d3d11::RenderResult::from(()) }
+        self.0.resized(args.client_size());
+        let context = &args.immediate_context;
+        if let Err(err) = unsafe { args.bind(context) } { return err.into() }
+        self.0.app.borrow_mut().render(&mut RenderArgs::new(Target::D3D11(context, &args.rtv), args.client_size(), self.0.alpha.get()));
+        unsafe { args.swap_chain.Present(1, 0) }.into()
+    }
+}
+
+
+
+#[test] fn headless_lifecycle() {
+    #[derive(Default)]
+    struct Recorder { log: Rc<RefCell<Vec<String>>>, fixed: u32 }
+    impl App for Recorder {
+        fn init(&mut self, ctx: &AppContext) { self.log.borrow_mut().push(format!("init {} {:?}", ctx.backend(), ctx.size())); }
+        fn fixed_update(&mut self, _ctx: &AppContext, dt: Duration) { assert_eq!(dt, Duration::from_millis(1)); self.fixed += 1; }
+        fn update(&mut self, _ctx: &AppContext, args: &EachFrameArgs) { self.log.borrow_mut().push(format!("update {}", args.frame)); }
+        fn render(&mut self, args: &mut RenderArgs) {
+            args.clear([1.0, 0.0, 0.0, 1.0]);
+            assert_eq!(args.cpu().unwrap().image().get(0, 0), Some([255, 0, 0, 255]));
+            self.log.borrow_mut().push(format!("render {:?}", args.size()));
+        }
+        fn shutdown(&mut self, _ctx: &AppContext) { self.log.borrow_mut().push(format!("shutdown after {} fixed updates", if self.fixed > 0 { "some" } else { "no" })); }
+    }
+
+    assert_eq!("D3D11".parse(), Ok(Backend::D3D11));
+    assert_eq!("cpu".parse(), Ok(Backend::Headless));
+    assert!("vulkan".parse::<Backend>().unwrap_err().to_string().contains("vulkan"));
+
+    std::thread::spawn(|| {
+        headless::message::frame_pacer(|pacer| pacer.set_target(Some(Duration::from_millis(5))));
+        let mut runner = AppRunner::new("test");
+        runner.set_backend(Backend::Headless);
+        runner.set_size((4, 2));
+        runner.set_fixed_step(Duration::from_millis(1));
+        runner.set_frame_limit(Some(3)); // the pacer only sleeps from the second frame on, so fixed updates are guaranteed by the third
+
+        for first in [1, 4].iter() { // frame limits count frames of each run, not of the thread
+            let app = Recorder::default();
+            let log = app.log.clone();
+            assert_eq!(runner.run(app).unwrap(), 0);
+            let expected = ["init headless (4, 2)".into(), format!("update {}", first), "render (4, 2)".into(), format!("update {}", first + 1), "render (4, 2)".into(), format!("update {}", first + 2), "render (4, 2)".into(), "shutdown after some fixed updates".into()];
+            assert_eq!(*log.borrow(), expected);
+        }
+
+        // quitting from elsewhere still stops the app
+        let app = Recorder::default();
+        let log = app.log.clone();
+        runner.set_frame_limit(None);
+        headless::message::post_quit(7);
+        assert_eq!(runner.run(app).unwrap(), 7);
+        assert_eq!(headless::message::loop_one_frame(), None);
+        assert_eq!(*log.borrow(), ["init headless (4, 2)", "shutdown after no fixed updates"], "callbacks outliving a run shouldn't touch its app");
+    }).join().unwrap();
+}
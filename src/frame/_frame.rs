@@ -0,0 +1,8 @@
+//! Frame loop scheduling: a [`FrameScheduler`] to run local tasks and [`each_frame`](FrameScheduler::each_frame) callbacks on, [`TaskHandle`]s and [`TaskScope`]s to cancel them with, [`next_frame`], [`frames`], [`delay`], and [`timeout`] futures for those tasks to await, [`LoopHandle`]s to post work to it from other threads, and a [`ThreadPool`] to hand work off to
+
+pub(crate) mod each_frame;      pub use each_frame::EachFrameArgs;
+pub(crate) mod loop_handle;     pub use loop_handle::{LoopHandle, Wakeup, WakeupSignal};
+mod scheduler;                  pub use scheduler::*;
+mod task;                       pub use task::*;
+mod thread_pool;                pub use thread_pool::*;
+mod wait;                       pub use wait::*;
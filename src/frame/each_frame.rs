@@ -0,0 +1,177 @@
+use crate::frame::FrameScheduler;
+use crate::time::{FrameRateCounter, FrameStats};
+
+use instant::{Duration, Instant};
+
+use std::cell::{Cell, RefCell};
+use std::fmt::{self, Debug, Formatter};
+use std::rc::Rc;
+
+
+
+/// Arguments to [`FrameScheduler::each_frame`]'s callbacks.
+///
+/// `dt` is what game logic usually wants: real time, clamped to [`max_dt`](FrameScheduler::set_max_dt) (so a breakpoint or window drag doesn't teleport everything),
+/// then scaled by the [`time_scale`](FrameScheduler::set_time_scale), and zero while [`paused`](FrameScheduler::set_paused).
+#[non_exhaustive]
+pub struct EachFrameArgs {
+    /// The index of this frame, starting at 1.
+    pub frame:          u64,
+    /// When this frame started.
+    pub now:            Instant,
+    /// Real time since the previous frame.
+    pub raw_dt:         Duration,
+    /// `raw_dt`, clamped to the scheduler's `max_dt`.
+    pub clamped_dt:     Duration,
+    /// `clamped_dt`, scaled by `time_scale`, or zero if `paused`.
+    pub dt:             Duration,
+    /// The sum of every frame's `dt` so far: "game time".
+    pub elapsed:        Duration,
+    /// Real time since the scheduler was created.
+    pub real_elapsed:   Duration,
+    pub time_scale:     f64,
+    pub paused:         bool,
+    frame_rate:         Rc<RefCell<FrameRateCounter>>,
+}
+
+type Callback = Box<dyn FnMut(&EachFrameArgs) -> bool>;
+
+/// [`FrameScheduler`] state for [`EachFrameArgs`] and [`each_frame`](FrameScheduler::each_frame) callbacks.
+pub(crate) struct FrameTime {
+    start:      Instant,
+    last:       Cell<Instant>,
+    elapsed:    Cell<Duration>,
+    time_scale: Cell<f64>,
+    paused:     Cell<bool>,
+    max_dt:     Cell<Duration>,
+    frame_rate: Rc<RefCell<FrameRateCounter>>,
+    each_frame: RefCell<Vec<Callback>>,
+    pending:    RefCell<Vec<Callback>>,
+}
+
+impl EachFrameArgs {
+    /// `dt` in seconds, for convenience.
+    pub fn dt_secs(&self) -> f32 { self.dt.as_secs_f32() }
+
+    /// The average `raw_dt` over the last 120 frames.
+    pub fn average_dt(&self) -> Duration { self.frame_rate.borrow().average() }
+
+    /// Statistics about the `raw_dt` of the last 120 frames.
+    pub fn stats(&self) -> FrameStats { self.frame_rate.borrow().stats() }
+}
+
+impl Debug for EachFrameArgs {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("EachFrameArgs")
+            .field("frame", &self.frame)
+            .field("raw_dt", &self.raw_dt)
+            .field("dt", &self.dt)
+            .field("elapsed", &self.elapsed)
+            .field("time_scale", &self.time_scale)
+            .field("paused", &self.paused)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FrameTime {
+    pub(crate) fn new(start: Instant) -> Self {
+        Self {
+            start,
+            last:       Cell::new(start),
+            elapsed:    Cell::new(Duration::ZERO),
+            time_scale: Cell::new(1.0),
+            paused:     Cell::new(false),
+            max_dt:     Cell::new(Duration::from_millis(250)),
+            frame_rate: Rc::new(RefCell::new(FrameRateCounter::new(120))),
+            each_frame: Default::default(),
+            pending:    Default::default(),
+        }
+    }
+
+    /// Start frame number `frame` at `now`.
+    pub(crate) fn advance(&self, frame: u64, now: Instant) -> EachFrameArgs {
+        let raw_dt = now.saturating_duration_since(self.last.replace(now));
+        let clamped_dt = raw_dt.min(self.max_dt.get());
+        let (time_scale, paused) = (self.time_scale.get(), self.paused.get());
+        let dt = if paused { Duration::ZERO } else { clamped_dt.mul_f64(time_scale) };
+        self.elapsed.set(self.elapsed.get() + dt);
+        self.frame_rate.borrow_mut().push_frame_time(raw_dt);
+        EachFrameArgs { frame, now, raw_dt, clamped_dt, dt, elapsed: self.elapsed.get(), real_elapsed: now - self.start, time_scale, paused, frame_rate: self.frame_rate.clone() }
+    }
+
+    pub(crate) fn run_each_frame(&self, args: &EachFrameArgs) {
+        if let Ok(mut each_frame) = self.each_frame.try_borrow_mut() {
+            each_frame.append(&mut *self.pending.borrow_mut());
+            each_frame.retain_mut(|f| f(args));
+        }
+        // else we're recursively running a frame within an each_frame callback?  probably an incredibly bad idea, but don't crash
+    }
+}
+
+impl FrameScheduler {
+    /// Call `f` each frame, after tasks have run, until it returns `false`.  Callbacks registered during a frame start on the next one.
+    ///
+    /// Wrap `f` with [`TaskScope::bind`](crate::frame::TaskScope::bind) to also stop it when the scope is cancelled.
+    pub fn each_frame(&self, f: impl 'static + FnMut(&EachFrameArgs) -> bool) {
+        self.time().pending.borrow_mut().push(Box::new(f));
+    }
+
+    /// Scale [`EachFrameArgs::dt`] (e.g. `0.5` for slow motion.)  Negative scales are treated as `0.0`.  Defaults to `1.0`.
+    pub fn set_time_scale(&self, scale: f64) { self.time().time_scale.set(scale.max(0.0)); }
+    pub fn time_scale(&self) -> f64 { self.time().time_scale.get() }
+
+    /// Stop [`EachFrameArgs::elapsed`] game time.  Tasks, real time, and [`delay`](crate::frame::delay)s keep running.
+    pub fn set_paused(&self, paused: bool) { self.time().paused.set(paused); }
+    pub fn is_paused(&self) -> bool { self.time().paused.get() }
+
+    /// Clamp [`EachFrameArgs::dt`] to at most `max`.  Defaults to 250ms.
+    pub fn set_max_dt(&self, max: Duration) { self.time().max_dt.set(max); }
+    pub fn max_dt(&self) -> Duration { self.time().max_dt.get() }
+
+    /// Game time: the sum of every frame's [`EachFrameArgs::dt`] so far.
+    pub fn elapsed(&self) -> Duration { self.time().elapsed.get() }
+
+    /// Statistics about the last 120 frames.
+    pub fn frame_stats(&self) -> FrameStats { self.time().frame_rate.borrow().stats() }
+}
+
+
+
+#[test] fn each_frame_args() {
+    use crate::time::ManualClock;
+    use std::sync::Arc;
+
+    let ms = Duration::from_millis;
+    let clock = Arc::new(ManualClock::new());
+    let scheduler = FrameScheduler::with_clock(clock.clone());
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let l = log.clone();
+    scheduler.each_frame(move |args| { l.borrow_mut().push((args.frame, args.raw_dt, args.clamped_dt, args.dt, args.elapsed)); args.frame < 5 });
+
+    clock.advance(ms(10));
+    scheduler.run_frame();
+    clock.advance(ms(300));
+    scheduler.run_frame(); // clamped
+    scheduler.set_time_scale(0.5);
+    clock.advance(ms(20));
+    scheduler.run_frame();
+    scheduler.set_paused(true);
+    clock.advance(ms(20));
+    scheduler.run_frame();
+    scheduler.set_paused(false);
+    clock.advance(ms(20));
+    scheduler.run_frame();
+    clock.advance(ms(20));
+    scheduler.run_frame(); // unregistered
+
+    assert_eq!(*log.borrow(), [
+        (1, ms(10),  ms(10),  ms(10),  ms(10)),
+        (2, ms(300), ms(250), ms(250), ms(260)),
+        (3, ms(20),  ms(20),  ms(10),  ms(270)),
+        (4, ms(20),  ms(20),  ms(0),   ms(270)),
+        (5, ms(20),  ms(20),  ms(10),  ms(280)),
+    ]);
+    assert_eq!(scheduler.elapsed(), ms(290));
+    let stats = scheduler.frame_stats();
+    assert_eq!((stats.frames, stats.max), (6, ms(300)));
+}
@@ -0,0 +1,237 @@
+use futures::Future;
+use futures::task::{ArcWake, SpawnError};
+use instant::Duration;
+
+use std::cell::Cell;
+use std::fmt::{self, Debug, Formatter};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, ThreadId};
+use std::task::{Context, Poll, Waker};
+
+
+
+/// A [`Send`] handle for posting closures and futures onto a [`FrameScheduler`](crate::frame::FrameScheduler)'s thread, from any thread.
+///
+/// Posted work runs at the start of the scheduler's next [`run_frame`](crate::frame::FrameScheduler::run_frame), after which the scheduler's [`Wakeup`] is poked so a sleeping loop notices.
+/// Posting fails with [`SpawnError::shutdown`] once the scheduler has been dropped.
+#[derive(Clone)]
+pub struct LoopHandle(pub(crate) Arc<Remote>);
+
+/// Wakes a frame loop that may be sleeping: e.g. by posting a window message, or signaling a [`WakeupSignal`].
+///
+/// Called (from any thread) when work is posted to a [`LoopHandle`], or when a task on the loop is woken - except by the loop's own thread while it's polling tasks.
+pub trait Wakeup : Send + Sync {
+    fn wake(&self);
+}
+
+/// A [`Wakeup`] for loops without a message queue of their own to wake (e.g. headless loops): [`wait`](Self::wait) sleeps until woken.
+#[derive(Debug, Default)]
+pub struct WakeupSignal {
+    woken:      Mutex<bool>,
+    condvar:    Condvar,
+}
+
+pub(crate) struct Remote {
+    posted:     Mutex<Option<Vec<Posted>>>, // None once the scheduler is dropped
+    wakeup:     Mutex<Option<Arc<dyn Wakeup>>>,
+    thread:     ThreadId,   // the scheduler's thread
+    polling:    AtomicBool, // true while the scheduler is polling tasks, which will see any wakes from its own thread without a Wakeup
+}
+
+/// Marks a [`Remote`]'s scheduler as polling tasks until dropped.
+pub(crate) struct Polling<'r>(&'r Remote);
+
+pub(crate) enum Posted {
+    Call(Box<dyn FnOnce() + Send>),
+    Spawn(Pin<Box<dyn Future<Output = ()> + Send>>),
+    SpawnWith(Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()>>> + Send>),
+}
+
+impl LoopHandle {
+    /// Call `f` on the loop's thread.
+    pub fn post(&self, f: impl FnOnce() + Send + 'static) -> Result<(), SpawnError> { self.0.post(Posted::Call(Box::new(f))) }
+
+    /// Spawn `f` as a task on the loop's thread.
+    pub fn spawn(&self, f: impl Future<Output = ()> + Send + 'static) -> Result<(), SpawnError> { self.0.post(Posted::Spawn(Box::pin(f))) }
+
+    /// Call `f` on the loop's thread, and spawn the future it returns as a task there.
+    /// Unlike [`spawn`](Self::spawn), the future needn't be [`Send`], so it can await [`next_frame`](crate::frame::next_frame) etc.
+    pub fn spawn_with<F: Future<Output = ()> + 'static>(&self, f: impl FnOnce() -> F + Send + 'static) -> Result<(), SpawnError> {
+        self.0.post(Posted::SpawnWith(Box::new(move || Box::pin(f()))))
+    }
+
+    /// Poke the loop's [`Wakeup`] without posting anything.
+    pub fn wake(&self) { self.0.wake() }
+
+    /// `true` if the scheduler has been dropped.
+    pub fn is_closed(&self) -> bool { self.0.posted.lock().unwrap().is_none() }
+}
+
+impl Debug for LoopHandle {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result { write!(fmt, "LoopHandle{}", if self.is_closed() { "(closed)" } else { "" }) }
+}
+
+impl<F: Fn() + Send + Sync> Wakeup for F {
+    fn wake(&self) { self() }
+}
+
+impl WakeupSignal {
+    pub fn new() -> Self { Self::default() }
+
+    /// Sleep until woken.  Returns immediately if already woken since the last wait.
+    pub fn wait(&self) {
+        let mut woken = self.condvar.wait_while(self.woken.lock().unwrap(), |woken| !*woken).unwrap();
+        *woken = false;
+    }
+
+    /// Sleep until woken, or until `timeout` elapses.  Returns `true` if woken.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let (mut woken, _) = self.condvar.wait_timeout_while(self.woken.lock().unwrap(), timeout, |woken| !*woken).unwrap();
+        std::mem::replace(&mut *woken, false)
+    }
+}
+
+impl Wakeup for WakeupSignal {
+    fn wake(&self) {
+        *self.woken.lock().unwrap() = true;
+        self.condvar.notify_all();
+    }
+}
+
+impl Remote {
+    pub(crate) fn new() -> Self { Self { posted: Mutex::new(Some(Vec::new())), wakeup: Mutex::new(None), thread: thread::current().id(), polling: AtomicBool::new(false) } }
+
+    pub(crate) fn set_wakeup(&self, wakeup: Option<Arc<dyn Wakeup>>) { *self.wakeup.lock().unwrap() = wakeup; }
+
+    pub(crate) fn wake(&self) {
+        let wakeup = self.wakeup.lock().unwrap().clone(); // don't hold the lock while waking
+        if let Some(wakeup) = wakeup { wakeup.wake(); }
+    }
+
+    /// Wake the loop, unless a task is being woken by the loop's own thread while it's polling tasks (e.g. timers coming due) - the loop is clearly awake, and will poll the task this frame.
+    fn wake_task(&self) {
+        let polling = thread::current().id() == self.thread && self.polling.load(Ordering::Relaxed);
+        if !polling { self.wake(); }
+    }
+
+    /// Until the result is dropped, skip [`Wakeup`]s for tasks woken by the scheduler's own thread.
+    pub(crate) fn polling(&self) -> Polling<'_> {
+        debug_assert_eq!(thread::current().id(), self.thread);
+        self.polling.store(true, Ordering::Relaxed);
+        Polling(self)
+    }
+
+    fn post(&self, posted: Posted) -> Result<(), SpawnError> {
+        match self.posted.lock().unwrap().as_mut() {
+            Some(queue) => queue.push(posted),
+            None        => return Err(SpawnError::shutdown()),
+        }
+        self.wake();
+        Ok(())
+    }
+
+    /// Take everything posted so far.
+    pub(crate) fn take(&self) -> Vec<Posted> { self.posted.lock().unwrap().as_mut().map(std::mem::take).unwrap_or_default() }
+
+    /// Refuse any further posts, dropping anything still queued.
+    pub(crate) fn close(&self) { drop(self.posted.lock().unwrap().take()); }
+}
+
+impl Drop for Polling<'_> {
+    fn drop(&mut self) { self.0.polling.store(false, Ordering::Relaxed); }
+}
+
+/// Wraps a local task, so waking it (possibly from another thread) also pokes the loop's [`Wakeup`].
+/// Also counts running tasks.
+pub(crate) struct WakeLoop {
+    remote:     Arc<Remote>,
+    running:    Rc<Cell<usize>>,
+    task:       Pin<Box<dyn Future<Output = ()>>>,
+    waker:      Option<(Arc<WakeLoopWaker>, Waker)>, // reused while the executor's waker doesn't change
+}
+
+struct WakeLoopWaker {
+    remote: Arc<Remote>,
+    waker:  Waker,
+}
+
+impl WakeLoop {
+    pub(crate) fn new(remote: Arc<Remote>, running: Rc<Cell<usize>>, task: Pin<Box<dyn Future<Output = ()>>>) -> Self {
+        running.set(running.get() + 1);
+        Self { remote, running, task, waker: None }
+    }
+}
+
+impl Drop for WakeLoop {
+    fn drop(&mut self) { self.running.set(self.running.get() - 1); }
+}
+
+impl Future for WakeLoop {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        if !this.waker.as_ref().is_some_and(|(wrapper, _)| wrapper.waker.will_wake(cx.waker())) {
+            let wrapper = Arc::new(WakeLoopWaker { remote: this.remote.clone(), waker: cx.waker().clone() });
+            this.waker = Some((wrapper.clone(), futures::task::waker(wrapper)));
+        }
+        let waker = &this.waker.as_ref().unwrap().1;
+        this.task.as_mut().poll(&mut Context::from_waker(waker))
+    }
+}
+
+impl ArcWake for WakeLoopWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.waker.wake_by_ref();
+        arc_self.remote.wake_task();
+    }
+}
+
+
+
+#[test] fn headless_handoff() {
+    use crate::frame::*;
+    use std::sync::mpsc;
+
+    let (send, recv) = mpsc::channel();
+    let worker = std::thread::spawn(move || {
+        let handle : LoopHandle = recv.recv().unwrap();
+        handle.post(|| assert_eq!(std::thread::current().name(), Some("loop"))).unwrap();
+        handle.spawn(async { assert_eq!(std::thread::current().name(), Some("loop")); }).unwrap();
+        handle.spawn_with(|| async { next_frame().await; }).unwrap();
+        handle
+    });
+
+    std::thread::Builder::new().name("loop".into()).spawn(move || {
+        let signal = Arc::new(WakeupSignal::new());
+        let scheduler = FrameScheduler::new();
+        scheduler.set_wakeup(Some(signal.clone()));
+        let pool = ThreadPool::new("headless_handoff", 1);
+        let (go, wait) = mpsc::channel::<()>();
+        let work = pool.spawn(move || { wait.recv().unwrap(); 42 });
+        let result = Arc::new(Mutex::new(None));
+        let r = result.clone();
+        scheduler.spawn_local(async move { *r.lock().unwrap() = Some(work.await); }).unwrap();
+
+        scheduler.run_frame();
+        assert!(!signal.wait_timeout(Duration::from_millis(10)), "nothing to do yet");
+
+        send.send(scheduler.handle()).unwrap();
+        let handle = worker.join().unwrap();
+        signal.wait();
+        scheduler.run_frame(); // runs posted work
+
+        go.send(()).unwrap();
+        signal.wait(); // woken by the background work finishing
+        scheduler.run_frame();
+        assert_eq!(*result.lock().unwrap(), Some(42));
+        assert_eq!(scheduler.running_tasks(), 0, "next_frame task woken and finished");
+        assert!(!signal.wait_timeout(Duration::from_millis(10)), "tasks woken by the loop itself while polling don't need a wakeup");
+
+        drop(scheduler);
+        assert!(handle.is_closed());
+        assert!(handle.post(|| {}).is_err());
+    }).unwrap().join().unwrap();
+}
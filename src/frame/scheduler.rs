@@ -0,0 +1,295 @@
+use crate::frame::{LoopHandle, Wakeup};
+use crate::frame::each_frame::FrameTime;
+use crate::frame::loop_handle::{Posted, Remote, WakeLoop};
+use crate::time::{Clock, RealClock};
+
+use futures::Future;
+use futures::executor::{LocalPool, LocalSpawner};
+use futures::task::{LocalSpawnExt, SpawnError};
+use instant::Instant;
+
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+use std::task::Waker;
+
+
+
+/// Runs local tasks once per frame, waking those awaiting [`next_frame`](crate::frame::next_frame), [`frames`](crate::frame::frames), or [`delay`](crate::frame::delay) as they come due.
+///
+/// The scheduler is platform neutral: a frame is whatever calls [`run_frame`](Self::run_frame), and time is whatever its [`Clock`] says.
+/// `windows::message` runs one per thread, but tests can drive one directly with a [`ManualClock`](crate::time::ManualClock).
+///
+/// Delays are measured from the start of the frame they were first polled in, so they don't depend on how long that frame took to get there.
+///
+/// ```
+/// # use kakistocracy::frame::*;
+/// # use kakistocracy::time::*;
+/// # use std::{cell::Cell, rc::Rc, sync::Arc, time::Duration};
+/// let clock = Arc::new(ManualClock::new());
+/// let scheduler = FrameScheduler::with_clock(clock.clone());
+/// let step = Rc::new(Cell::new(0));
+/// let s = step.clone();
+/// scheduler.spawn_local(async move {
+///     next_frame().await;
+///     s.set(1);
+///     delay(Duration::from_secs(1)).await;
+///     s.set(2);
+/// }).unwrap();
+///
+/// scheduler.run_frame(); assert_eq!(step.get(), 0);
+/// scheduler.run_frame(); assert_eq!(step.get(), 1);
+/// scheduler.run_frame(); assert_eq!(step.get(), 1);
+/// clock.advance(Duration::from_secs(1));
+/// scheduler.run_frame(); assert_eq!(step.get(), 2);
+/// ```
+pub struct FrameScheduler {
+    shared:     Rc<Shared>,
+    remote:     Arc<Remote>,
+    running:    Rc<Cell<usize>>,
+    time:       FrameTime,
+    pool:       RefCell<LocalPool>,
+    spawner:    LocalSpawner,
+}
+
+pub(crate) struct Shared {
+    clock:      Arc<dyn Clock>,
+    frame:      Cell<u64>,
+    now:        Cell<Instant>,
+    seq:        Cell<u64>,
+    frames:     RefCell<BinaryHeap<Wait<u64>>>,
+    timers:     RefCell<BinaryHeap<Wait<Instant>>>,
+}
+
+/// When a [`Waiter`] is due.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Due {
+    Frame(u64),
+    Time(Instant),
+}
+
+/// A registered wait: woken by [`FrameScheduler::run_frame`] once due.  Dropping this cancels the wait.
+pub(crate) struct Waiter {
+    shared: Rc<Shared>,
+    due:    Due,
+    waker:  Rc<RefCell<Option<Waker>>>,
+}
+
+struct Wait<K> {
+    at:     K,
+    seq:    u64,
+    waker:  Weak<RefCell<Option<Waker>>>,
+}
+
+impl FrameScheduler {
+    pub fn new() -> Self { Self::with_clock(RealClock) }
+
+    /// Time frames and [`delay`](crate::frame::delay)s with `clock`.
+    pub fn with_clock(clock: impl Clock + 'static) -> Self {
+        let clock : Arc<dyn Clock> = Arc::new(clock);
+        let now = clock.now();
+        let pool = LocalPool::new();
+        let spawner = pool.spawner();
+        Self {
+            shared:  Rc::new(Shared { clock, frame: Cell::new(0), now: Cell::new(now), seq: Cell::new(0), frames: Default::default(), timers: Default::default() }),
+            remote:  Arc::new(Remote::new()),
+            running: Default::default(),
+            time:    FrameTime::new(now),
+            pool:    RefCell::new(pool),
+            spawner,
+        }
+    }
+
+    pub fn clock(&self) -> &dyn Clock { &*self.shared.clock }
+
+    /// The index of the current (or most recently run) frame.  `0` until the first [`run_frame`](Self::run_frame), `1` during it.
+    pub fn frame(&self) -> u64 { self.shared.frame.get() }
+
+    /// The time the current (or most recently run) frame started.
+    pub fn now(&self) -> Instant { self.shared.now.get() }
+
+    /// Run a future/task on this scheduler.  It'll first be polled during the next [`run_frame`](Self::run_frame).
+    pub fn spawn_local<F: Future<Output = ()> + 'static>(&self, f: F) -> Result<(), SpawnError> {
+        self.spawner.spawn_local(WakeLoop::new(self.remote.clone(), self.running.clone(), Box::pin(f)))
+    }
+
+    /// The number of tasks spawned on this scheduler that haven't yet finished (or been dropped after being cancelled.)  Useful for leak checks.
+    pub fn running_tasks(&self) -> usize { self.running.get() }
+
+    /// A [`Send`]able handle for posting work onto this scheduler from other threads.
+    pub fn handle(&self) -> LoopHandle { LoopHandle(self.remote.clone()) }
+
+    /// Call `wakeup` whenever work is posted to a [`LoopHandle`], or a task is woken (possibly from another thread), so a loop sleeping between frames knows to run another.
+    pub fn set_wakeup(&self, wakeup: Option<Arc<dyn Wakeup>>) { self.remote.set_wakeup(wakeup); }
+
+    /// Start a new frame: run work posted from other threads, wake any tasks that are now due, run tasks until they've all stalled, then run [`each_frame`](Self::each_frame) callbacks.
+    pub fn run_frame(&self) {
+        let mut pool = match self.pool.try_borrow_mut() {
+            Ok(pool) => pool,
+            Err(_) => return, // recursively running a frame from within a task?  probably an incredibly bad idea, but don't crash
+        };
+
+        let shared = &self.shared;
+        shared.frame.set(shared.frame.get() + 1);
+        shared.now.set(shared.clock.now().max(shared.now.get()));
+        let polling = self.remote.polling();
+        wake_due(&shared.frames, shared.frame.get());
+        wake_due(&shared.timers, shared.now.get());
+        let args = self.time.advance(shared.frame.get(), shared.now.get());
+
+        let current = Current::enter(shared.clone());
+        for posted in self.remote.take() {
+            match posted {
+                Posted::Call(f)     => f(),
+                Posted::Spawn(f)    => self.spawn_local(f).expect("FrameScheduler::run_frame: unable to spawn posted task"),
+                Posted::SpawnWith(f)=> self.spawn_local(f()).expect("FrameScheduler::run_frame: unable to spawn posted task"),
+            }
+        }
+        pool.run_until_stalled();
+        drop(polling); // each_frame callbacks waking tasks should still wake the loop, as the tasks won't run until next frame
+        drop(current);
+        drop(pool); // so tasks still run if an each_frame callback recursively runs a message loop (e.g. a modal dialog)
+        self.time.run_each_frame(&args);
+    }
+
+    pub(crate) fn time(&self) -> &FrameTime { &self.time }
+
+    /// The earliest time a pending [`delay`](crate::frame::delay) will come due, if any.  A loop with nothing else to do can sleep until then.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let mut timers = self.shared.timers.borrow_mut();
+        while let Some(wait) = timers.peek() {
+            if wait.waker.strong_count() > 0 { return Some(wait.at) }
+            timers.pop(); // cancelled
+        }
+        None
+    }
+}
+
+impl Default for FrameScheduler {
+    fn default() -> Self { Self::new() }
+}
+
+impl Drop for FrameScheduler {
+    fn drop(&mut self) { self.remote.close(); }
+}
+
+impl Shared {
+    /// The scheduler running the current task.  Panics if there isn't one.
+    pub(crate) fn current(what: &str) -> Rc<Shared> {
+        CURRENT.with(|c| c.borrow().clone()).unwrap_or_else(|| panic!("{} must be polled by a task running on a FrameScheduler", what))
+    }
+
+    pub(crate) fn frame(&self) -> u64 { self.frame.get() }
+    pub(crate) fn now(&self) -> Instant { self.now.get() }
+}
+
+impl Waiter {
+    pub(crate) fn new(shared: Rc<Shared>, due: Due) -> Self {
+        let waker = Rc::new(RefCell::new(None));
+        let seq = shared.seq.get();
+        shared.seq.set(seq + 1);
+        match due {
+            Due::Frame(at)  => shared.frames.borrow_mut().push(Wait { at, seq, waker: Rc::downgrade(&waker) }),
+            Due::Time(at)   => shared.timers.borrow_mut().push(Wait { at, seq, waker: Rc::downgrade(&waker) }),
+        }
+        Self { shared, due, waker }
+    }
+
+    pub(crate) fn is_due(&self) -> bool {
+        match self.due {
+            Due::Frame(at)  => self.shared.frame.get() >= at,
+            Due::Time(at)   => self.shared.now.get() >= at,
+        }
+    }
+
+    /// Ready if due, otherwise remember `waker` to wake once it is.
+    pub(crate) fn poll(&self, waker: &Waker) -> bool {
+        if self.is_due() { return true }
+        *self.waker.borrow_mut() = Some(waker.clone());
+        false
+    }
+}
+
+impl<K: Ord> PartialEq  for Wait<K> { fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal } }
+impl<K: Ord> Eq         for Wait<K> {}
+impl<K: Ord> PartialOrd for Wait<K> { fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) } }
+impl<K: Ord> Ord        for Wait<K> { fn cmp(&self, other: &Self) -> Ordering { (&other.at, other.seq).cmp(&(&self.at, self.seq)) } } // reversed: BinaryHeap is a max-heap
+
+fn wake_due<K: Ord>(waits: &RefCell<BinaryHeap<Wait<K>>>, now: K) {
+    let mut wakers = Vec::new();
+    {
+        let mut waits = waits.borrow_mut();
+        while waits.peek().is_some_and(|w| w.at <= now) {
+            let wait = waits.pop().unwrap();
+            if let Some(waker) = wait.waker.upgrade().and_then(|w| w.borrow_mut().take()) { wakers.push(waker); }
+        }
+    }
+    for waker in wakers { waker.wake(); }
+}
+
+thread_local! { static CURRENT : RefCell<Option<Rc<Shared>>> = const { RefCell::new(None) }; }
+
+/// Makes a scheduler [`Shared::current`] until dropped.
+struct Current(Option<Rc<Shared>>);
+
+impl Current {
+    fn enter(shared: Rc<Shared>) -> Self { Self(CURRENT.with(|c| c.replace(Some(shared)))) }
+}
+
+impl Drop for Current {
+    fn drop(&mut self) { CURRENT.with(|c| *c.borrow_mut() = self.0.take()); }
+}
+
+
+
+#[test] fn cancelled_waits() {
+    use crate::frame::*;
+    use crate::time::ManualClock;
+    use futures::future::{select, Either};
+    use instant::Duration;
+
+    let clock = Arc::new(ManualClock::new());
+    let scheduler = FrameScheduler::with_clock(clock.clone());
+    let start = scheduler.now();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let l = log.clone();
+    scheduler.spawn_local(async move {
+        let winner = match select(delay(Duration::from_millis(10)), delay(Duration::from_millis(30))).await { Either::Left(_) => "10ms", Either::Right(_) => "30ms" };
+        l.borrow_mut().push(winner);
+        frames(2).await;
+        l.borrow_mut().push("2 frames");
+    }).unwrap();
+
+    assert_eq!(scheduler.next_deadline(), None);
+    scheduler.run_frame();
+    assert_eq!(scheduler.frame(), 1);
+    assert_eq!(scheduler.next_deadline(), Some(start + Duration::from_millis(10)));
+    clock.advance(Duration::from_millis(15));
+    scheduler.run_frame();
+    assert_eq!(*log.borrow(), ["10ms"]);
+    assert_eq!(scheduler.next_deadline(), None, "30ms delay was dropped");
+    scheduler.run_frame();
+    assert_eq!(*log.borrow(), ["10ms"]);
+    scheduler.run_frame();
+    assert_eq!(*log.borrow(), ["10ms", "2 frames"]);
+}
+
+#[test] fn nested_frames() {
+    use crate::frame::*;
+
+    let scheduler = Rc::new(FrameScheduler::new());
+    let ticks = Rc::new(Cell::new(0));
+    let t = ticks.clone();
+    scheduler.spawn_local(async move { loop { t.set(t.get() + 1); next_frame().await; } }).unwrap();
+
+    let nested = Rc::downgrade(&scheduler);
+    scheduler.each_frame(move |args| {
+        if args.frame == 1 { nested.upgrade().unwrap().run_frame(); } // e.g. a modal message loop
+        true
+    });
+    scheduler.run_frame();
+    assert_eq!(scheduler.frame(), 2);
+    assert_eq!(ticks.get(), 2, "tasks should still run in frames nested within each_frame callbacks");
+}
@@ -0,0 +1,209 @@
+use crate::frame::FrameScheduler;
+
+use futures::Future;
+use futures::task::SpawnError;
+
+use std::cell::{Cell, RefCell};
+use std::fmt::{self, Debug, Formatter};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+
+
+/// A task spawned with [`FrameScheduler::spawn`].  Dropping the handle cancels the task, unless it's been [`detach`](Self::detach)ed.
+///
+/// Cancelled tasks are dropped the next time the scheduler runs, without being polled again.
+#[must_use = "dropping a TaskHandle cancels the task"]
+pub struct TaskHandle {
+    task:       Rc<TaskState>,
+    detached:   bool,
+}
+
+/// Cancels a group of tasks together: when [`cancel`](Self::cancel)ed or dropped (e.g. along with the window or level that owns it.)
+///
+/// ```
+/// # use kakistocracy::frame::*;
+/// let scheduler = FrameScheduler::new();
+/// let level = TaskScope::new();
+/// level.spawn(&scheduler, async { loop { next_frame().await; /* ...animate... */ } }).unwrap();
+/// level.spawn(&scheduler, async { loop { next_frame().await; /* ...more... */ } }).unwrap();
+/// scheduler.run_frame();
+/// assert_eq!(level.running(), 2);
+/// assert_eq!(scheduler.running_tasks(), 2);
+///
+/// drop(level); // leave the level
+/// scheduler.run_frame();
+/// assert_eq!(scheduler.running_tasks(), 0);
+/// ```
+#[derive(Default)]
+pub struct TaskScope {
+    cancelled:  Rc<Cell<bool>>,
+    tasks:      RefCell<Vec<Rc<TaskState>>>,
+}
+
+#[derive(Default)]
+struct TaskState {
+    cancelled:  Cell<bool>,
+    finished:   Cell<bool>,
+    waker:      RefCell<Option<Waker>>,
+}
+
+/// A task that stops early if cancelled.
+struct Cancellable {
+    task:   Rc<TaskState>,
+    future: Pin<Box<dyn Future<Output = ()>>>,
+}
+
+impl FrameScheduler {
+    /// Run a future/task on this scheduler, like [`spawn_local`](Self::spawn_local), but cancel it when the returned handle is dropped.
+    pub fn spawn<F: Future<Output = ()> + 'static>(&self, f: F) -> Result<TaskHandle, SpawnError> {
+        let task = Rc::new(TaskState::default());
+        self.spawn_local(Cancellable { task: task.clone(), future: Box::pin(f) })?;
+        Ok(TaskHandle { task, detached: false })
+    }
+}
+
+impl TaskHandle {
+    /// Stop the task early.  Does nothing if it's already finished.
+    pub fn cancel(&self) { self.task.cancel() }
+
+    /// Let the task run to completion, even though the handle's been dropped.
+    pub fn detach(mut self) { self.detached = true; }
+
+    pub fn is_cancelled(&self) -> bool { self.task.cancelled.get() }
+
+    /// `true` if the task ran to completion.
+    pub fn is_finished(&self) -> bool { self.task.finished.get() }
+
+    /// `true` if the task has neither finished nor been cancelled.
+    pub fn is_running(&self) -> bool { self.task.is_running() }
+}
+
+impl Drop for TaskHandle {
+    fn drop(&mut self) { if !self.detached { self.task.cancel(); } }
+}
+
+impl Debug for TaskHandle {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "TaskHandle({})", if self.is_finished() { "finished" } else if self.is_cancelled() { "cancelled" } else { "running" })
+    }
+}
+
+impl TaskScope {
+    pub fn new() -> Self { Self::default() }
+
+    /// Spawn `f` on `scheduler`, cancelling it along with the rest of this scope.
+    /// If the scope has already been cancelled, `f` is dropped without being run.
+    pub fn spawn<F: Future<Output = ()> + 'static>(&self, scheduler: &FrameScheduler, f: F) -> Result<(), SpawnError> {
+        let handle = scheduler.spawn(f)?;
+        self.add(handle);
+        Ok(())
+    }
+
+    /// Take ownership of an already spawned task, cancelling it along with the rest of this scope.
+    pub fn add(&self, mut handle: TaskHandle) {
+        handle.detached = true;
+        if self.cancelled.get() { return handle.task.cancel() }
+        let mut tasks = self.tasks.borrow_mut();
+        tasks.retain(|t| t.is_running());
+        tasks.push(handle.task.clone());
+    }
+
+    /// Wrap an `each_frame`-style callback (run until it returns `false`) so it also stops once this scope is cancelled.
+    pub fn bind<A: ?Sized>(&self, mut f: impl FnMut(&A) -> bool) -> impl FnMut(&A) -> bool {
+        let cancelled = self.cancelled.clone();
+        move |args| !cancelled.get() && f(args)
+    }
+
+    /// Cancel every task in this scope, including any added later.
+    pub fn cancel(&self) {
+        self.cancelled.set(true);
+        for task in std::mem::take(&mut *self.tasks.borrow_mut()) { task.cancel(); }
+    }
+
+    pub fn is_cancelled(&self) -> bool { self.cancelled.get() }
+
+    /// The number of tasks in this scope that have neither finished nor been cancelled.  Useful for leak checks.
+    pub fn running(&self) -> usize { self.tasks.borrow().iter().filter(|t| t.is_running()).count() }
+}
+
+impl Drop for TaskScope {
+    fn drop(&mut self) { self.cancel(); }
+}
+
+impl Debug for TaskScope {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "TaskScope({} running{})", self.running(), if self.is_cancelled() { ", cancelled" } else { "" })
+    }
+}
+
+impl TaskState {
+    fn is_running(&self) -> bool { !self.cancelled.get() && !self.finished.get() }
+
+    fn cancel(&self) {
+        if self.finished.get() { return }
+        self.cancelled.set(true);
+        let waker = self.waker.borrow_mut().take(); // so the scheduler notices, and drops the task
+        if let Some(waker) = waker { waker.wake(); }
+    }
+}
+
+impl Future for Cancellable {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.task.cancelled.get() { return Poll::Ready(()) }
+        *self.task.waker.borrow_mut() = Some(cx.waker().clone());
+        let poll = self.future.as_mut().poll(cx);
+        if poll.is_ready() { self.task.finished.set(true); }
+        poll
+    }
+}
+
+
+
+#[test] fn cancellation() {
+    use crate::frame::*;
+
+    struct DropFlag(Rc<Cell<bool>>);
+    impl Drop for DropFlag { fn drop(&mut self) { self.0.set(true); } }
+
+    let scheduler = FrameScheduler::new();
+    let dropped = Rc::new(Cell::new(false));
+    let flag = DropFlag(dropped.clone());
+    let forever = scheduler.spawn(async move { let _flag = flag; loop { next_frame().await; } }).unwrap();
+    let quick = scheduler.spawn(async {}).unwrap();
+    let frames = Rc::new(Cell::new(0));
+    let f = frames.clone();
+    scheduler.spawn(async move { loop { f.set(f.get() + 1); next_frame().await; } }).unwrap().detach();
+
+    scheduler.run_frame();
+    assert!(quick.is_finished() && !quick.is_running());
+    assert!(forever.is_running());
+    assert_eq!(scheduler.running_tasks(), 2);
+
+    drop(forever);
+    assert!(!dropped.get(), "dropped by the scheduler, not the handle");
+    scheduler.run_frame();
+    assert!(dropped.get());
+    assert_eq!(scheduler.running_tasks(), 1);
+    assert_eq!(frames.get(), 2, "detached task kept running");
+
+    let scope = TaskScope::new();
+    let calls = Rc::new(Cell::new(0));
+    let c = calls.clone();
+    let mut each_frame = scope.bind(move |_: &()| { c.set(c.get() + 1); true });
+    scope.spawn(&scheduler, async { loop { next_frame().await; } }).unwrap();
+    scope.add(scheduler.spawn(async { loop { next_frame().await; } }).unwrap());
+    scheduler.run_frame();
+    assert!(each_frame(&()));
+    assert_eq!((scope.running(), scheduler.running_tasks()), (2, 3));
+
+    scope.cancel();
+    assert!(!each_frame(&()));
+    assert_eq!(calls.get(), 1);
+    assert_eq!(scope.running(), 0);
+    scope.spawn(&scheduler, async { panic!("spawned into a cancelled scope") }).unwrap();
+    scheduler.run_frame();
+    assert_eq!(scheduler.running_tasks(), 1);
+}
@@ -0,0 +1,138 @@
+use futures::Future;
+
+use std::fmt::{self, Debug, Formatter};
+use std::panic::{AssertUnwindSafe, catch_unwind, resume_unwind};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, mpsc};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+
+
+
+/// A fixed set of background threads to run work on, off the frame loop.
+///
+/// [`spawn`](Self::spawn) returns a [`Background`] future, so a task on a [`FrameScheduler`](crate::frame::FrameScheduler) can hand work off and resume with the result back on its own thread:
+///
+/// ```
+/// # use kakistocracy::frame::*;
+/// let pool = ThreadPool::new("example", 2);
+/// let scheduler = FrameScheduler::new();
+/// let work = pool.spawn(|| (1 ..= 100).sum::<u32>());
+/// scheduler.spawn_local(async move {
+///     let sum = work.await; // back on the loop thread
+///     assert_eq!(sum, 5050);
+/// }).unwrap();
+/// # drop(pool); // finish the work
+/// # scheduler.run_frame();
+/// ```
+pub struct ThreadPool {
+    jobs:       Option<mpsc::Sender<Job>>,
+    threads:    Vec<JoinHandle<()>>,
+}
+
+/// The result of [`ThreadPool::spawn`]: resolves to the result on whichever thread polls it.  Panics are resumed on that thread.
+#[must_use = "futures do nothing unless polled"]
+pub struct Background<T>(Arc<Mutex<BackgroundState<T>>>);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct BackgroundState<T> {
+    result: Option<std::thread::Result<T>>,
+    waker:  Option<Waker>,
+}
+
+impl ThreadPool {
+    /// Spawn `threads` (at least 1) background threads, named `"{name} #1"`, `"{name} #2"`, etc.
+    pub fn new(name: &str, threads: usize) -> Self {
+        let (send, recv) = mpsc::channel::<Job>();
+        let recv = Arc::new(Mutex::new(recv));
+        let threads = (0 .. threads.max(1)).map(|i| {
+            let recv = recv.clone();
+            std::thread::Builder::new().name(format!("{} #{}", name, i+1)).spawn(move || loop {
+                let job = recv.lock().unwrap().recv(); // lock released before running the job
+                match job { Ok(job) => job(), Err(_) => return }
+            }).expect("unable to spawn ThreadPool thread")
+        }).collect();
+        Self { jobs: Some(send), threads }
+    }
+
+    pub fn threads(&self) -> usize { self.threads.len() }
+
+    /// Run `f` on a background thread.  Panics are caught (and discarded) so they don't take a thread down with them.
+    pub fn execute(&self, f: impl FnOnce() + Send + 'static) {
+        let job = Box::new(move || { let _ = catch_unwind(AssertUnwindSafe(f)); });
+        self.jobs.as_ref().unwrap().send(job).expect("ThreadPool threads have died");
+    }
+
+    /// Run `f` on a background thread, returning a future for its result.
+    pub fn spawn<T: Send + 'static>(&self, f: impl FnOnce() -> T + Send + 'static) -> Background<T> {
+        let state = Arc::new(Mutex::new(BackgroundState { result: None, waker: None }));
+        let s = state.clone();
+        self.execute(move || {
+            let result = catch_unwind(AssertUnwindSafe(f));
+            let waker = {
+                let mut s = s.lock().unwrap();
+                s.result = Some(result);
+                s.waker.take()
+            };
+            if let Some(waker) = waker { waker.wake(); }
+        });
+        Background(state)
+    }
+}
+
+impl Drop for ThreadPool {
+    /// Finishes any queued work before returning.
+    fn drop(&mut self) {
+        drop(self.jobs.take());
+        for thread in self.threads.drain(..) { let _ = thread.join(); }
+    }
+}
+
+impl Debug for ThreadPool {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result { write!(fmt, "ThreadPool({} threads)", self.threads.len()) }
+}
+
+impl<T> Background<T> {
+    /// `true` once the work has finished (and the result hasn't yet been taken.)
+    pub fn is_finished(&self) -> bool { self.0.lock().unwrap().result.is_some() }
+}
+
+impl<T> Future for Background<T> {
+    type Output = T;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.0.lock().unwrap();
+        match state.result.take() {
+            Some(Ok(value)) => Poll::Ready(value),
+            Some(Err(panic)) => { drop(state); resume_unwind(panic) },
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+}
+
+impl<T> Debug for Background<T> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result { write!(fmt, "Background({})", if self.is_finished() { "finished" } else { "pending" }) }
+}
+
+
+
+#[test] fn background() {
+    use futures::executor::block_on;
+
+    let pool = ThreadPool::new("test", 2);
+    assert_eq!(pool.threads(), 2);
+    let (go, wait) = mpsc::channel::<()>();
+    let slow = pool.spawn(move || { wait.recv().unwrap(); std::thread::current().name().unwrap().to_string() });
+    assert!(!slow.is_finished());
+    go.send(()).unwrap();
+    assert!(block_on(slow).starts_with("test #"));
+
+    pool.execute(|| panic!("discarded"));
+    let panics = pool.spawn(|| -> u32 { panic!("resumed") });
+    let panic = catch_unwind(AssertUnwindSafe(|| block_on(panics))).unwrap_err();
+    assert_eq!(panic.downcast_ref::<&str>(), Some(&"resumed"));
+    assert_eq!(block_on(pool.spawn(|| 42)), 42, "threads survived panics");
+}
@@ -0,0 +1,145 @@
+use super::scheduler::{Due, Shared, Waiter};
+
+use futures::Future;
+use instant::Duration;
+
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+
+
+/// Wait until the next frame of the [`FrameScheduler`](crate::frame::FrameScheduler) running the current task.
+///
+/// ```
+/// # use kakistocracy::frame::*;
+/// # async fn script() {
+/// for i in 0 .. 60 {
+///     // ...fade in a little...
+///     next_frame().await;
+/// }
+/// # }
+/// ```
+pub fn next_frame() -> Frames { frames(1) }
+
+/// Wait `n` frames.  `frames(0)` is immediately ready.
+pub fn frames(n: u64) -> Frames { Frames { frames: n, waiter: None } }
+
+/// Wait until the first frame starting at least `duration` after the frame this is first polled in.  `delay(Duration::ZERO)` is immediately ready.
+pub fn delay(duration: Duration) -> Delay { Delay { duration, waiter: None } }
+
+/// Wait for `future`, giving up after `duration` (see [`delay`].)
+pub fn timeout<F: Future>(future: F, duration: Duration) -> Timeout<F> { Timeout { future: Box::pin(future), delay: delay(duration) } }
+
+/// A future returned by [`next_frame`] or [`frames`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Frames {
+    frames: u64,
+    waiter: Option<Waiter>,
+}
+
+/// A future returned by [`delay`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Delay {
+    duration:   Duration,
+    waiter:     Option<Waiter>,
+}
+
+/// A future returned by [`timeout`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Timeout<F: Future> {
+    future: Pin<Box<F>>,
+    delay:  Delay,
+}
+
+/// The error returned by a [`timeout`] that elapsed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimedOut;
+
+impl Future for Frames {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let this = self.get_mut();
+        if this.frames == 0 { return Poll::Ready(()) }
+        let frames = this.frames;
+        let waiter = this.waiter.get_or_insert_with(|| {
+            let shared = Shared::current("frames");
+            let due = Due::Frame(shared.frame() + frames);
+            Waiter::new(shared, due)
+        });
+        if waiter.poll(cx.waker()) { Poll::Ready(()) } else { Poll::Pending }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let this = self.get_mut();
+        if this.duration.is_zero() { return Poll::Ready(()) }
+        let duration = this.duration;
+        let waiter = this.waiter.get_or_insert_with(|| {
+            let shared = Shared::current("delay");
+            let due = Due::Time(shared.now() + duration);
+            Waiter::new(shared, due)
+        });
+        if waiter.poll(cx.waker()) { Poll::Ready(()) } else { Poll::Pending }
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, TimedOut>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Poll::Ready(output) = this.future.as_mut().poll(cx) { return Poll::Ready(Ok(output)) }
+        match Pin::new(&mut this.delay).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(TimedOut)),
+            Poll::Pending   => Poll::Pending,
+        }
+    }
+}
+
+impl Debug for Frames   { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { write!(fmt, "Frames({})", self.frames) } }
+impl Debug for Delay    { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { write!(fmt, "Delay({:?})", self.duration) } }
+
+impl Display for TimedOut { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { write!(fmt, "timed out") } }
+impl Error for TimedOut {}
+
+
+
+#[test] fn timeouts() {
+    use crate::frame::FrameScheduler;
+    use crate::time::ManualClock;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    let ms = Duration::from_millis;
+    let clock = Arc::new(ManualClock::new());
+    let scheduler = FrameScheduler::with_clock(clock.clone());
+    let results = Rc::new(RefCell::new(Vec::new()));
+
+    let r = results.clone();
+    scheduler.spawn_local(async move { let x = timeout(async { frames(2).await; 42 }, ms(100)).await; r.borrow_mut().push(x); }).unwrap();
+    let r = results.clone();
+    scheduler.spawn_local(async move { let x = timeout(async { delay(ms(200)).await; 42 }, ms(100)).await; r.borrow_mut().push(x); }).unwrap();
+    let r = results.clone();
+    scheduler.spawn_local(async move { let x = timeout(async { 1 }, Duration::ZERO).await; r.borrow_mut().push(x); }).unwrap();
+
+    scheduler.run_frame();
+    assert_eq!(*results.borrow(), [Ok(1)]);
+    scheduler.run_frame();
+    assert_eq!(*results.borrow(), [Ok(1)]);
+    scheduler.run_frame();
+    assert_eq!(*results.borrow(), [Ok(1), Ok(42)]);
+    clock.advance(ms(99));
+    scheduler.run_frame();
+    assert_eq!(results.borrow().len(), 2);
+    clock.advance(ms(1));
+    scheduler.run_frame();
+    assert_eq!(*results.borrow(), [Ok(1), Ok(42), Err(TimedOut)]);
+}
+
+#[test] #[should_panic = "must be polled by a task running on a FrameScheduler"] fn outside_scheduler() {
+    futures::executor::block_on(next_frame());
+}
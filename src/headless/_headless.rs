@@ -0,0 +1,4 @@
+//! Platform-neutral equivalents of `windows` functionality, for dedicated servers, tools, and CI: a [`message`] loop, and a [`CpuRenderTarget`] to render sprites to
+
+pub mod message;
+mod render;                     pub use render::*;
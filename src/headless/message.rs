@@ -0,0 +1,139 @@
+//! A headless equivalent of `windows::message`: the same frame loop, tasks, `each_frame` callbacks, and quit semantics, without any windows.
+//!
+//! ```
+//! use kakistocracy::frame::frames;
+//! use kakistocracy::headless::message;
+//!
+//! message::frame_pacer(|pacer| pacer.set_target(None)); // as fast as possible
+//! message::spawn_local(async {
+//!     frames(3).await;
+//!     message::post_quit(42);
+//! }).unwrap();
+//! assert_eq!(message::loop_until_quit(), 42);
+//! ```
+
+use crate::frame::{FrameScheduler, LoopHandle, TaskHandle};
+pub use crate::frame::EachFrameArgs;
+use crate::time::FramePacer;
+
+use futures::Future;
+use futures::task::*;
+
+use std::cell::{Cell, RefCell};
+
+
+
+/// Run a frame loop on this thread until [`post_quit`] is called.
+///
+/// Returns the exit code that was passed to [`post_quit`].
+pub fn loop_until_quit() -> i32 {
+    loop {
+        if let Some(exit) = loop_one_frame() {
+            return exit;
+        }
+    }
+}
+
+/// Run a frame loop on this thread once, then wait out the rest of the frame (see [`frame_pacer`].)
+///
+/// If [`post_quit`] was called (before this frame), returns `Some(exit_code)` without running the frame.
+pub fn loop_one_frame() -> Option<i32> {
+    TL.with(|tl|{
+        if let Some(exit) = tl.quit.take() { return Some(exit) }
+
+        tl.scheduler.run_frame(); // tasks & each_frame callbacks (no-op if we're recursively running a frame loop within either)
+
+        if let Ok(mut pacer) = tl.pacer.try_borrow_mut() {
+            pacer.wait();
+        }
+        None
+    })
+}
+
+/// Make the next [`loop_one_frame`] on this thread return `Some(exit_code)`, ending [`loop_until_quit`].
+///
+/// To quit from another thread, [`post`](LoopHandle::post) a call to this via [`handle`].
+pub fn post_quit(exit_code: i32) {
+    TL.with(|tl| tl.quit.set(Some(exit_code)));
+}
+
+/// Run a future/task in the current thread's frame loop
+pub fn spawn_local<F: Future<Output = ()> + 'static>(f: F) -> Result<(), SpawnError> {
+    TL.with(|tl| tl.scheduler.spawn_local(f))
+}
+
+/// Run a future/task in the current thread's frame loop, until it finishes or the returned handle is dropped.
+pub fn spawn<F: Future<Output = ()> + 'static>(f: F) -> Result<TaskHandle, SpawnError> {
+    TL.with(|tl| tl.scheduler.spawn(f))
+}
+
+/// A [`Send`]able handle for posting closures and futures onto the current thread's frame loop from other threads.
+pub fn handle() -> LoopHandle {
+    TL.with(|tl| tl.scheduler.handle())
+}
+
+/// Run logic in the current thread's frame loop "each frame", paced by [`frame_pacer`] (60 fps by default.)
+///
+/// If the callback ever returns `false`, it will be unregistered and not called again.
+pub fn each_frame(f: impl 'static + FnMut(&EachFrameArgs) -> bool) {
+    TL.with(|tl| tl.scheduler.each_frame(f));
+}
+
+/// Run `f` with the current thread's [`FrameScheduler`], e.g. to [`set_time_scale`](FrameScheduler::set_time_scale) or [`set_paused`](FrameScheduler::set_paused).
+pub fn scheduler<R>(f: impl FnOnce(&FrameScheduler) -> R) -> R {
+    TL.with(|tl| f(&tl.scheduler))
+}
+
+/// Configure the [`FramePacer`] the current thread's frame loop waits on each frame (60 fps by default.)
+pub fn frame_pacer<R>(f: impl FnOnce(&mut FramePacer) -> R) -> R {
+    TL.with(|tl| f(&mut tl.pacer.borrow_mut()))
+}
+
+
+
+#[derive(Default)]
+struct ThreadLocal {
+    scheduler:          FrameScheduler,
+    pacer:              RefCell<FramePacer>,
+    quit:               Cell<Option<i32>>,
+}
+
+thread_local! { static TL : ThreadLocal = ThreadLocal::default(); }
+
+
+
+#[test] fn quit_codes() {
+    for code in [-9001, 0, 42].iter().copied() {
+        assert_eq!(code, std::thread::spawn(move || {
+            post_quit(code);
+            loop_until_quit()
+        }).join().unwrap());
+    }
+}
+
+#[test] fn frame_loop() {
+    use crate::frame::frames;
+    use std::rc::Rc;
+
+    std::thread::spawn(|| {
+        frame_pacer(|pacer| pacer.set_target(None));
+        let each = Rc::new(Cell::new(0));
+        let e = each.clone();
+        each_frame(move |args| { e.set(args.frame); true });
+        spawn_local(async { frames(3).await; post_quit(7); }).unwrap();
+        let cancelled = spawn(async { frames(2).await; unreachable!() }).unwrap();
+        drop(cancelled);
+        assert_eq!(loop_until_quit(), 7);
+        assert_eq!(each.get(), 4, "the frame post_quit was called in finishes");
+        assert_eq!(scheduler(|s| s.running_tasks()), 0);
+    }).join().unwrap();
+
+    let (send, recv) = std::sync::mpsc::channel();
+    let looper = std::thread::spawn(move || {
+        frame_pacer(|pacer| pacer.set_target(Some(std::time::Duration::from_millis(1))));
+        send.send(handle()).unwrap();
+        loop_until_quit()
+    });
+    recv.recv().unwrap().post(|| post_quit(3)).unwrap();
+    assert_eq!(looper.join().unwrap(), 3, "quit from another thread");
+}
@@ -0,0 +1,125 @@
+use crate::image::Image;
+use crate::sprite::{Instance, private};
+use crate::texture::{CpuTextureDevice, TextureCache, TextureSource};
+
+use std::fmt::{self, Debug, Formatter};
+
+
+
+/// A software [`sprite::RenderTarget`](crate::sprite::RenderTarget): renders sprites into an [`Image`], for headless runs and tests.
+///
+/// Like the Direct3D targets, pixel centers are at half-pixel offsets (the top left pixel covers `0.0 .. 1.0`.)
+/// Textures are sampled nearest-neighbor, and alpha blended over what's already there.  Z is ignored: later sprites draw over earlier ones.
+///
+/// ```
+/// # use kakistocracy::headless::CpuRenderTarget;
+/// # use kakistocracy::sprite::{self, Instance};
+/// let mut target = CpuRenderTarget::new(64, 64);
+/// target.clear([0, 0, 0, 255]);
+/// let d3d = kakistocracy::include_file!(CARGO_MANIFEST_DIR / "examples/d3d-16x9.png");
+/// let sprite = Instance { anchor: [32.0, 32.0, 0.0], rotation: 0.0, dimensions: [-16.0 .. 16.0, -9.0 .. 9.0], texcoords: [0.0 .. 1.0, 0.0 .. 1.0] };
+/// unsafe { sprite::render1(&mut target, &d3d, &[sprite]) };
+/// assert_ne!(target.image().get(32, 32), Some([0, 0, 0, 255]));
+/// ```
+pub struct CpuRenderTarget {
+    image:      Image,
+    textures:   TextureCache<CpuTextureDevice>,
+}
+
+impl CpuRenderTarget {
+    /// A `width` x `height` target, initially transparent black.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { image: Image::new(width, height), textures: TextureCache::new(CpuTextureDevice).expect("TextureCache::new(CpuTextureDevice) failed") }
+    }
+
+    pub fn image(&self) -> &Image { &self.image }
+    pub fn into_image(self) -> Image { self.image }
+    pub fn dimensions(&self) -> (u32, u32) { self.image.dimensions() }
+
+    /// The cache textures are loaded through.  [`next_frame`](TextureCache::next_frame) is left to the caller.
+    pub fn textures(&self) -> &TextureCache<CpuTextureDevice> { &self.textures }
+
+    /// Fill the entire target with `rgba`.
+    pub fn clear(&mut self, rgba: [u8; 4]) {
+        for pixel in self.image.pixels_mut() { *pixel = rgba; }
+    }
+
+    /// Resize the target, clearing it to transparent black.
+    pub fn resize(&mut self, width: u32, height: u32) { self.image = Image::new(width, height); }
+
+    fn draw(&mut self, texture: TextureSource, instances: &[Instance]) {
+        if instances.is_empty() { return }
+        let texture = self.textures.get_texture_2d(texture);
+        let (tw, th) = texture.dimensions();
+        let (w, h) = self.image.dimensions();
+        if tw == 0 || th == 0 || w == 0 || h == 0 { return }
+
+        for instance in instances.iter() {
+            let [ax, ay, _az] = instance.anchor;
+            let [x, y] = instance.dimensions.clone();
+            let [u, v] = instance.texcoords.clone();
+            let (sin, cos) = instance.rotation.sin_cos();
+
+            // bounding box of the rotated quad, in pixels
+            let corners = [[x.start, y.start], [x.end, y.start], [x.end, y.end], [x.start, y.end]].iter().map(|&[x, y]| [ax + x * cos - y * sin, ay + y * cos + x * sin]).collect::<Vec<_>>();
+            let min_x = corners.iter().map(|c| c[0]).fold(f32::INFINITY, f32::min).floor().max(0.0) as u32;
+            let max_x = corners.iter().map(|c| c[0]).fold(f32::NEG_INFINITY, f32::max).ceil().min(w as f32) as u32;
+            let min_y = corners.iter().map(|c| c[1]).fold(f32::INFINITY, f32::min).floor().max(0.0) as u32;
+            let max_y = corners.iter().map(|c| c[1]).fold(f32::NEG_INFINITY, f32::max).ceil().min(h as f32) as u32;
+
+            for py in min_y .. max_y {
+                for px in min_x .. max_x {
+                    // pixel center, back into the sprite's unrotated frame
+                    let (dx, dy) = (px as f32 + 0.5 - ax, py as f32 + 0.5 - ay);
+                    let (lx, ly) = (dx * cos + dy * sin, dy * cos - dx * sin);
+                    let (tx, ty) = ((lx - x.start) / (x.end - x.start), (ly - y.start) / (y.end - y.start));
+                    if !(0.0 .. 1.0).contains(&tx) || !(0.0 .. 1.0).contains(&ty) { continue }
+
+                    let (su, sv) = (u.start + (u.end - u.start) * tx, v.start + (v.end - v.start) * ty);
+                    let texel = texture.get(((su * tw as f32) as u32).min(tw-1), ((sv * th as f32) as u32).min(th-1)).unwrap();
+                    let dst = self.image.get(px, py).unwrap();
+                    self.image.set(px, py, blend(texel, dst));
+                }
+            }
+        }
+    }
+}
+
+impl private::RenderTarget for &mut CpuRenderTarget {
+    unsafe fn render1(&mut self, texture: TextureSource, instances: &[Instance]) { self.draw(texture, instances) }
+}
+
+impl Debug for CpuRenderTarget {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result { write!(fmt, "CpuRenderTarget({}x{})", self.image.width(), self.image.height()) }
+}
+
+/// `src` over `dst`
+fn blend(src: [u8; 4], dst: [u8; 4]) -> [u8; 4] {
+    let a = u32::from(src[3]);
+    let mix = |s: u8, d: u8| ((u32::from(s) * a + u32::from(d) * (255 - a) + 127) / 255) as u8;
+    [mix(src[0], dst[0]), mix(src[1], dst[1]), mix(src[2], dst[2]), (a + (u32::from(dst[3]) * (255 - a) + 127) / 255) as u8]
+}
+
+
+
+#[test] fn software_sprites() {
+    use crate::sprite::render1;
+    use crate::texture::RuntimeTexture;
+
+    let checker = RuntimeTexture::new("checker", Image::from_fn(2, 2, |x, y| if x == y { [255, 0, 0, 255] } else { [0, 0, 255, 128] }));
+    let mut target = CpuRenderTarget::new(8, 4);
+    target.clear([0, 0, 0, 255]);
+    let quad = |anchor: [f32; 2], rotation: f32| Instance { anchor: [anchor[0], anchor[1], 0.0], rotation, dimensions: [0.0 .. 4.0, 0.0 .. 4.0], texcoords: [0.0 .. 1.0, 0.0 .. 1.0] };
+    unsafe { render1(&mut target, &checker, &[quad([0.0, 0.0], 0.0)]) };
+    assert_eq!(target.image().get(0, 0), Some([255, 0, 0, 255]));
+    assert_eq!(target.image().get(3, 0), Some([0, 0, 128, 255]), "alpha blended over black");
+    assert_eq!(target.image().get(3, 3), Some([255, 0, 0, 255]));
+    assert_eq!(target.image().get(4, 0), Some([0, 0, 0, 255]), "outside the sprite");
+
+    // rotated a quarter turn clockwise around its top left corner, then moved right: the same pixels, flipped
+    target.clear([0, 0, 0, 255]);
+    unsafe { render1(&mut target, &checker, &[quad([8.0, 0.0], std::f32::consts::FRAC_PI_2)]) };
+    assert_eq!(target.image().get(7, 0), Some([255, 0, 0, 255]));
+    assert_eq!(target.image().get(4, 0), Some([0, 0, 128, 255]));
+    assert_eq!(target.image().get(3, 0), Some([0, 0, 0, 255]));
+}
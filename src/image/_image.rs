@@ -0,0 +1,6 @@
+//! [`Image`]s, diagnostic [`Placeholder`]s, and [`bcn`] texture (de)compression
+
+#[path = "bcn/_bcn.rs"] pub mod bcn;
+
+mod buffer;                     pub use buffer::*;
+mod placeholder;                pub use placeholder::*;
@@ -0,0 +1,292 @@
+//! Software [BCn](https://docs.microsoft.com/en-us/windows/win32/direct3d11/texture-block-compression-in-direct3d-11)
+//! (a.k.a. DXTn / S3TC / BPTC) texture compression and decompression.
+//!
+//! | Format            | Encode    | Decode    | Decodes to                                    |
+//! | ----------------- | --------- | --------- | --------------------------------------------- |
+//! | [`Format::Bc1`]   | ✔️        | ✔️        | RGB + 1-bit alpha                             |
+//! | [`Format::Bc2`]   | ❌        | ✔️        | RGB + 4-bit explicit alpha                    |
+//! | [`Format::Bc3`]   | ✔️        | ✔️        | RGB + interpolated alpha                      |
+//! | [`Format::Bc4`]   | ✔️        | ✔️        | `[r, 0, 0, 255]` (encodes red only)           |
+//! | [`Format::Bc5`]   | ✔️        | ✔️        | `[r, g, 0, 255]` (encodes red + green only)   |
+//! | [`Format::Bc6hUf16`] / [`Format::Bc6hSf16`] | ❌ | ✔️ | HDR RGB, clamped to `0.0 ..= 1.0` (see [`decompress_bc6h`]) |
+//! | [`Format::Bc7`]   | ❌        | ✔️        | RGBA                                          |
+//!
+//! Images whose dimensions aren't a multiple of 4 are padded by repeating edge pixels when compressing,
+//! and the padding is discarded when decompressing.
+
+mod bc1;
+mod bc4;
+mod bc6h;
+mod bc7;
+mod bits;
+
+use crate::image::Image;
+
+use std::convert::*;
+use std::fmt::{self, Display, Formatter};
+
+
+
+/// A block compressed format
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Format {
+    /// `DXGI_FORMAT_BC1_UNORM` / `D3DFMT_DXT1`
+    Bc1,
+    /// `DXGI_FORMAT_BC2_UNORM` / `D3DFMT_DXT3`
+    Bc2,
+    /// `DXGI_FORMAT_BC3_UNORM` / `D3DFMT_DXT5`
+    Bc3,
+    /// `DXGI_FORMAT_BC4_UNORM`
+    Bc4,
+    /// `DXGI_FORMAT_BC5_UNORM`
+    Bc5,
+    /// `DXGI_FORMAT_BC6H_UF16`
+    Bc6hUf16,
+    /// `DXGI_FORMAT_BC6H_SF16`
+    Bc6hSf16,
+    /// `DXGI_FORMAT_BC7_UNORM`
+    Bc7,
+}
+
+impl Format {
+    /// The size of a single 4x4 block of this format, in bytes (8 or 16)
+    pub fn block_bytes(self) -> usize {
+        match self {
+            Format::Bc1 | Format::Bc4 => 8,
+            _other                    => 16,
+        }
+    }
+
+    /// The number of bytes required to store a `width` x `height` image in this format.
+    pub fn compressed_size(self, width: u32, height: u32) -> usize {
+        blocks(width) * blocks(height) * self.block_bytes()
+    }
+
+    /// `true` if [`compress`] supports this format
+    pub fn can_compress(self) -> bool {
+        matches!(self, Format::Bc1 | Format::Bc3 | Format::Bc4 | Format::Bc5)
+    }
+}
+
+/// How much effort [`compress`] should spend searching for good endpoints
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Quality {
+    /// Bounding box endpoints.  Quick, suitable for runtime compression.
+    Fast,
+    /// Principal axis endpoints, refined once.
+    #[default] Normal,
+    /// Principal axis endpoints, refined iteratively, trying every block mode.  Suitable for build steps.
+    Best,
+}
+
+/// An error compressing or decompressing BCn data
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// [`compress`] doesn't (yet?) support encoding this format
+    UnsupportedEncoderFormat(Format),
+    /// [`decompress`] was given fewer bytes than [`Format::compressed_size`] requires
+    DataTooShort { expected: usize, actual: usize },
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnsupportedEncoderFormat(format)     => write!(fmt, "compressing to {:?} is not supported", format),
+            Error::DataTooShort { expected, actual }    => write!(fmt, "expected at least {} bytes of BCn data, got {}", expected, actual),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+
+
+/// Compress `image` into `format` blocks, row by row.
+///
+/// Returns [`Error::UnsupportedEncoderFormat`] unless <code>format.[can_compress](Format::can_compress)()</code>.
+pub fn compress(image: &Image, format: Format, quality: Quality) -> Result<Vec<u8>, Error> {
+    if !format.can_compress() { return Err(Error::UnsupportedEncoderFormat(format)) }
+
+    let (w, h) = image.dimensions();
+    let mut out = Vec::with_capacity(format.compressed_size(w, h));
+    for by in 0 .. blocks(h) as u32 {
+        for bx in 0 .. blocks(w) as u32 {
+            let block = read_block(image, bx, by);
+            match format {
+                Format::Bc1 => out.extend_from_slice(&bc1::encode(&block, true, quality)),
+                Format::Bc3 => {
+                    out.extend_from_slice(&bc4::encode(&channel(&block, 3), quality));
+                    out.extend_from_slice(&bc1::encode(&block, false, quality));
+                },
+                Format::Bc4 => out.extend_from_slice(&bc4::encode(&channel(&block, 0), quality)),
+                Format::Bc5 => {
+                    out.extend_from_slice(&bc4::encode(&channel(&block, 0), quality));
+                    out.extend_from_slice(&bc4::encode(&channel(&block, 1), quality));
+                },
+                _other => unreachable!("Format::can_compress() returned true for {:?}", format),
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Decompress `width` x `height` pixels worth of `format` blocks into an [`Image`].
+///
+/// HDR formats ([`Format::Bc6hUf16`], [`Format::Bc6hSf16`]) are clamped to `0.0 ..= 1.0`.
+/// Use [`decompress_bc6h`] to preserve the full range.
+pub fn decompress(data: &[u8], width: u32, height: u32, format: Format) -> Result<Image, Error> {
+    let data = check_size(data, width, height, format)?;
+    let mut image = Image::new(width, height);
+    for (i, block) in data.chunks_exact(format.block_bytes()).enumerate() {
+        let decoded = match format {
+            Format::Bc1         => bc1::decode(block.try_into().unwrap(), true),
+            Format::Bc2         => {
+                let mut rgba = bc1::decode(block[8..].try_into().unwrap(), false);
+                for (i, px) in rgba.iter_mut().enumerate() {
+                    let a = (block[i/2] >> (4 * (i % 2))) & 0xF;
+                    px[3] = a << 4 | a;
+                }
+                rgba
+            },
+            Format::Bc3         => {
+                let mut rgba = bc1::decode(block[8..].try_into().unwrap(), false);
+                let alpha = bc4::decode(block[..8].try_into().unwrap());
+                for (px, a) in rgba.iter_mut().zip(alpha.iter().copied()) { px[3] = a; }
+                rgba
+            },
+            Format::Bc4         => {
+                let r = bc4::decode(block.try_into().unwrap());
+                let mut rgba = [[0, 0, 0, 0xFF]; 16];
+                for (px, r) in rgba.iter_mut().zip(r.iter().copied()) { px[0] = r; }
+                rgba
+            },
+            Format::Bc5         => {
+                let r = bc4::decode(block[..8].try_into().unwrap());
+                let g = bc4::decode(block[8..].try_into().unwrap());
+                let mut rgba = [[0, 0, 0, 0xFF]; 16];
+                for (i, px) in rgba.iter_mut().enumerate() { px[0] = r[i]; px[1] = g[i]; }
+                rgba
+            },
+            Format::Bc6hUf16    => bc6h::decode(block.try_into().unwrap(), false).map(unorm8),
+            Format::Bc6hSf16    => bc6h::decode(block.try_into().unwrap(), true ).map(unorm8),
+            Format::Bc7         => bc7::decode(block.try_into().unwrap()),
+        };
+        let bw = blocks(width);
+        write_block(&mut image, (i % bw) as u32, (i / bw) as u32, &decoded);
+    }
+    Ok(image)
+}
+
+/// Decompress `width` x `height` pixels worth of BC6H blocks into row-major, unclamped, linear RGB.
+pub fn decompress_bc6h(data: &[u8], width: u32, height: u32, signed: bool) -> Result<Vec<[f32; 3]>, Error> {
+    let format = if signed { Format::Bc6hSf16 } else { Format::Bc6hUf16 };
+    let data = check_size(data, width, height, format)?;
+    let bw = blocks(width);
+    let mut rgb = vec![[0.0; 3]; width as usize * height as usize];
+    for (i, block) in data.chunks_exact(16).enumerate() {
+        let decoded = bc6h::decode(block.try_into().unwrap(), signed);
+        let (x0, y0) = (i % bw * 4, i / bw * 4);
+        for (j, px) in decoded.iter().enumerate() {
+            let (x, y) = (x0 + j % 4, y0 + j / 4);
+            if x < width as usize && y < height as usize { rgb[y * width as usize + x] = *px; }
+        }
+    }
+    Ok(rgb)
+}
+
+
+
+fn blocks(pixels: u32) -> usize { (pixels as usize).div_ceil(4) }
+
+fn check_size(data: &[u8], width: u32, height: u32, format: Format) -> Result<&[u8], Error> {
+    let expected = format.compressed_size(width, height);
+    if data.len() < expected { return Err(Error::DataTooShort { expected, actual: data.len() }) }
+    Ok(&data[..expected])
+}
+
+/// Read a 4x4 block, clamping to the edge of the image.
+fn read_block(image: &Image, bx: u32, by: u32) -> [[u8; 4]; 16] {
+    let mut block = [[0; 4]; 16];
+    let (w, h) = image.dimensions();
+    for (i, px) in block.iter_mut().enumerate() {
+        let x = (bx * 4 + i as u32 % 4).min(w.saturating_sub(1));
+        let y = (by * 4 + i as u32 / 4).min(h.saturating_sub(1));
+        *px = image.get(x, y).unwrap_or_default();
+    }
+    block
+}
+
+/// Write a 4x4 block, discarding pixels past the edge of the image.
+fn write_block(image: &mut Image, bx: u32, by: u32, block: &[[u8; 4]; 16]) {
+    for (i, px) in block.iter().enumerate() {
+        image.set(bx * 4 + i as u32 % 4, by * 4 + i as u32 / 4, *px);
+    }
+}
+
+fn channel(block: &[[u8; 4]; 16], c: usize) -> [u8; 16] {
+    let mut out = [0; 16];
+    for (o, px) in out.iter_mut().zip(block.iter()) { *o = px[c]; }
+    out
+}
+
+fn unorm8(rgb: [f32; 3]) -> [u8; 4] {
+    let c = |v: f32| (v.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+    [c(rgb[0]), c(rgb[1]), c(rgb[2]), 0xFF]
+}
+
+
+
+#[test] fn roundtrip_solid_colors() {
+    for &rgba in [[0, 0, 0, 255], [255, 255, 255, 255], [255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]].iter() {
+        let image = Image::new_filled(5, 7, rgba);
+        for &quality in [Quality::Fast, Quality::Normal, Quality::Best].iter() {
+            for &format in [Format::Bc1, Format::Bc3].iter() {
+                let data = compress(&image, format, quality).unwrap();
+                assert_eq!(data.len(), format.compressed_size(5, 7));
+                assert_eq!(decompress(&data, 5, 7, format).unwrap(), image, "{:?} {:?} {:?}", rgba, format, quality);
+            }
+            let bc4 = decompress(&compress(&image, Format::Bc4, quality).unwrap(), 5, 7, Format::Bc4).unwrap();
+            let bc5 = decompress(&compress(&image, Format::Bc5, quality).unwrap(), 5, 7, Format::Bc5).unwrap();
+            assert!(bc4.pixels().iter().all(|&px| px == [rgba[0], 0, 0, 255]));
+            assert!(bc5.pixels().iter().all(|&px| px == [rgba[0], rgba[1], 0, 255]));
+        }
+    }
+}
+
+#[test] fn roundtrip_gradients() {
+    let image = Image::from_fn(32, 32, |x, y| [(x * 8) as u8, (y * 8) as u8, ((x + y) * 4) as u8, (255 - x * 8) as u8]);
+    let mut prev_error = u64::MAX;
+    for &quality in [Quality::Fast, Quality::Normal, Quality::Best].iter() {
+        let mut error = 0;
+        for &format in [Format::Bc1, Format::Bc3, Format::Bc4, Format::Bc5].iter() {
+            let decoded = decompress(&compress(&image, format, quality).unwrap(), 32, 32, format).unwrap();
+            for (a, b) in image.pixels().iter().zip(decoded.pixels().iter()) {
+                if format == Format::Bc1 && a[3] < 0x80 { continue } // punch-through alpha discards color
+                let channels = match format { Format::Bc1 => 3, Format::Bc3 => 4, Format::Bc4 => 1, _ => 2 };
+                for c in 0 .. channels {
+                    let d = i64::from(a[c]) - i64::from(b[c]);
+                    assert!(d.abs() <= 24, "{:?} {:?}: {:?} vs {:?}", format, quality, a, b);
+                    error += (d * d) as u64;
+                }
+            }
+        }
+        assert!(error <= prev_error, "{:?} was worse than the previous quality level", quality);
+        prev_error = error;
+    }
+}
+
+#[test] fn bc1_punchthrough_alpha() {
+    let image = Image::from_fn(4, 4, |x, _y| if x < 2 { [0, 0, 0, 0] } else { [200, 100, 50, 255] });
+    let decoded = decompress(&compress(&image, Format::Bc1, Quality::Normal).unwrap(), 4, 4, Format::Bc1).unwrap();
+    for (a, b) in image.pixels().iter().zip(decoded.pixels().iter()) {
+        assert_eq!(a[3], b[3]);
+    }
+}
+
+#[test] fn errors() {
+    let image = Image::new(4, 4);
+    assert_eq!(compress(&image, Format::Bc7, Quality::Fast), Err(Error::UnsupportedEncoderFormat(Format::Bc7)));
+    assert_eq!(decompress(&[0; 8], 8, 4, Format::Bc1), Err(Error::DataTooShort { expected: 16, actual: 8 }));
+    assert_eq!(decompress(&[0; 16], 4, 4, Format::Bc1).unwrap().dimensions(), (4, 4)); // trailing data is ignored
+}
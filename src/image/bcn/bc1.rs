@@ -0,0 +1,210 @@
+//! BC1 color blocks (also the color half of BC2/BC3 blocks)
+
+use super::Quality;
+
+
+
+/// Decode a BC1 color block.
+///
+/// `allow_punchthrough` should be `false` for the color half of BC2/BC3 blocks, which always use 4-color mode.
+pub(super) fn decode(block: &[u8; 8], allow_punchthrough: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let palette = palette(c0, c1, allow_punchthrough);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    let mut out = [[0; 4]; 16];
+    for (i, px) in out.iter_mut().enumerate() {
+        *px = palette[(indices >> (2 * i) & 3) as usize];
+    }
+    out
+}
+
+/// Encode a BC1 color block.
+///
+/// If `allow_punchthrough`, pixels with alpha < 128 are encoded as transparent black.
+pub(super) fn encode(block: &[[u8; 4]; 16], allow_punchthrough: bool, quality: Quality) -> [u8; 8] {
+    let transparent = |px: &[u8; 4]| allow_punchthrough && px[3] < 0x80;
+    let opaque = block.iter().filter(|px| !transparent(px)).map(|px| [f32::from(px[0]), f32::from(px[1]), f32::from(px[2])]).collect::<Vec<_>>();
+    if opaque.is_empty() { return [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF] } // 3-color mode, all transparent
+
+    let any_transparent = opaque.len() < 16;
+    let mut best = None;
+    for &colors in [4, 3].iter() {
+        if colors == 4 && any_transparent { continue } // 4-color mode can't represent transparency
+        if colors == 3 && !any_transparent && !(allow_punchthrough && quality == Quality::Best) { continue }
+
+        let (e0, e1) = initial_endpoints(&opaque, quality);
+        let mut candidate = quantize(block, transparent, e0, e1, colors);
+        let iterations = match quality { Quality::Fast => 0, Quality::Normal => 1, Quality::Best => 8 };
+        for _ in 0 .. iterations {
+            let refined = match refine(block, transparent, &candidate) {
+                Some((e0, e1)) => quantize(block, transparent, e0, e1, colors),
+                None => break,
+            };
+            if refined.error >= candidate.error { break }
+            candidate = refined;
+        }
+
+        if best.as_ref().is_none_or(|b: &Candidate| candidate.error < b.error) { best = Some(candidate); }
+    }
+    best.unwrap().to_bytes()
+}
+
+
+
+struct Candidate {
+    colors:     u8,
+    c0:         u16,
+    c1:         u16,
+    indices:    [u8; 16],
+    error:      u64,
+}
+
+impl Candidate {
+    fn to_bytes(&self) -> [u8; 8] {
+        let (mut c0, mut c1, mut indices) = (self.c0, self.c1, self.indices);
+        let swap = if self.colors == 4 { c0 < c1 } else { c0 > c1 };
+        if swap {
+            std::mem::swap(&mut c0, &mut c1);
+            for i in indices.iter_mut() {
+                *i = match (self.colors, *i) { (_, 0) => 1, (_, 1) => 0, (4, 2) => 3, (4, 3) => 2, (_, i) => i };
+            }
+        }
+        if self.colors == 4 && c0 == c1 {
+            indices = [0; 16]; // would decode as 3-color mode, where index 3 is transparent
+        }
+        let mut packed = 0u32;
+        for (i, idx) in indices.iter().copied().enumerate() { packed |= u32::from(idx) << (2 * i); }
+        let [a, b] = c0.to_le_bytes();
+        let [c, d] = c1.to_le_bytes();
+        let [e, f, g, h] = packed.to_le_bytes();
+        [a, b, c, d, e, f, g, h]
+    }
+}
+
+fn initial_endpoints(pixels: &[[f32; 3]], quality: Quality) -> ([f32; 3], [f32; 3]) {
+    let n = pixels.len() as f32;
+    if quality == Quality::Fast {
+        let mut lo = [255.0f32; 3];
+        let mut hi = [0.0f32; 3];
+        for px in pixels.iter() {
+            for c in 0 .. 3 { lo[c] = lo[c].min(px[c]); hi[c] = hi[c].max(px[c]); }
+        }
+        for c in 0 .. 3 {
+            let inset = (hi[c] - lo[c]) / 16.0;
+            lo[c] += inset;
+            hi[c] -= inset;
+        }
+        return (hi, lo);
+    }
+
+    // Principal component analysis: fit a line through the colors
+    let mut mean = [0.0f32; 3];
+    for px in pixels.iter() { for c in 0 .. 3 { mean[c] += px[c] / n; } }
+    let mut cov = [[0.0f32; 3]; 3];
+    for px in pixels.iter() {
+        let d = [px[0] - mean[0], px[1] - mean[1], px[2] - mean[2]];
+        for i in 0 .. 3 { for j in 0 .. 3 { cov[i][j] += d[i] * d[j]; } }
+    }
+    let mut axis = [1.0f32, 1.0, 1.0];
+    for _ in 0 .. 8 { // power iteration
+        let next = [
+            cov[0][0] * axis[0] + cov[0][1] * axis[1] + cov[0][2] * axis[2],
+            cov[1][0] * axis[0] + cov[1][1] * axis[1] + cov[1][2] * axis[2],
+            cov[2][0] * axis[0] + cov[2][1] * axis[1] + cov[2][2] * axis[2],
+        ];
+        let len = (next[0] * next[0] + next[1] * next[1] + next[2] * next[2]).sqrt();
+        if len < 1e-6 { return (mean, mean) } // all pixels are (nearly) identical
+        axis = [next[0] / len, next[1] / len, next[2] / len];
+    }
+
+    let (mut lo, mut hi) = (f32::MAX, f32::MIN);
+    for px in pixels.iter() {
+        let t = (px[0] - mean[0]) * axis[0] + (px[1] - mean[1]) * axis[1] + (px[2] - mean[2]) * axis[2];
+        lo = lo.min(t);
+        hi = hi.max(t);
+    }
+    let at = |t: f32| [mean[0] + axis[0] * t, mean[1] + axis[1] * t, mean[2] + axis[2] * t];
+    (at(hi), at(lo))
+}
+
+/// Least squares endpoints for the indices chosen by `candidate`.
+fn refine(block: &[[u8; 4]; 16], transparent: impl Fn(&[u8; 4]) -> bool, candidate: &Candidate) -> Option<([f32; 3], [f32; 3])> {
+    let weights : &[f32] = if candidate.colors == 4 { &[1.0, 0.0, 2.0/3.0, 1.0/3.0] } else { &[1.0, 0.0, 0.5] };
+    let (mut aa, mut bb, mut ab) = (0.0, 0.0, 0.0);
+    let (mut ax, mut bx) = ([0.0f32; 3], [0.0f32; 3]);
+    for (px, idx) in block.iter().zip(candidate.indices.iter().copied()) {
+        if transparent(px) { continue }
+        let a = weights[usize::from(idx)];
+        let b = 1.0 - a;
+        aa += a * a;
+        bb += b * b;
+        ab += a * b;
+        for c in 0 .. 3 {
+            ax[c] += a * f32::from(px[c]);
+            bx[c] += b * f32::from(px[c]);
+        }
+    }
+    let det = aa * bb - ab * ab;
+    if det.abs() < 1e-6 { return None }
+    let mut e0 = [0.0; 3];
+    let mut e1 = [0.0; 3];
+    for c in 0 .. 3 {
+        e0[c] = (ax[c] * bb - bx[c] * ab) / det;
+        e1[c] = (bx[c] * aa - ax[c] * ab) / det;
+    }
+    Some((e0, e1))
+}
+
+fn quantize(block: &[[u8; 4]; 16], transparent: impl Fn(&[u8; 4]) -> bool, e0: [f32; 3], e1: [f32; 3], colors: u8) -> Candidate {
+    let (c0, c1) = (pack565(e0), pack565(e1));
+    let palette = if colors == 4 { palette(c0.max(c1), c0.min(c1), false) } else { palette(c0.min(c1), c0.max(c1), true) };
+    let swapped = if colors == 4 { c0 < c1 } else { c0 > c1 };
+
+    let mut indices = [0; 16];
+    let mut error = 0;
+    for (px, index) in block.iter().zip(indices.iter_mut()) {
+        if transparent(px) { *index = 3; continue }
+        let (idx, err) = palette[.. usize::from(colors)].iter().enumerate().map(|(i, p)| {
+            let d = |c: usize| i32::from(px[c]) - i32::from(p[c]);
+            (i, (d(0) * d(0) + d(1) * d(1) + d(2) * d(2)) as u64)
+        }).min_by_key(|&(_, err)| err).unwrap();
+        *index = idx as u8;
+        error += err;
+    }
+
+    // Express indices relative to (c0, c1) rather than the sorted palette
+    if swapped {
+        for i in indices.iter_mut() {
+            *i = match (colors, *i) { (_, 0) => 1, (_, 1) => 0, (4, 2) => 3, (4, 3) => 2, (_, i) => i };
+        }
+    }
+    Candidate { colors, c0, c1, indices, error }
+}
+
+fn palette(c0: u16, c1: u16, allow_punchthrough: bool) -> [[u8; 4]; 4] {
+    let (p0, p1) = (unpack565(c0), unpack565(c1));
+    let mix = |w0: u16, w1: u16| {
+        let mut px = [0, 0, 0, 0xFF];
+        for c in 0 .. 3 { px[c] = ((w0 * u16::from(p0[c]) + w1 * u16::from(p1[c])) / (w0 + w1)) as u8; }
+        px
+    };
+    if c0 > c1 || !allow_punchthrough {
+        [p0, p1, mix(2, 1), mix(1, 2)]
+    } else {
+        [p0, p1, mix(1, 1), [0, 0, 0, 0]]
+    }
+}
+
+fn pack565(rgb: [f32; 3]) -> u16 {
+    let q = |v: f32, max: f32| (v.clamp(0.0, 255.0) * max / 255.0 + 0.5) as u16;
+    q(rgb[0], 31.0) << 11 | q(rgb[1], 63.0) << 5 | q(rgb[2], 31.0)
+}
+
+fn unpack565(c: u16) -> [u8; 4] {
+    let r = (c >> 11 & 0x1F) as u8;
+    let g = (c >>  5 & 0x3F) as u8;
+    let b = (c       & 0x1F) as u8;
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2, 0xFF]
+}
@@ -0,0 +1,84 @@
+//! BC4 single channel blocks (also the alpha half of BC3 blocks, and both halves of BC5 blocks)
+
+use super::Quality;
+
+
+
+pub(super) fn decode(block: &[u8; 8]) -> [u8; 16] {
+    let palette = palette(block[0], block[1]);
+    let indices = u64::from_le_bytes([block[2], block[3], block[4], block[5], block[6], block[7], 0, 0]);
+
+    let mut out = [0; 16];
+    for (i, v) in out.iter_mut().enumerate() {
+        *v = palette[(indices >> (3 * i) & 7) as usize];
+    }
+    out
+}
+
+pub(super) fn encode(values: &[u8; 16], quality: Quality) -> [u8; 8] {
+    let lo = values.iter().copied().min().unwrap();
+    let hi = values.iter().copied().max().unwrap();
+    if lo == hi { return to_bytes(lo, lo, &[0; 16]) }
+
+    // 8-value mode requires a0 > a1
+    let mut best = fit(values, hi, lo);
+    if quality == Quality::Fast { return best.0 }
+
+    // 6-value mode requires a0 <= a1, and gets exact 0 and 255 for free
+    let inner = values.iter().copied().filter(|&v| v != 0 && v != 255);
+    let lo6 = inner.clone().min().unwrap_or(lo);
+    let hi6 = inner.max().unwrap_or(hi);
+    let mut consider = |a0: u8, a1: u8| {
+        let candidate = fit(values, a0, a1);
+        if candidate.1 < best.1 { best = candidate; }
+    };
+    consider(lo6, hi6);
+
+    if quality == Quality::Best {
+        const R : i32 = 4;
+        let nudge = |v: u8, d: i32| (i32::from(v) + d).clamp(0, 255) as u8;
+        for d0 in -R ..= R {
+            for d1 in -R ..= R {
+                let (a0, a1) = (nudge(hi, d0), nudge(lo, d1));
+                if a0 > a1 { consider(a0, a1); }
+                let (a0, a1) = (nudge(lo6, d0), nudge(hi6, d1));
+                if a0 <= a1 { consider(a0, a1); }
+            }
+        }
+    }
+
+    best.0
+}
+
+
+
+fn fit(values: &[u8; 16], a0: u8, a1: u8) -> ([u8; 8], u32) {
+    let palette = palette(a0, a1);
+    let mut indices = [0; 16];
+    let mut error = 0;
+    for (v, index) in values.iter().copied().zip(indices.iter_mut()) {
+        let (i, e) = palette.iter().enumerate().map(|(i, &p)| {
+            let d = i32::from(v) - i32::from(p);
+            (i, (d * d) as u32)
+        }).min_by_key(|&(_, e)| e).unwrap();
+        *index = i as u8;
+        error += e;
+    }
+    (to_bytes(a0, a1, &indices), error)
+}
+
+fn to_bytes(a0: u8, a1: u8, indices: &[u8; 16]) -> [u8; 8] {
+    let mut packed = 0u64;
+    for (i, idx) in indices.iter().copied().enumerate() { packed |= u64::from(idx) << (3 * i); }
+    let p = packed.to_le_bytes();
+    [a0, a1, p[0], p[1], p[2], p[3], p[4], p[5]]
+}
+
+fn palette(a0: u8, a1: u8) -> [u8; 8] {
+    let mix = |w0: u16, w1: u16| ((w0 * u16::from(a0) + w1 * u16::from(a1) + (w0 + w1) / 2) / (w0 + w1)) as u8;
+    if a0 > a1 {
+        [a0, a1, mix(6, 1), mix(5, 2), mix(4, 3), mix(3, 4), mix(2, 5), mix(1, 6)]
+    } else {
+        [a0, a1, mix(4, 1), mix(3, 2), mix(2, 3), mix(1, 4), 0, 255]
+    }
+}
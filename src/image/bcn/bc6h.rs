@@ -0,0 +1,173 @@
+//! BC6H HDR blocks
+
+use super::bc7::{ANCHOR2, PARTITIONS2, weights};
+use super::bits::BitReader;
+
+
+
+pub(super) fn decode(block: &[u8; 16], signed: bool) -> [[f32; 3]; 16] {
+    let mut bits = BitReader::new(block);
+    let mode = match bits.read(2) {
+        0 => 1,
+        1 => 2,
+        m2 => match m2 | bits.read(3) << 2 {
+            0x02 => 3, 0x06 => 4, 0x0A => 5, 0x0E => 6, 0x12 => 7, 0x16 => 8, 0x1A => 9, 0x1E => 10,
+            0x03 => 11, 0x07 => 12, 0x0B => 13, 0x0F => 14,
+            _reserved => return [[0.0; 3]; 16],
+        },
+    };
+    let (transformed, epb, delta_bits) = MODES[mode - 1];
+    let regions = if mode <= 10 { 2 } else { 1 };
+
+    // e[channel][endpoint]
+    let mut e = [[0i32; 4]; 3];
+    for &(c, ep, hi, lo) in LAYOUTS[mode - 1].iter() {
+        let (c, ep) = (usize::from(c), usize::from(ep));
+        if hi >= lo {
+            e[c][ep] |= (bits.read(u32::from(hi - lo + 1)) << lo) as i32;
+        } else {
+            e[c][ep] |= (bits.read_reversed(u32::from(lo - hi + 1)) << hi) as i32;
+        }
+    }
+    let partition = if regions == 2 { bits.read(5) as usize } else { 0 };
+
+    let ends = 2 * regions;
+    for (c, e) in e.iter_mut().enumerate() {
+        if signed { e[0] = sign_extend(e[0], epb); }
+        if transformed || signed {
+            for v in e[1 .. ends].iter_mut() { *v = sign_extend(*v, delta_bits[c]); }
+        }
+        if transformed {
+            let w = e[0];
+            for v in e[1 .. ends].iter_mut() {
+                *v = (w + *v) & ((1 << epb) - 1);
+                if signed { *v = sign_extend(*v, epb); }
+            }
+        }
+        for v in e[.. ends].iter_mut() { *v = unquantize(*v, epb, signed); }
+    }
+
+    let index_bits = if regions == 2 { 3 } else { 4 };
+    let mut out = [[0.0; 3]; 16];
+    for (i, px) in out.iter_mut().enumerate() {
+        let anchor = i == 0 || (regions == 2 && i == usize::from(ANCHOR2[partition]));
+        let index = bits.read(index_bits - u32::from(anchor)) as usize;
+        let subset = if regions == 2 { usize::from(PARTITIONS2[partition] >> i & 1) } else { 0 };
+        let w = i32::from(weights(index_bits)[index]);
+        for c in 0 .. 3 {
+            let (a, b) = (e[c][2*subset], e[c][2*subset+1]);
+            let v = (a * (64 - w) + b * w + 32) >> 6;
+            px[c] = half_to_f32(finish_unquantize(v, signed));
+        }
+    }
+    out
+}
+
+
+
+fn sign_extend(v: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (v << shift) >> shift
+}
+
+fn unquantize(v: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        if bits >= 15 || v == 0 { v }
+        else if v == (1 << bits) - 1 { 0xFFFF }
+        else { ((v << 16) + 0x8000) >> bits }
+    } else {
+        if bits >= 16 { return v }
+        let (negative, v) = (v < 0, v.abs());
+        let u = if v == 0 { 0 }
+            else if v >= (1 << (bits - 1)) - 1 { 0x7FFF }
+            else { ((v << 15) + 0x4000) >> (bits - 1) };
+        if negative { -u } else { u }
+    }
+}
+
+/// Scale interpolated values into the bits of an IEEE half
+fn finish_unquantize(v: i32, signed: bool) -> u16 {
+    if !signed {
+        ((v * 31) >> 6) as u16
+    } else if v < 0 {
+        0x8000 | (((-v) * 31) >> 5) as u16
+    } else {
+        ((v * 31) >> 5) as u16
+    }
+}
+
+fn half_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = i32::from(h >> 10 & 0x1F);
+    let man = f32::from(h & 0x3FF);
+    match exp {
+        0   => sign * man * 2f32.powi(-24),
+        31  => if man == 0.0 { sign * f32::INFINITY } else { f32::NAN },
+        _   => sign * (1.0 + man / 1024.0) * 2f32.powi(exp - 15),
+    }
+}
+
+
+
+/// (transformed, endpoint bits, delta bits per channel) for modes 1 ..= 14
+const MODES : [(bool, u32, [u32; 3]); 14] = [
+    (true,  10, [ 5,  5,  5]),
+    (true,   7, [ 6,  6,  6]),
+    (true,  11, [ 5,  4,  4]),
+    (true,  11, [ 4,  5,  4]),
+    (true,  11, [ 4,  4,  5]),
+    (true,   9, [ 5,  5,  5]),
+    (true,   8, [ 6,  5,  5]),
+    (true,   8, [ 5,  6,  5]),
+    (true,   8, [ 5,  5,  6]),
+    (false,  6, [ 6,  6,  6]),
+    (false, 10, [10, 10, 10]),
+    (true,  11, [ 9,  9,  9]),
+    (true,  12, [ 8,  8,  8]),
+    (true,  16, [ 4,  4,  4]),
+];
+
+const R : u8 = 0;
+const G : u8 = 1;
+const B : u8 = 2;
+const W : u8 = 0;
+const X : u8 = 1;
+const Y : u8 = 2;
+const Z : u8 = 3;
+
+/// Header bit fields after the mode bits, in stream order: (channel, endpoint, msb, lsb).
+/// `msb < lsb` indicates a field stored in reversed bit order.
+const LAYOUTS : [&[(u8, u8, u8, u8)]; 14] = [
+    &[(G,Y,4,4),(B,Y,4,4),(B,Z,4,4),(R,W,9,0),(G,W,9,0),(B,W,9,0),(R,X,4,0),(G,Z,4,4),(G,Y,3,0),(G,X,4,0),(B,Z,0,0),(G,Z,3,0),(B,X,4,0),(B,Z,1,1),(B,Y,3,0),(R,Y,4,0),(B,Z,2,2),(R,Z,4,0),(B,Z,3,3)],
+    &[(G,Y,5,5),(G,Z,4,4),(G,Z,5,5),(R,W,6,0),(B,Z,0,0),(B,Z,1,1),(B,Y,4,4),(G,W,6,0),(B,Y,5,5),(B,Z,2,2),(G,Y,4,4),(B,W,6,0),(B,Z,3,3),(B,Z,5,5),(B,Z,4,4),(R,X,5,0),(G,Y,3,0),(G,X,5,0),(G,Z,3,0),(B,X,5,0),(B,Y,3,0),(R,Y,5,0),(R,Z,5,0)],
+    &[(R,W,9,0),(G,W,9,0),(B,W,9,0),(R,X,4,0),(R,W,10,10),(G,Y,3,0),(G,X,3,0),(G,W,10,10),(B,Z,0,0),(G,Z,3,0),(B,X,3,0),(B,W,10,10),(B,Z,1,1),(B,Y,3,0),(R,Y,4,0),(B,Z,2,2),(R,Z,4,0),(B,Z,3,3)],
+    &[(R,W,9,0),(G,W,9,0),(B,W,9,0),(R,X,3,0),(R,W,10,10),(G,Z,4,4),(G,Y,3,0),(G,X,4,0),(G,W,10,10),(G,Z,3,0),(B,X,3,0),(B,W,10,10),(B,Z,1,1),(B,Y,3,0),(R,Y,3,0),(B,Z,0,0),(B,Z,2,2),(R,Z,3,0),(G,Y,4,4),(B,Z,3,3)],
+    &[(R,W,9,0),(G,W,9,0),(B,W,9,0),(R,X,3,0),(R,W,10,10),(B,Y,4,4),(G,Y,3,0),(G,X,3,0),(G,W,10,10),(B,Z,0,0),(G,Z,3,0),(B,X,4,0),(B,W,10,10),(B,Y,3,0),(R,Y,3,0),(B,Z,1,1),(B,Z,2,2),(R,Z,3,0),(B,Z,4,4),(B,Z,3,3)],
+    &[(R,W,8,0),(B,Y,4,4),(G,W,8,0),(G,Y,4,4),(B,W,8,0),(B,Z,4,4),(R,X,4,0),(G,Z,4,4),(G,Y,3,0),(G,X,4,0),(B,Z,0,0),(G,Z,3,0),(B,X,4,0),(B,Z,1,1),(B,Y,3,0),(R,Y,4,0),(B,Z,2,2),(R,Z,4,0),(B,Z,3,3)],
+    &[(R,W,7,0),(G,Z,4,4),(B,Y,4,4),(G,W,7,0),(B,Z,2,2),(G,Y,4,4),(B,W,7,0),(B,Z,3,3),(B,Z,4,4),(R,X,5,0),(G,Y,3,0),(G,X,4,0),(B,Z,0,0),(G,Z,3,0),(B,X,4,0),(B,Z,1,1),(B,Y,3,0),(R,Y,5,0),(R,Z,5,0)],
+    &[(R,W,7,0),(B,Z,0,0),(B,Y,4,4),(G,W,7,0),(G,Y,5,5),(G,Y,4,4),(B,W,7,0),(G,Z,5,5),(B,Z,4,4),(R,X,4,0),(G,Z,4,4),(G,Y,3,0),(G,X,5,0),(G,Z,3,0),(B,X,4,0),(B,Z,1,1),(B,Y,3,0),(R,Y,4,0),(B,Z,2,2),(R,Z,4,0),(B,Z,3,3)],
+    &[(R,W,7,0),(B,Z,1,1),(B,Y,4,4),(G,W,7,0),(B,Y,5,5),(G,Y,4,4),(B,W,7,0),(B,Z,5,5),(B,Z,4,4),(R,X,4,0),(G,Z,4,4),(G,Y,3,0),(G,X,4,0),(B,Z,0,0),(G,Z,3,0),(B,X,5,0),(B,Y,3,0),(R,Y,4,0),(B,Z,2,2),(R,Z,4,0),(B,Z,3,3)],
+    &[(R,W,5,0),(G,Z,4,4),(B,Z,0,0),(B,Z,1,1),(B,Y,4,4),(G,W,5,0),(G,Y,5,5),(B,Y,5,5),(B,Z,2,2),(G,Y,4,4),(B,W,5,0),(G,Z,5,5),(B,Z,3,3),(B,Z,5,5),(B,Z,4,4),(R,X,5,0),(G,Y,3,0),(G,X,5,0),(G,Z,3,0),(B,X,5,0),(B,Y,3,0),(R,Y,5,0),(R,Z,5,0)],
+    &[(R,W,9,0),(G,W,9,0),(B,W,9,0),(R,X,9,0),(G,X,9,0),(B,X,9,0)],
+    &[(R,W,9,0),(G,W,9,0),(B,W,9,0),(R,X,8,0),(R,W,10,10),(G,X,8,0),(G,W,10,10),(B,X,8,0),(B,W,10,10)],
+    &[(R,W,9,0),(G,W,9,0),(B,W,9,0),(R,X,7,0),(R,W,10,11),(G,X,7,0),(G,W,10,11),(B,X,7,0),(B,W,10,11)],
+    &[(R,W,9,0),(G,W,9,0),(B,W,9,0),(R,X,3,0),(R,W,10,15),(G,X,3,0),(G,W,10,15),(B,X,3,0),(B,W,10,15)],
+];
+
+
+
+#[test] fn layout_sizes() {
+    for (i, layout) in LAYOUTS.iter().enumerate() {
+        let bits = layout.iter().map(|&(_, _, hi, lo)| u32::from(hi.max(lo) - hi.min(lo) + 1)).sum::<u32>();
+        let (mode_bits, partition_bits) = match i { 0 | 1 => (2, 5), 2 ..= 9 => (5, 5), _ => (5, 0) };
+        let expected = if partition_bits == 0 { 65 } else { 82 };
+        assert_eq!(mode_bits + bits + partition_bits, expected, "mode {}", i + 1);
+    }
+}
+
+#[test] fn mode11_one() {
+    let mut block = 0x03u128;
+    for i in 0 .. 6 { block |= 495 << (5 + 10 * i); } // unquantizes to exactly 1.0
+    assert_eq!(decode(&block.to_le_bytes(), false), [[1.0; 3]; 16]);
+    assert_eq!(decode(&[0x13, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], false), [[0.0; 3]; 16]); // reserved mode
+}
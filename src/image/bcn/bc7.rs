@@ -0,0 +1,195 @@
+//! BC7 blocks (and the partition tables BC6H shares with them)
+
+use super::bits::BitReader;
+
+
+
+pub(super) fn decode(block: &[u8; 16]) -> [[u8; 4]; 16] {
+    let mode = block[0].trailing_zeros() as usize;
+    if mode >= MODES.len() { return [[0; 4]; 16] } // reserved
+    let m = &MODES[mode];
+
+    let mut bits = BitReader::new(block);
+    bits.read(mode as u32 + 1);
+    let partition   = bits.read(m.partition_bits) as usize;
+    let rotation    = bits.read(m.rotation_bits);
+    let index_sel   = bits.read(m.index_sel_bits);
+
+    let ns = usize::from(m.subsets);
+    let mut ep = [[0u32; 4]; 6];
+    for c in 0 .. 3 { for e in ep[.. 2*ns].iter_mut() { e[c] = bits.read(m.color_bits); } }
+    for e in ep[.. 2*ns].iter_mut() { e[3] = bits.read(m.alpha_bits); }
+    if m.endpoint_pbits {
+        for e in ep[.. 2*ns].iter_mut() {
+            let p = bits.read(1);
+            for c in e.iter_mut() { *c = *c << 1 | p; }
+        }
+    }
+    if m.shared_pbits {
+        for s in 0 .. ns {
+            let p = bits.read(1);
+            for e in ep[2*s .. 2*s+2].iter_mut() { for c in e.iter_mut() { *c = *c << 1 | p; } }
+        }
+    }
+
+    let pbit = u32::from(m.endpoint_pbits || m.shared_pbits);
+    let (cbits, abits) = (m.color_bits + pbit, m.alpha_bits + pbit);
+    let expand = |v: u32, bits: u32| (v << (8 - bits) | v >> (2 * bits - 8)) as u8;
+    let mut endpoints = [[0u8; 4]; 6];
+    for (dst, src) in endpoints.iter_mut().zip(ep.iter()) {
+        for c in 0 .. 3 { dst[c] = expand(src[c], cbits); }
+        dst[3] = if m.alpha_bits == 0 { 0xFF } else { expand(src[3], abits) };
+    }
+
+    let subset = |i: usize| match ns {
+        1 => 0,
+        2 => usize::from(PARTITIONS2[partition] >> i & 1),
+        _ => usize::from(PARTITIONS3[partition][i]),
+    };
+    let is_anchor = |i: usize| i == 0 || match ns {
+        1 => false,
+        2 => i == usize::from(ANCHOR2[partition]),
+        _ => i == usize::from(ANCHOR3_2[partition]) || i == usize::from(ANCHOR3_3[partition]),
+    };
+
+    let mut primary = [0u8; 16];
+    for (i, idx) in primary.iter_mut().enumerate() { *idx = bits.read(m.index_bits - u32::from(is_anchor(i))) as u8; }
+    let mut secondary = [0u8; 16];
+    if m.index2_bits > 0 {
+        for (i, idx) in secondary.iter_mut().enumerate() { *idx = bits.read(m.index2_bits - u32::from(i == 0)) as u8; }
+    }
+
+    let (color_idx, color_bits, alpha_idx, alpha_bits) = match (m.index2_bits, index_sel) {
+        (0, _) => (&primary,   m.index_bits,  &primary,   m.index_bits),
+        (_, 0) => (&primary,   m.index_bits,  &secondary, m.index2_bits),
+        (_, _) => (&secondary, m.index2_bits, &primary,   m.index_bits),
+    };
+
+    let mut out = [[0u8; 4]; 16];
+    for (i, px) in out.iter_mut().enumerate() {
+        let s = subset(i);
+        let (e0, e1) = (endpoints[2*s], endpoints[2*s+1]);
+        let cw = weights(color_bits)[usize::from(color_idx[i])];
+        let aw = weights(alpha_bits)[usize::from(alpha_idx[i])];
+        for c in 0 .. 3 { px[c] = interpolate(e0[c], e1[c], cw); }
+        px[3] = interpolate(e0[3], e1[3], aw);
+        match rotation {
+            1 => px.swap(0, 3),
+            2 => px.swap(1, 3),
+            3 => px.swap(2, 3),
+            _ => {},
+        }
+    }
+    out
+}
+
+pub(super) fn weights(index_bits: u32) -> &'static [u16] {
+    match index_bits {
+        2 => &[0, 21, 43, 64],
+        3 => &[0, 9, 18, 27, 37, 46, 55, 64],
+        _ => &[0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64],
+    }
+}
+
+fn interpolate(e0: u8, e1: u8, w: u16) -> u8 {
+    ((u16::from(e0) * (64 - w) + u16::from(e1) * w + 32) >> 6) as u8
+}
+
+
+
+struct Mode {
+    subsets:        u8,
+    partition_bits: u32,
+    rotation_bits:  u32,
+    index_sel_bits: u32,
+    color_bits:     u32,
+    alpha_bits:     u32,
+    endpoint_pbits: bool,
+    shared_pbits:   bool,
+    index_bits:     u32,
+    index2_bits:    u32,
+}
+
+const MODES : [Mode; 8] = [
+    Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_sel_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_pbits: true,  shared_pbits: false, index_bits: 3, index2_bits: 0 },
+    Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_sel_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_pbits: false, shared_pbits: true,  index_bits: 3, index2_bits: 0 },
+    Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_sel_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_pbits: false, shared_pbits: false, index_bits: 2, index2_bits: 0 },
+    Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_sel_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_pbits: true,  shared_pbits: false, index_bits: 2, index2_bits: 0 },
+    Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_sel_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_pbits: false, shared_pbits: false, index_bits: 2, index2_bits: 3 },
+    Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_sel_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_pbits: false, shared_pbits: false, index_bits: 2, index2_bits: 2 },
+    Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_sel_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_pbits: true,  shared_pbits: false, index_bits: 4, index2_bits: 0 },
+    Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_sel_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_pbits: true,  shared_pbits: false, index_bits: 2, index2_bits: 0 },
+];
+
+/// 2-subset partitions: bit `i` is set if pixel `i` belongs to subset 1.
+pub(super) const PARTITIONS2 : [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00, 0xFFF0, 0xF000,
+    0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE, 0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C,
+    0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// The anchor (first index with an implicit high bit of 0) of subset 1 for each of [`PARTITIONS2`]
+pub(super) const ANCHOR2 : [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15,  2,  8,  2,  2,  8,  8, 15,  2,  8,  2,  2,  8,  8,  2,  2,
+    15, 15,  6,  8,  2,  8, 15, 15,  2,  8,  2,  2,  2, 15, 15,  6,
+     6,  2,  6,  8, 15, 15,  2,  2, 15, 15, 15, 15, 15,  2,  2, 15,
+];
+
+const PARTITIONS3 : [[u8; 16]; 64] = [
+    [0,0,1,1,0,0,1,1,0,2,2,1,2,2,2,2], [0,0,0,1,0,0,1,1,2,2,1,1,2,2,2,1], [0,0,0,0,2,0,0,1,2,2,1,1,2,2,1,1], [0,2,2,2,0,0,2,2,0,0,1,1,0,1,1,1],
+    [0,0,0,0,0,0,0,0,1,1,2,2,1,1,2,2], [0,0,1,1,0,0,1,1,0,0,2,2,0,0,2,2], [0,0,2,2,0,0,2,2,1,1,1,1,1,1,1,1], [0,0,1,1,0,0,1,1,2,2,1,1,2,2,1,1],
+    [0,0,0,0,0,0,0,0,1,1,1,1,2,2,2,2], [0,0,0,0,1,1,1,1,1,1,1,1,2,2,2,2], [0,0,0,0,1,1,1,1,2,2,2,2,2,2,2,2], [0,0,1,2,0,0,1,2,0,0,1,2,0,0,1,2],
+    [0,1,1,2,0,1,1,2,0,1,1,2,0,1,1,2], [0,1,2,2,0,1,2,2,0,1,2,2,0,1,2,2], [0,0,1,1,0,1,1,2,1,1,2,2,1,2,2,2], [0,0,1,1,2,0,0,1,2,2,0,0,2,2,2,0],
+    [0,0,0,1,0,0,1,1,0,1,1,2,1,1,2,2], [0,1,1,1,0,0,1,1,2,0,0,1,2,2,0,0], [0,0,0,0,1,1,2,2,1,1,2,2,1,1,2,2], [0,0,2,2,0,0,2,2,0,0,2,2,1,1,1,1],
+    [0,1,1,1,0,1,1,1,0,2,2,2,0,2,2,2], [0,0,0,1,0,0,0,1,2,2,2,1,2,2,2,1], [0,0,0,0,0,0,1,1,0,1,2,2,0,1,2,2], [0,0,0,0,1,1,0,0,2,2,1,0,2,2,1,0],
+    [0,1,2,2,0,1,2,2,0,0,1,1,0,0,0,0], [0,0,1,2,0,0,1,2,1,1,2,2,2,2,2,2], [0,1,1,0,1,2,2,1,1,2,2,1,0,1,1,0], [0,0,0,0,0,1,1,0,1,2,2,1,1,2,2,1],
+    [0,0,2,2,1,1,0,2,1,1,0,2,0,0,2,2], [0,1,1,0,0,1,1,0,2,0,0,2,2,2,2,2], [0,0,1,1,0,1,2,2,0,1,2,2,0,0,1,1], [0,0,0,0,2,0,0,0,2,2,1,1,2,2,2,1],
+    [0,0,0,0,0,0,0,2,1,1,2,2,1,2,2,2], [0,2,2,2,0,0,2,2,0,0,1,2,0,0,1,1], [0,0,1,1,0,0,1,2,0,0,2,2,0,2,2,2], [0,1,2,0,0,1,2,0,0,1,2,0,0,1,2,0],
+    [0,0,0,0,1,1,1,1,2,2,2,2,0,0,0,0], [0,1,2,0,1,2,0,1,2,0,1,2,0,1,2,0], [0,1,2,0,2,0,1,2,1,2,0,1,0,1,2,0], [0,0,1,1,2,2,0,0,1,1,2,2,0,0,1,1],
+    [0,0,1,1,1,1,2,2,2,2,0,0,0,0,1,1], [0,1,0,1,0,1,0,1,2,2,2,2,2,2,2,2], [0,0,0,0,0,0,0,0,2,1,2,1,2,1,2,1], [0,0,2,2,1,1,2,2,0,0,2,2,1,1,2,2],
+    [0,0,2,2,0,0,1,1,0,0,2,2,0,0,1,1], [0,2,2,0,1,2,2,1,0,2,2,0,1,2,2,1], [0,1,0,1,2,2,2,2,2,2,2,2,0,1,0,1], [0,0,0,0,2,1,2,1,2,1,2,1,2,1,2,1],
+    [0,1,0,1,0,1,0,1,0,1,0,1,2,2,2,2], [0,2,2,2,0,1,1,1,0,2,2,2,0,1,1,1], [0,0,0,2,1,1,1,2,0,0,0,2,1,1,1,2], [0,0,0,0,2,1,1,2,2,1,1,2,2,1,1,2],
+    [0,2,2,2,0,1,1,1,0,1,1,1,0,2,2,2], [0,0,0,2,1,1,1,2,1,1,1,2,0,0,0,2], [0,1,1,0,0,1,1,0,0,1,1,0,2,2,2,2], [0,0,0,0,0,0,0,0,2,1,1,2,2,1,1,2],
+    [0,1,1,0,0,1,1,0,2,2,2,2,2,2,2,2], [0,0,2,2,0,0,1,1,0,0,1,1,0,0,2,2], [0,0,2,2,1,1,2,2,1,1,2,2,0,0,2,2], [0,0,0,0,0,0,0,0,0,0,0,0,2,1,1,2],
+    [0,0,0,2,0,0,0,1,0,0,0,2,0,0,0,1], [0,2,2,2,1,2,2,2,0,2,2,2,1,2,2,2], [0,1,0,1,2,2,2,2,2,2,2,2,2,2,2,2], [0,1,1,1,2,0,1,1,2,2,0,1,2,2,2,0],
+];
+
+const ANCHOR3_2 : [u8; 64] = [
+     3,  3, 15, 15,  8,  3, 15, 15,  8,  8,  6,  6,  6,  5,  3,  3,
+     3,  3,  8, 15,  3,  3,  6, 10,  5,  8,  8,  6,  8,  5, 15, 15,
+     8, 15,  3,  5,  6, 10,  8, 15, 15,  3, 15,  5, 15, 15, 15, 15,
+     3, 15,  5,  5,  5,  8,  5, 10,  5, 10,  8, 13, 15, 12,  3,  3,
+];
+
+const ANCHOR3_3 : [u8; 64] = [
+    15,  8,  8,  3, 15, 15,  3,  8, 15, 15, 15, 15, 15, 15, 15,  8,
+    15,  8, 15,  3, 15,  8, 15,  8,  3, 15,  6, 10, 15, 15, 10,  8,
+    15,  3, 15, 10, 10,  8,  9, 10,  6, 15,  8, 15,  3,  6,  6,  8,
+    15,  3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,  3, 15, 15,  8,
+];
+
+
+
+#[test] fn partition_anchors() {
+    for p in 0 .. 64 {
+        assert_eq!(PARTITIONS2[p] & 1, 0, "pixel 0 of partition {} must be in subset 0", p);
+        assert_eq!(PARTITIONS2[p] >> ANCHOR2[p] & 1, 1, "anchor of partition {} must be in subset 1", p);
+
+        assert_eq!(PARTITIONS3[p][0], 0, "pixel 0 of partition {} must be in subset 0", p);
+        assert_eq!(PARTITIONS3[p][usize::from(ANCHOR3_2[p])], 1, "anchor 1 of partition {} must be in subset 1", p);
+        assert_eq!(PARTITIONS3[p][usize::from(ANCHOR3_3[p])], 2, "anchor 2 of partition {} must be in subset 2", p);
+    }
+}
+
+#[test] fn mode6_solid() {
+    let mut block = 1u128 << 6;
+    let mut pos = 7;
+    for &(value, bits) in [(100, 7), (100, 7), (50, 7), (50, 7), (25, 7), (25, 7), (127, 7), (127, 7), (1, 1), (1, 1)].iter() {
+        block |= (value as u128) << pos;
+        pos += bits;
+    }
+    assert_eq!(decode(&block.to_le_bytes()), [[201, 101, 51, 255]; 16]);
+    assert_eq!(decode(&[0; 16]), [[0; 4]; 16]); // reserved mode
+}
@@ -0,0 +1,25 @@
+/// Reads little-endian, LSB-first bit fields from a 128-bit block, as BC6H and BC7 require.
+pub(super) struct BitReader {
+    bits:   u128,
+    pos:    u32,
+}
+
+impl BitReader {
+    pub fn new(block: &[u8; 16]) -> Self { Self { bits: u128::from_le_bytes(*block), pos: 0 } }
+
+    /// Read the next `n` bits, with the first bit read becoming the LSB of the result.
+    pub fn read(&mut self, n: u32) -> u32 {
+        debug_assert!(n <= 32 && self.pos + n <= 128);
+        if n == 0 { return 0 }
+        let v = (self.bits >> self.pos) & ((1u128 << n) - 1);
+        self.pos += n;
+        v as u32
+    }
+
+    /// Read the next `n` bits, with the first bit read becoming the MSB of the result.
+    pub fn read_reversed(&mut self, n: u32) -> u32 {
+        let mut v = 0;
+        for _ in 0 .. n { v = (v << 1) | self.read(1); }
+        v
+    }
+}
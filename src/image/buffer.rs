@@ -0,0 +1,114 @@
+use std::fmt::{self, Debug, Formatter};
+
+
+
+/// A CPU-side, row-major, 8-bit RGBA image (straight alpha, no padding between rows.)
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Image {
+    width:  u32,
+    height: u32,
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Image {
+    /// Create a `width` x `height` image filled with transparent black.
+    pub fn new(width: u32, height: u32) -> Self { Self::new_filled(width, height, [0, 0, 0, 0]) }
+
+    /// Create a `width` x `height` image filled with `rgba`.
+    pub fn new_filled(width: u32, height: u32, rgba: [u8; 4]) -> Self {
+        Self { width, height, pixels: vec![rgba; width as usize * height as usize] }
+    }
+
+    /// Create a `width` x `height` image by calling `f(x, y)` for each pixel.
+    pub fn from_fn(width: u32, height: u32, mut f: impl FnMut(u32, u32) -> [u8; 4]) -> Self {
+        let mut pixels = Vec::with_capacity(width as usize * height as usize);
+        for y in 0 .. height {
+            for x in 0 .. width {
+                pixels.push(f(x, y));
+            }
+        }
+        Self { width, height, pixels }
+    }
+
+    /// Wrap existing pixels.  Returns `None` if `pixels.len() != width * height`.
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<[u8; 4]>) -> Option<Self> {
+        if pixels.len() != width as usize * height as usize { return None }
+        Some(Self { width, height, pixels })
+    }
+
+    /// Decode a PNG.  Grayscale, paletted, and 16-bit PNGs are normalized to 8-bit RGBA.
+    pub fn from_png_bytes(bytes: &[u8]) -> Result<Self, png::DecodingError> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(
+            png::Transformations::EXPAND        |
+            png::Transformations::GRAY_TO_RGB   |
+            png::Transformations::PACKING       |
+            png::Transformations::STRIP_16
+        );
+        let (info, mut reader) = decoder.read_info()?;
+        let mut buf = vec![0; info.buffer_size()];
+        reader.next_frame(&mut buf)?;
+
+        let pixels = match info.color_type {
+            png::ColorType::RGB  => buf.chunks_exact(3).map(|p| [p[0], p[1], p[2], 0xFF]).collect(),
+            png::ColorType::RGBA => buf.chunks_exact(4).map(|p| [p[0], p[1], p[2], p[3]]).collect(),
+            _other => return Err(png::DecodingError::Other(format!("png::{:?} was expected to be normalized to RGB or RGBA", _other).into())),
+        };
+        Ok(Self { width: info.width, height: info.height, pixels })
+    }
+
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+
+    /// `(width, height)`
+    pub fn dimensions(&self) -> (u32, u32) { (self.width, self.height) }
+
+    /// The number of bytes used by [`pixels`](Self::pixels) (`width * height * 4`)
+    pub fn byte_size(&self) -> usize { self.pixels.len() * 4 }
+
+    /// All pixels, row by row.
+    pub fn pixels(&self) -> &[[u8; 4]] { &self.pixels[..] }
+
+    /// All pixels, row by row.
+    pub fn pixels_mut(&mut self) -> &mut [[u8; 4]] { &mut self.pixels[..] }
+
+    /// All pixels, row by row, as `[r, g, b, a, r, g, b, a, ...]`.
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: [[u8; 4]] has the same layout as 4x as many u8s
+        unsafe { std::slice::from_raw_parts(self.pixels.as_ptr().cast(), self.pixels.len() * 4) }
+    }
+
+    /// Get the pixel at (`x`, `y`), or `None` if out of bounds.
+    pub fn get(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height { return None }
+        Some(self.pixels[y as usize * self.width as usize + x as usize])
+    }
+
+    /// Set the pixel at (`x`, `y`).  Out of bounds writes are ignored.
+    pub fn set(&mut self, x: u32, y: u32, rgba: [u8; 4]) {
+        if x >= self.width || y >= self.height { return }
+        self.pixels[y as usize * self.width as usize + x as usize] = rgba;
+    }
+
+    pub fn into_pixels(self) -> Vec<[u8; 4]> { self.pixels }
+}
+
+impl Debug for Image {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Image({}x{})", self.width, self.height)
+    }
+}
+
+
+
+#[test] fn decode_example_pngs() {
+    let image = Image::from_png_bytes(include_bytes!("../../examples/d3d-16x9.png")).unwrap();
+    assert_eq!(image.dimensions(), (16, 9));
+    assert_eq!(image.byte_size(), 16 * 9 * 4);
+    assert_eq!(image.as_bytes().len(), image.byte_size());
+
+    let image = Image::from_png_bytes(include_bytes!("../../examples/d3d-16x16.png")).unwrap();
+    assert_eq!(image.dimensions(), (16, 16));
+
+    Image::from_png_bytes(b"not a png").unwrap_err();
+}
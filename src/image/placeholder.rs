@@ -0,0 +1,194 @@
+use crate::image::Image;
+
+
+
+/// Which kind of diagnostic placeholder to generate, when a real image isn't available.
+///
+/// Each kind uses a distinct checkerboard, so problems can be told apart at a glance:
+///
+/// | Placeholder       | Checkerboard              | Cells |
+/// | ----------------- | ------------------------- | ----- |
+/// | `Missing`         | magenta / black           | 4px   |
+/// | `DecodeError`     | red / yellow              | 2px   |
+/// | `Unsupported`     | cyan / dark blue          | 8px   |
+/// | `Loading`         | light grey / dark grey    | 8px   |
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Placeholder {
+    /// The file doesn't exist.
+    Missing,
+    /// The file exists, but couldn't be read or decoded (e.g. it's corrupt.)
+    DecodeError,
+    /// The file is in a format that isn't supported.
+    Unsupported,
+    /// The image is still being loaded in the background (see [`AssetLoader`](crate::io::AssetLoader).)
+    Loading,
+}
+
+impl Placeholder {
+    /// The two colors of the checkerboard.
+    pub fn colors(self) -> [[u8; 4]; 2] {
+        match self {
+            Placeholder::Missing        => [[0xFF, 0x00, 0xFF, 0xFF], [0x00, 0x00, 0x00, 0xFF]],
+            Placeholder::DecodeError    => [[0xFF, 0x00, 0x00, 0xFF], [0xFF, 0xFF, 0x00, 0xFF]],
+            Placeholder::Unsupported    => [[0x00, 0xFF, 0xFF, 0xFF], [0x00, 0x00, 0x80, 0xFF]],
+            Placeholder::Loading        => [[0xC0, 0xC0, 0xC0, 0xFF], [0x40, 0x40, 0x40, 0xFF]],
+        }
+    }
+
+    /// The size of each checkerboard cell, in pixels.
+    pub fn cell_size(self) -> u32 {
+        match self {
+            Placeholder::Missing        => 4,
+            Placeholder::DecodeError    => 2,
+            Placeholder::Unsupported    => 8,
+            Placeholder::Loading        => 8,
+        }
+    }
+
+    /// A short, uppercase label suitable for [`image_with_text`](Self::image_with_text).
+    pub fn label(self) -> &'static str {
+        match self {
+            Placeholder::Missing        => "MISSING",
+            Placeholder::DecodeError    => "ERROR",
+            Placeholder::Unsupported    => "UNSUPPORTED",
+            Placeholder::Loading        => "LOADING",
+        }
+    }
+
+    /// A 16x16 checkerboard.
+    pub fn default_image(self) -> Image { self.image(16, 16) }
+
+    /// A `width` x `height` checkerboard.
+    pub fn image(self, width: u32, height: u32) -> Image {
+        let [a, b] = self.colors();
+        let cell = self.cell_size();
+        Image::from_fn(width, height, |x, y| if (x / cell + y / cell).is_multiple_of(2) { a } else { b })
+    }
+
+    /// A `width` x `height` checkerboard, with `text` drawn in the top left corner with a tiny 3x5 pixel font.
+    ///
+    /// Text is wrapped at the edge of the image, and truncated if it doesn't fit.
+    /// Only ASCII letters (drawn uppercase), digits, and `.-_/:?!` are supported, anything else is drawn as `?`.
+    pub fn image_with_text(self, width: u32, height: u32, text: &str) -> Image {
+        let mut image = self.image(width, height);
+        draw_text(&mut image, text);
+        image
+    }
+}
+
+
+
+const GLYPH_W : u32 = 3;
+const GLYPH_H : u32 = 5;
+const ADVANCE_X : u32 = GLYPH_W + 1;
+const ADVANCE_Y : u32 = GLYPH_H + 1;
+
+fn draw_text(image: &mut Image, text: &str) {
+    let (w, h) = image.dimensions();
+    let columns = w.saturating_sub(1) / ADVANCE_X;
+    let rows    = h.saturating_sub(1) / ADVANCE_Y;
+    if columns == 0 || rows == 0 { return }
+
+    let chars = text.chars().take((columns * rows) as usize).collect::<Vec<_>>();
+    for (i, ch) in chars.iter().copied().enumerate() {
+        let (col, row) = (i as u32 % columns, i as u32 / columns);
+        let (x0, y0) = (1 + col * ADVANCE_X, 1 + row * ADVANCE_Y);
+        let glyph = glyph(ch);
+
+        // black backdrop (including a 1px border) for legibility against the checkerboard
+        for y in y0 - 1 .. y0 + ADVANCE_Y {
+            for x in x0 - 1 .. x0 + ADVANCE_X {
+                image.set(x, y, [0, 0, 0, 0xFF]);
+            }
+        }
+        for (j, bit) in glyph.bytes().enumerate() {
+            if bit != b'#' { continue }
+            image.set(x0 + j as u32 % GLYPH_W, y0 + j as u32 / GLYPH_W, [0xFF, 0xFF, 0xFF, 0xFF]);
+        }
+    }
+}
+
+/// 3x5 pixels, row by row
+fn glyph(ch: char) -> &'static str {
+    match ch.to_ascii_uppercase() {
+        'A' => ".#.#.#####.##.#",
+        'B' => "##.#.###.#.###.",
+        'C' => ".###..#..#...##",
+        'D' => "##.#.##.##.###.",
+        'E' => "####..##.#..###",
+        'F' => "####..##.#..#..",
+        'G' => ".###..#.##.#.##",
+        'H' => "#.##.#####.##.#",
+        'I' => "###.#..#..#.###",
+        'J' => "..#..#..##.#.#.",
+        'K' => "#.##.###.#.##.#",
+        'L' => "#..#..#..#..###",
+        'M' => "#.########.##.#",
+        'N' => "##.#.##.##.##.#",
+        'O' => ".#.#.##.##.#.#.",
+        'P' => "##.#.###.#..#..",
+        'Q' => ".#.#.##.###..##",
+        'R' => "##.#.###.#.##.#",
+        'S' => ".###...#...###.",
+        'T' => "###.#..#..#..#.",
+        'U' => "#.##.##.##.####",
+        'V' => "#.##.##.##.#.#.",
+        'W' => "#.##.########.#",
+        'X' => "#.##.#.#.#.##.#",
+        'Y' => "#.##.#.#..#..#.",
+        'Z' => "###..#.#.#..###",
+        '0' => "####.##.##.####",
+        '1' => ".#.##..#..#.###",
+        '2' => "##...#.#.#..###",
+        '3' => "##...#.#...###.",
+        '4' => "#.##.####..#..#",
+        '5' => "####..##...###.",
+        '6' => ".###..####.####",
+        '7' => "###..#.#..#..#.",
+        '8' => "####.#####.####",
+        '9' => "####.####..###.",
+        '.' => ".............#.",
+        '-' => "......###......",
+        '_' => "............###",
+        '/' => "..#..#.#.#..#..",
+        ':' => "....#.....#....",
+        '!' => ".#..#..#.....#.",
+        ' ' => "...............",
+        _   => "##...#.#.....#.", // '?'
+    }
+}
+
+
+
+#[test] fn distinct() {
+    let kinds = [Placeholder::Missing, Placeholder::DecodeError, Placeholder::Unsupported, Placeholder::Loading];
+    for (i, a) in kinds.iter().enumerate() {
+        for b in kinds[i+1..].iter() {
+            assert_ne!(a.default_image(), b.default_image(), "{:?} vs {:?}", a, b);
+        }
+    }
+    let image = Placeholder::Missing.image(8, 8);
+    assert_eq!(image.get(0, 0), Some([0xFF, 0x00, 0xFF, 0xFF]));
+    assert_eq!(image.get(4, 0), Some([0x00, 0x00, 0x00, 0xFF]));
+    assert_eq!(image.get(4, 4), Some([0xFF, 0x00, 0xFF, 0xFF]));
+}
+
+#[test] fn glyphs() {
+    for ch in "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.-_/:! ?".chars() {
+        let g = glyph(ch);
+        assert_eq!(g.len(), (GLYPH_W * GLYPH_H) as usize, "glyph {:?}", ch);
+        assert!(g.bytes().all(|b| b == b'#' || b == b'.'), "glyph {:?}", ch);
+    }
+}
+
+#[test] fn text() {
+    let plain = Placeholder::DecodeError.image(32, 8);
+    let text = Placeholder::DecodeError.image_with_text(32, 8, "I");
+    assert_ne!(plain, text);
+    assert_eq!(text.get(1, 1), Some([0xFF, 0xFF, 0xFF, 0xFF])); // top left of 'I'
+    assert_eq!(text.get(1, 2), Some([0x00, 0x00, 0x00, 0xFF]));
+    assert_eq!(text.get(31, 7), plain.get(31, 7)); // untouched
+
+    Placeholder::Missing.image_with_text(2, 2, "too small to draw anything");
+    Placeholder::Missing.image_with_text(9, 7, "truncated");
+}
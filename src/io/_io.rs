@@ -1,3 +1,24 @@
-//! [`StaticFile`]
+//! Asset I/O: embedded files, archives, and a virtual filesystem, plus loading, decoding, and reloading what's in them
+//!
+//! * [`StaticFile`]s, [`CompressedStaticFile`]s, [`StaticDir`]s, and [`StaticImage`]s (see [`AssetManifest`]) embedded in the executable
+//! * [`pak`] archives
+//! * The [`Vfs`] that overlays them with files on disk
+//! * A [`FileWatcher`] for hot reloading
+//! * An [`AssetRegistry`] of formats to decode assets with
+//! * An [`AssetLoader`] for loading in the background
+//! * An [`AssetManager`] tracking dependencies between assets
 
+mod asset_loader;               pub use asset_loader::*;
+mod asset_manager;              pub use asset_manager::*;
+mod asset_manifest;             pub use asset_manifest::*;
+mod asset_registry;             pub use asset_registry::*;
+mod compressed_static_file;     pub use compressed_static_file::*;
+mod file_watcher;               pub use file_watcher::*;
+pub mod pak;                    pub use pak::{PakArchive, PakCompression, PakEntry, PakWriter};
+mod static_dir;                 pub use static_dir::*;
 mod static_file;                pub use static_file::*;
+mod static_image;               pub use static_image::*;
+mod vfs;                        pub use vfs::*;
+
+#[doc(hidden)] pub use kakistocracy_macros::include_dir as include_dir_impl;
+#[doc(hidden)] pub use kakistocracy_macros::include_file_compressed as include_file_compressed_impl;
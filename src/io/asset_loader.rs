@@ -0,0 +1,256 @@
+use crate::frame::ThreadPool;
+use crate::image::Image;
+use crate::io::{AssetRegistry, AssetSource, Vfs};
+
+use std::error::Error;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll, Waker};
+
+
+
+/// Why an asset failed to load.  Shared by every clone of an [`AssetHandle`].
+pub type AssetError = Arc<dyn Error + Send + Sync>;
+
+/// Reads and decodes assets on background threads, so the frame loop doesn't hitch.  Dropping the loader finishes any queued loads.
+///
+/// Returns [`AssetHandle`]s immediately, which can be polled for their [`LoadState`] each frame,
+/// or `.await`ed (e.g. by a task on a [`FrameScheduler`](crate::frame::FrameScheduler)) - wakeups happen on the loader's threads,
+/// but the result is delivered to whichever thread polls the handle, typically the main thread.
+///
+/// ```
+/// use kakistocracy::io::*;
+///
+/// let loader = AssetLoader::new(2);
+/// let handle = loader.load_image("examples/d3d-16x9.png");
+/// // ...render a placeholder until handle.state() == LoadState::Ready, or:
+/// let image = futures::executor::block_on(handle).unwrap();
+/// assert_eq!(image.dimensions(), (16, 9));
+/// ```
+pub struct AssetLoader {
+    vfs:        Arc<Vfs>,
+    pool:       ThreadPool,
+}
+
+/// A shared handle to an asset that may still be loading.  Clones refer to the same asset.
+///
+/// `.await` it for <code>Result&lt;Arc&lt;T&gt;, [AssetError]&gt;</code>, or check [`state`](Self::state) / [`get`](Self::get) without blocking.
+pub struct AssetHandle<T>(Arc<Shared<T>>);
+
+/// A non-owning reference to an [`AssetHandle`], used by caches to notice when it's been dropped.
+pub(crate) struct WeakAssetHandle<T>(Weak<Shared<T>>);
+
+/// The state of an [`AssetHandle`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LoadState {
+    Pending,
+    Ready,
+    Failed,
+}
+
+struct Shared<T> {
+    id:         u64,
+    debug_name: String,
+    state:      Mutex<(Slot<T>, Vec<Waker>)>,
+}
+
+enum Slot<T> {
+    Pending,
+    Ready(Arc<T>),
+    Failed(AssetError),
+}
+
+impl AssetLoader {
+    /// Create a loader reading from [`Vfs::global`], with `threads` background threads (at least 1.)
+    pub fn new(threads: usize) -> Self { Self::with_vfs(Vfs::global().clone(), threads) }
+
+    /// Create a loader reading from `vfs`, with `threads` background threads (at least 1.)
+    pub fn with_vfs(vfs: Arc<Vfs>, threads: usize) -> Self {
+        Self { vfs, pool: ThreadPool::new("kakistocracy::io::AssetLoader", threads) }
+    }
+
+    pub fn vfs(&self) -> &Arc<Vfs> { &self.vfs }
+
+    /// Run `f` on a background thread.
+    pub fn spawn<T: Send + Sync + 'static>(&self, debug_name: impl Into<String>, f: impl FnOnce() -> Result<T, AssetError> + Send + 'static) -> AssetHandle<T> {
+        let handle = AssetHandle::pending(debug_name.into());
+        let h = handle.clone();
+        self.pool.execute(move || {
+            let result = catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| Err(Arc::from(Box::<dyn Error + Send + Sync>::from(format!("{}: panicked while loading", h.debug_name())))));
+            h.complete(result);
+        });
+        handle
+    }
+
+    /// Read the file at `path` (through the loader's [`Vfs`]) and `decode` it, on a background thread.
+    pub fn load<T: Send + Sync + 'static>(&self, path: impl AsRef<Path>, decode: impl FnOnce(&[u8]) -> Result<T, AssetError> + Send + 'static) -> AssetHandle<T> {
+        let path = path.as_ref().to_path_buf();
+        let vfs = self.vfs.clone();
+        self.spawn(path.display().to_string(), move || {
+            let file = vfs.read(&path).map_err(|err| Arc::new(err) as AssetError)?;
+            decode(&file.data)
+        })
+    }
+
+    /// Read the file at `path` on a background thread.
+    pub fn load_bytes(&self, path: impl AsRef<Path>) -> AssetHandle<Vec<u8>> {
+        self.load(path, |bytes| Ok(bytes.to_vec()))
+    }
+
+    /// Read the file at `path` and decode it as a `T` with [`AssetRegistry::global`], on a background thread.
+    pub fn load_as<T: Send + Sync + 'static>(&self, path: impl AsRef<Path>) -> AssetHandle<T> {
+        let path = path.as_ref().to_path_buf();
+        let vfs = self.vfs.clone();
+        self.spawn(path.display().to_string(), move || {
+            let file = vfs.read(&path).map_err(|err| Arc::new(err) as AssetError)?;
+            AssetRegistry::global().load::<T>(AssetSource::new(&path.to_string_lossy(), &file.data)).map_err(|err| Arc::new(err) as AssetError)
+        })
+    }
+
+    /// Read and decode the image at `path` on a background thread.
+    pub fn load_image(&self, path: impl AsRef<Path>) -> AssetHandle<Image> { self.load_as(path) }
+}
+
+impl Debug for AssetLoader {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "AssetLoader({} threads)", self.pool.threads())
+    }
+}
+
+impl<T> AssetHandle<T> {
+    /// An already loaded asset.
+    pub fn ready(debug_name: impl Into<String>, value: T) -> Self {
+        let handle = Self::pending(debug_name.into());
+        handle.clone().complete(Ok(value));
+        handle
+    }
+
+    /// An asset that already failed to load.
+    pub fn failed(debug_name: impl Into<String>, error: AssetError) -> Self {
+        let handle = Self::pending(debug_name.into());
+        handle.clone().complete(Err(error));
+        handle
+    }
+
+    /// A process-unique identifier for this asset (shared by clones.)
+    pub fn id(&self) -> u64 { self.0.id }
+
+    /// The path (or name passed to [`AssetLoader::spawn`]) of the asset.
+    pub fn debug_name(&self) -> &str { &self.0.debug_name }
+
+    pub fn state(&self) -> LoadState {
+        match self.0.state.lock().unwrap().0 {
+            Slot::Pending   => LoadState::Pending,
+            Slot::Ready(_)  => LoadState::Ready,
+            Slot::Failed(_) => LoadState::Failed,
+        }
+    }
+
+    pub fn is_pending(&self) -> bool { self.state() == LoadState::Pending }
+
+    /// The asset, if it's finished loading successfully.
+    pub fn get(&self) -> Option<Arc<T>> {
+        match &self.0.state.lock().unwrap().0 { Slot::Ready(value) => Some(value.clone()), _ => None }
+    }
+
+    /// Why the asset failed to load, if it did.
+    pub fn error(&self) -> Option<AssetError> {
+        match &self.0.state.lock().unwrap().0 { Slot::Failed(err) => Some(err.clone()), _ => None }
+    }
+
+    pub(crate) fn downgrade(&self) -> WeakAssetHandle<T> { WeakAssetHandle(Arc::downgrade(&self.0)) }
+
+    fn pending(debug_name: String) -> Self {
+        static NEXT_ID : AtomicU64 = AtomicU64::new(1);
+        Self(Arc::new(Shared { id: NEXT_ID.fetch_add(1, Ordering::Relaxed), debug_name, state: Mutex::new((Slot::Pending, Vec::new())) }))
+    }
+
+    fn complete(self, result: Result<T, AssetError>) {
+        let wakers = {
+            let mut state = self.0.state.lock().unwrap();
+            state.0 = match result { Ok(value) => Slot::Ready(Arc::new(value)), Err(err) => Slot::Failed(err) };
+            std::mem::take(&mut state.1)
+        };
+        drop(self); // before waking, so woken tasks see an accurate reference count
+        for waker in wakers { waker.wake(); }
+    }
+}
+
+impl<T> WeakAssetHandle<T> {
+    pub fn is_alive(&self) -> bool { self.0.strong_count() > 0 }
+}
+
+impl<T> Clone for AssetHandle<T> {
+    fn clone(&self) -> Self { Self(self.0.clone()) }
+}
+
+impl<T> Clone for WeakAssetHandle<T> {
+    fn clone(&self) -> Self { Self(self.0.clone()) }
+}
+
+impl<T> Debug for AssetHandle<T> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "AssetHandle({:?}, {:?})", self.0.debug_name, self.state())
+    }
+}
+
+impl<T> Future for AssetHandle<T> {
+    type Output = Result<Arc<T>, AssetError>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.0.state.lock().unwrap();
+        match &state.0 {
+            Slot::Ready(value)  => Poll::Ready(Ok(value.clone())),
+            Slot::Failed(err)   => Poll::Ready(Err(err.clone())),
+            Slot::Pending       => {
+                if !state.1.iter().any(|w| w.will_wake(cx.waker())) { state.1.push(cx.waker().clone()); }
+                Poll::Pending
+            },
+        }
+    }
+}
+
+
+
+#[test] fn background_loads() {
+    use futures::executor::{LocalPool, block_on};
+    use futures::task::LocalSpawnExt;
+
+    static EXAMPLES : crate::io::StaticDir = crate::include_dir!(CARGO_MANIFEST_DIR / "examples", "*.png");
+    let vfs = Arc::new(Vfs::new());
+    vfs.mount("", 0, &EXAMPLES);
+    let loader = AssetLoader::with_vfs(vfs, 2);
+
+    let image = loader.load_image("d3d-16x9.png");
+    let missing = loader.load_image("missing.png");
+    let bytes = loader.load_bytes("d3d-16x16.png");
+    assert_eq!(block_on(image.clone()).unwrap().dimensions(), (16, 9));
+    assert_eq!(image.state(), LoadState::Ready);
+    assert_eq!(image.get().unwrap().dimensions(), (16, 9));
+    assert!(block_on(missing.clone()).is_err());
+    assert_eq!(missing.state(), LoadState::Failed);
+    assert!(missing.error().unwrap().to_string().contains("missing.png"));
+    assert_eq!(&block_on(bytes).unwrap()[..], EXAMPLES.get("d3d-16x16.png").unwrap().as_bytes());
+
+    // resolves on the thread polling the handle, once the background thread finishes
+    let (go, wait) = mpsc::channel::<()>();
+    let slow = loader.spawn("slow", move || { wait.recv().unwrap(); Ok(42) });
+    let mut pool = LocalPool::new();
+    let result = Arc::new(Mutex::new(None));
+    let r = result.clone();
+    let s = slow.clone();
+    pool.spawner().spawn_local(async move { *r.lock().unwrap() = Some(*s.await.unwrap()); }).unwrap();
+    pool.run_until_stalled();
+    assert_eq!(slow.state(), LoadState::Pending);
+    assert_eq!(*result.lock().unwrap(), None);
+    go.send(()).unwrap();
+    pool.run();
+    assert_eq!(*result.lock().unwrap(), Some(42));
+
+    let panics = loader.spawn::<()>("panics", || panic!("oops"));
+    assert!(block_on(panics).unwrap_err().to_string().contains("panicked"));
+}
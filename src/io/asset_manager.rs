@@ -0,0 +1,444 @@
+use crate::io::*;
+
+use std::any::{Any, TypeId};
+use std::cell::{Cell, RefCell};
+use std::collections::*;
+use std::error::Error;
+use std::fmt::{self, Debug, Formatter};
+use std::path::Path;
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+
+
+
+/// Loads assets that depend on other assets (atlases → images, fonts → page textures, tilemaps → tilesets),
+/// sharing, reference counting, and hot reloading them as a graph.
+///
+/// *   Assets are identified by an [`AssetKey`] (and type): a [`Vfs`] path, or a [`StaticFile`] (by *contents*, per [`StaticFileKey`].)
+///     Loading the same key twice returns the same [`Asset`].
+/// *   Loaders record dependencies by loading them through their [`LoadContext`].
+///     Assets keep their dependencies loaded, and are unloaded when their last [`Asset`] handle (or dependent) is dropped.
+/// *   [`reload_changed`](Self::reload_changed) reloads assets whose files have changed, *and* everything depending on them.
+///
+/// Types without a loader registered via [`set_loader`](Self::set_loader) are decoded with [`AssetRegistry::global`], and have no dependencies.
+///
+/// ```
+/// use kakistocracy::image::Image;
+/// use kakistocracy::io::*;
+///
+/// struct Atlas { pages: Vec<Asset<Image>> }
+///
+/// static FILES : StaticDir = kakistocracy::include_dir!(CARGO_MANIFEST_DIR / "examples", "*.png");
+/// let vfs = std::sync::Arc::new(Vfs::new());
+/// vfs.mount("", 0, &FILES);
+///
+/// let assets = AssetManager::with_vfs(vfs);
+/// assets.set_loader::<Atlas>(|ctx, source| {
+///     let pages = std::str::from_utf8(source.bytes)?.lines().map(|page| ctx.load::<Image>(ctx.relative_path(page))).collect::<Result<_, _>>()?;
+///     Ok(Atlas { pages })
+/// });
+///
+/// static ATLAS : StaticFile = StaticFile { path: "atlas.txt", data: b"d3d-16x9.png\nd3d-16x16.png", _non_exhaustive_init_via_macros_only: () };
+/// let atlas = assets.load::<Atlas>(&ATLAS).unwrap();
+/// assert_eq!(atlas.get().pages[1].get().dimensions(), (16, 16));
+/// assert_eq!(assets.dependencies(&AssetKey::from(&ATLAS)).len(), 2);
+///
+/// drop(atlas);
+/// assets.collect();
+/// assert_eq!(assets.len(), 0, "atlas and pages were unloaded");
+/// ```
+pub struct AssetManager {
+    vfs:        Arc<Vfs>,
+    loaders:    RefCell<HashMap<TypeId, Box<dyn Any>>>,
+    entries:    RefCell<HashMap<EntryKey, Entry>>,
+    loading:    RefCell<Vec<EntryKey>>,
+}
+
+/// What an [`AssetManager`] asset is loaded from.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum AssetKey {
+    /// A normalized [`Vfs`] path.  Reloaded when the file changes.
+    Path(String),
+    /// An embedded file, identified by its contents.  Never reloaded.
+    StaticFile(&'static StaticFile),
+}
+
+/// Loads an asset of type `T` for an [`AssetManager`], loading any dependencies through the [`LoadContext`].
+pub type LoadFn<T> = fn(&mut LoadContext, AssetSource) -> Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Passed to [`LoadFn`]s, to load (and record) dependencies.
+pub struct LoadContext<'m> {
+    manager:        &'m AssetManager,
+    key:            AssetKey,
+    dependencies:   Vec<(EntryKey, Rc<dyn Any>)>,
+}
+
+/// A reference counted handle to an asset loaded by an [`AssetManager`].  Clones refer to the same asset.
+///
+/// The asset is replaced in place when hot reloaded, so [`get`](Self::get) it when needed rather than holding onto the result.
+pub struct Asset<T>(Rc<Slot<T>>);
+
+/// The result of reloading one asset in [`AssetManager::reload_changed`].
+#[derive(Clone, Debug)]
+pub struct AssetReload {
+    pub key:    AssetKey,
+    /// On failure, the previously loaded asset is kept.
+    pub result: Result<(), AssetError>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct EntryKey {
+    type_id:    TypeId,
+    key:        AssetKey,
+}
+
+struct Entry {
+    slot:           Weak<dyn Any>,
+    /// Reload the asset, given its slot (which must still be alive.)
+    reload:         ReloadFn,
+    dependencies:   Vec<EntryKey>,
+    /// For [`AssetKey::Path`]s, the file as of when it was loaded.
+    stamp:          Option<FileStamp>,
+}
+
+type ReloadFn = fn(&AssetManager, Rc<dyn Any>) -> Result<Option<FileStamp>, AssetError>;
+
+/// An asset, its dependencies, and the file it was loaded from.
+type Decoded<T> = (T, Vec<(EntryKey, Rc<dyn Any>)>, Option<FileStamp>);
+
+struct Slot<T> {
+    key:            AssetKey,
+    value:          RefCell<Rc<T>>,
+    generation:     Cell<u64>,
+    /// Keeps dependencies loaded for as long as this asset is.
+    dependencies:   RefCell<Vec<Rc<dyn Any>>>,
+}
+
+impl AssetManager {
+    /// Create a manager reading paths from [`Vfs::global`].
+    pub fn new() -> Self { Self::with_vfs(Vfs::global().clone()) }
+
+    /// Create a manager reading paths from `vfs`.
+    pub fn with_vfs(vfs: Arc<Vfs>) -> Self {
+        Self { vfs, loaders: Default::default(), entries: Default::default(), loading: Default::default() }
+    }
+
+    pub fn vfs(&self) -> &Arc<Vfs> { &self.vfs }
+
+    /// Load `T`s with `loader` instead of [`AssetRegistry::global`].  Assets already loaded are unaffected until reloaded.
+    pub fn set_loader<T: 'static>(&self, loader: LoadFn<T>) {
+        self.loaders.borrow_mut().insert(TypeId::of::<T>(), Box::new(loader));
+    }
+
+    /// Load (or get the already loaded) `T` for `key`.
+    pub fn load<T: 'static>(&self, key: impl Into<AssetKey>) -> Result<Asset<T>, AssetError> {
+        Ok(Asset(self.load_slot::<T>(key.into())?))
+    }
+
+    /// The loaded asset for `key`, if any.
+    pub fn get<T: 'static>(&self, key: impl Into<AssetKey>) -> Option<Asset<T>> {
+        let slot = self.entries.borrow().get(&EntryKey::of::<T>(key.into()))?.slot.upgrade()?;
+        slot.downcast::<Slot<T>>().ok().map(Asset)
+    }
+
+    /// The number of assets currently loaded (including ones whose handles have been dropped since the last [`collect`](Self::collect).)
+    pub fn len(&self) -> usize { self.entries.borrow().len() }
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// `true` if an asset of any type is loaded for `key`.
+    pub fn is_loaded(&self, key: &AssetKey) -> bool {
+        self.entries.borrow().iter().any(|(k, e)| k.key == *key && e.slot.strong_count() > 0)
+    }
+
+    /// The assets that the asset(s) loaded for `key` directly depend on.
+    pub fn dependencies(&self, key: &AssetKey) -> Vec<AssetKey> {
+        let entries = self.entries.borrow();
+        let mut deps = entries.iter().filter(|(k, _)| k.key == *key).flat_map(|(_, e)| e.dependencies.iter().map(|d| d.key.clone())).collect::<Vec<_>>();
+        deps.dedup();
+        deps
+    }
+
+    /// The assets that directly depend on the asset(s) loaded for `key`.
+    pub fn dependents(&self, key: &AssetKey) -> Vec<AssetKey> {
+        self.entries.borrow().iter().filter(|(_, e)| e.dependencies.iter().any(|d| d.key == *key)).map(|(k, _)| k.key.clone()).collect()
+    }
+
+    /// Forget assets that are no longer referenced by any [`Asset`] handle or dependent.  Returns the number of assets forgotten.
+    ///
+    /// (The assets themselves are freed as soon as they're unreferenced - this just cleans up bookkeeping.)
+    pub fn collect(&self) -> usize {
+        let mut entries = self.entries.borrow_mut();
+        let before = entries.len();
+        entries.retain(|_, e| e.slot.strong_count() > 0);
+        before - entries.len()
+    }
+
+    /// Forget the asset(s) for `key`, and everything depending on them, so they're loaded fresh next time.
+    /// Existing [`Asset`] handles keep their current values, but won't be hot reloaded.  Returns the number of assets forgotten.
+    pub fn unload(&self, key: &AssetKey) -> usize {
+        let mut entries = self.entries.borrow_mut();
+        let doomed = with_dependents(&entries, entries.keys().filter(|k| k.key == *key).cloned().collect());
+        for k in doomed.iter() { entries.remove(k); }
+        doomed.len()
+    }
+
+    /// Reload every [`AssetKey::Path`] asset whose file has changed since it was loaded, and every asset depending on those (in dependency order.)
+    pub fn reload_changed(&self) -> Vec<AssetReload> {
+        self.collect();
+        let changed = self.entries.borrow().iter().filter(|(k, e)| match &k.key {
+            AssetKey::Path(path) => self.vfs.metadata(path).ok().filter(|m| !m.is_dir).map(FileStamp::from) != e.stamp,
+            AssetKey::StaticFile(_) => false,
+        }).map(|(k, _)| k.clone()).collect::<Vec<_>>();
+        if changed.is_empty() { return Vec::new() }
+
+        let mut pending = with_dependents(&self.entries.borrow(), changed);
+        let mut reloads = Vec::new();
+        while !pending.is_empty() {
+            // reload an asset only after any dependencies that are also being reloaded
+            let next = {
+                let entries = self.entries.borrow();
+                pending.iter().find(|k| entries.get(k).is_none_or(|e| e.dependencies.iter().all(|d| !pending.contains(d)))).cloned().unwrap_or_else(|| pending.iter().next().unwrap().clone())
+            };
+            pending.remove(&next);
+            let (reload, slot) = match self.entries.borrow().get(&next).and_then(|e| Some((e.reload, e.slot.upgrade()?))) {
+                Some(r) => r,
+                None    => continue,
+            };
+            let result = reload(self, slot);
+            if let (Ok(stamp), Some(entry)) = (result.as_ref(), self.entries.borrow_mut().get_mut(&next)) { entry.stamp = *stamp; }
+            reloads.push(AssetReload { key: next.key, result: result.map(|_| ()) });
+        }
+        reloads
+    }
+}
+
+impl AssetManager {
+    fn load_slot<T: 'static>(&self, key: AssetKey) -> Result<Rc<Slot<T>>, AssetError> {
+        let ekey = EntryKey::of::<T>(key.clone());
+        if let Some(slot) = self.entries.borrow().get(&ekey).and_then(|e| e.slot.upgrade()) {
+            return Ok(slot.downcast::<Slot<T>>().expect("AssetManager entry type mismatch"));
+        }
+
+        let (value, dependencies, stamp) = self.decode::<T>(&ekey)?;
+        let slot = Rc::new(Slot { key, value: RefCell::new(Rc::new(value)), generation: Cell::new(0), dependencies: RefCell::new(dependencies.iter().map(|(_, rc)| rc.clone()).collect()) });
+        let weak : Weak<dyn Any> = Rc::downgrade(&(slot.clone() as Rc<dyn Any>));
+        self.entries.borrow_mut().insert(ekey, Entry { slot: weak, reload: reload::<T>, dependencies: dependencies.into_iter().map(|(k, _)| k).collect(), stamp });
+        Ok(slot)
+    }
+
+    /// Read and decode the asset for `ekey`, without touching its entry (if any.)
+    fn decode<T: 'static>(&self, ekey: &EntryKey) -> Result<Decoded<T>, AssetError> {
+        if self.loading.borrow().contains(ekey) {
+            return Err(AssetError::from(Box::<dyn Error + Send + Sync>::from(format!("{:?}: dependency cycle", ekey.key))));
+        }
+
+        let (path, bytes, stamp) = match &ekey.key {
+            AssetKey::StaticFile(file)  => (file.path_str().to_string(), std::borrow::Cow::Borrowed(file.as_bytes()), None),
+            AssetKey::Path(path)        => {
+                let file = self.vfs.read(path).map_err(|err| Arc::new(err) as AssetError)?;
+                let stamp = FileStamp { modified: file.modified, len: file.data.len() as u64 };
+                (path.clone(), file.data, Some(stamp))
+            },
+        };
+        let source = AssetSource::new(&path, &bytes);
+
+        let loader = self.loaders.borrow().get(&TypeId::of::<T>()).and_then(|l| l.downcast_ref::<LoadFn<T>>()).copied();
+        let mut ctx = LoadContext { manager: self, key: ekey.key.clone(), dependencies: Vec::new() };
+        self.loading.borrow_mut().push(ekey.clone());
+        let result = match loader {
+            Some(loader)    => loader(&mut ctx, source).map_err(AssetError::from),
+            None            => AssetRegistry::global().load::<T>(source).map_err(|err| Arc::new(err) as AssetError),
+        };
+        self.loading.borrow_mut().pop();
+        Ok((result?, ctx.dependencies, stamp))
+    }
+}
+
+fn reload<T: 'static>(manager: &AssetManager, slot: Rc<dyn Any>) -> Result<Option<FileStamp>, AssetError> {
+    let slot = slot.downcast::<Slot<T>>().expect("AssetManager entry type mismatch");
+    let ekey = EntryKey::of::<T>(slot.key.clone());
+    let (value, dependencies, stamp) = manager.decode::<T>(&ekey)?;
+    *slot.value.borrow_mut() = Rc::new(value);
+    *slot.dependencies.borrow_mut() = dependencies.iter().map(|(_, rc)| rc.clone()).collect();
+    slot.generation.set(slot.generation.get() + 1);
+    if let Some(entry) = manager.entries.borrow_mut().get_mut(&ekey) { entry.dependencies = dependencies.into_iter().map(|(k, _)| k).collect(); }
+    Ok(stamp)
+}
+
+/// `keys`, and everything (transitively) depending on them.
+fn with_dependents(entries: &HashMap<EntryKey, Entry>, keys: Vec<EntryKey>) -> HashSet<EntryKey> {
+    let mut all = HashSet::new();
+    let mut queue = keys;
+    while let Some(key) = queue.pop() {
+        if !all.insert(key.clone()) { continue }
+        queue.extend(entries.iter().filter(|(_, e)| e.dependencies.contains(&key)).map(|(k, _)| k.clone()));
+    }
+    all
+}
+
+impl Default for AssetManager {
+    fn default() -> Self { Self::new() }
+}
+
+impl Debug for AssetManager {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        let entries = self.entries.borrow();
+        fmt.debug_list().entries(entries.keys().map(|k| &k.key)).finish()
+    }
+}
+
+impl<'m> LoadContext<'m> {
+    /// The key of the asset being loaded.
+    pub fn key(&self) -> &AssetKey { &self.key }
+
+    /// Load (or get the already loaded) `T` for `key`, as a dependency of the asset being loaded.
+    pub fn load<T: 'static>(&mut self, key: impl Into<AssetKey>) -> Result<Asset<T>, AssetError> {
+        let key = key.into();
+        let slot = self.manager.load_slot::<T>(key.clone())?;
+        self.dependencies.push((EntryKey::of::<T>(key), slot.clone()));
+        Ok(Asset(slot))
+    }
+
+    /// `path`, relative to the directory of the asset being loaded.
+    pub fn relative_path(&self, path: &str) -> String {
+        let this = match &self.key { AssetKey::Path(p) => p.as_str(), AssetKey::StaticFile(f) => f.path_str() };
+        match this.rfind(['/', '\\']) {
+            Some(slash) => format!("{}/{}", &this[..slash], path),
+            None        => path.into(),
+        }
+    }
+}
+
+impl<T> Asset<T> {
+    /// The current value of the asset.
+    pub fn get(&self) -> Rc<T> { self.0.value.borrow().clone() }
+
+    pub fn key(&self) -> &AssetKey { &self.0.key }
+
+    /// The number of times this asset has been reloaded.
+    pub fn generation(&self) -> u64 { self.0.generation.get() }
+
+    /// `true` if `a` and `b` are handles to the same asset.
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool { Rc::ptr_eq(&a.0, &b.0) }
+}
+
+impl<T> Clone for Asset<T> {
+    fn clone(&self) -> Self { Self(self.0.clone()) }
+}
+
+impl<T> Debug for Asset<T> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Asset({:?}, generation {})", self.0.key, self.0.generation.get())
+    }
+}
+
+impl AssetKey {
+    /// A [`Vfs`] path.  Relative paths are [normalized](normalize_path).
+    pub fn path(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_string_lossy();
+        if Path::new(&*path).is_absolute() { return AssetKey::Path(path.into_owned()) }
+        AssetKey::Path(normalize_path(&path).unwrap_or_else(|_| path.into_owned()))
+    }
+}
+
+impl Debug for AssetKey {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetKey::Path(path)        => write!(fmt, "{:?}", path),
+            AssetKey::StaticFile(file)  => write!(fmt, "StaticFile({:?})", file.path_str()),
+        }
+    }
+}
+
+impl From<&str                  > for AssetKey { fn from(path: &str                 ) -> Self { AssetKey::path(path) } }
+impl From<String                > for AssetKey { fn from(path: String               ) -> Self { AssetKey::path(path) } }
+impl From<&Path                 > for AssetKey { fn from(path: &Path                ) -> Self { AssetKey::path(path) } }
+impl From<&'static StaticFile   > for AssetKey { fn from(file: &'static StaticFile  ) -> Self { AssetKey::StaticFile(file) } }
+
+impl EntryKey {
+    fn of<T: 'static>(key: AssetKey) -> Self { Self { type_id: TypeId::of::<T>(), key } }
+}
+
+
+
+
+#[test] fn dependencies() {
+    let dir = std::env::temp_dir().join(format!("kakistocracy-asset-manager-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("tiles")).unwrap();
+    std::fs::write(dir.join("tiles/grass.txt"), "grass").unwrap();
+    std::fs::write(dir.join("tiles/water.txt"), "water").unwrap();
+    std::fs::write(dir.join("tiles/set.txt"), "grass.txt\nwater.txt").unwrap();
+    std::fs::write(dir.join("map.txt"), "tiles/set.txt").unwrap();
+    std::fs::write(dir.join("cycle.txt"), "cycle.txt").unwrap();
+
+    struct Tileset { tiles: Vec<Asset<String>> }
+    struct Map { tileset: Asset<Tileset> }
+    struct Chain { _next: Asset<Chain> }
+
+    let vfs = Arc::new(Vfs::new());
+    vfs.mount("", 0, DiskDir::new(&dir));
+    let assets = AssetManager::with_vfs(vfs);
+    assets.set_loader::<Tileset>(|ctx, source| {
+        let tiles = std::str::from_utf8(source.bytes)?.lines().map(|tile| ctx.load::<String>(ctx.relative_path(tile))).collect::<Result<_, _>>()?;
+        Ok(Tileset { tiles })
+    });
+    assets.set_loader::<Map>(|ctx, source| Ok(Map { tileset: ctx.load(std::str::from_utf8(source.bytes)?)? }));
+    assets.set_loader::<Chain>(|ctx, source| Ok(Chain { _next: ctx.load(std::str::from_utf8(source.bytes)?)? }));
+
+    let map = assets.load::<Map>("./map.txt").unwrap();
+    assert_eq!(*map.get().tileset.get().tiles[1].get(), "water");
+    assert_eq!(assets.len(), 4);
+    assert_eq!(assets.dependencies(&"map.txt".into()), [AssetKey::from("tiles/set.txt")]);
+    assert_eq!(assets.dependents(&"tiles/grass.txt".into()), [AssetKey::from("tiles/set.txt")]);
+    let water = assets.get::<String>("tiles/water.txt").unwrap();
+    assert!(Asset::ptr_eq(&water, &assets.load("tiles/water.txt").unwrap()), "loads are shared");
+    assert!(assets.get::<Tileset>("tiles/water.txt").is_none(), "keyed by type too");
+
+    // hot reloading propagates to dependents, in dependency order
+    std::fs::write(dir.join("tiles/water.txt"), "deep water").unwrap();
+    let reloads = assets.reload_changed();
+    assert_eq!(reloads.iter().map(|r| r.key.clone()).collect::<Vec<_>>(), [AssetKey::from("tiles/water.txt"), "tiles/set.txt".into(), "map.txt".into()]);
+    assert!(reloads.iter().all(|r| r.result.is_ok()));
+    assert_eq!(*water.get(), "deep water");
+    assert_eq!(*map.get().tileset.get().tiles[1].get(), "deep water");
+    assert_eq!((water.generation(), map.generation()), (1, 1));
+    assert!(assets.reload_changed().is_empty());
+
+    // failed reloads keep the previous asset
+    std::fs::remove_file(dir.join("tiles/grass.txt")).unwrap();
+    let reloads = assets.reload_changed();
+    assert_eq!(reloads.iter().filter(|r| r.result.is_err()).map(|r| r.key.clone()).collect::<Vec<_>>(), [AssetKey::from("tiles/grass.txt")]);
+    assert_eq!(*map.get().tileset.get().tiles[0].get(), "grass");
+
+    // unloading
+    drop(water);
+    assert_eq!(assets.collect(), 0, "still referenced by the tileset");
+    drop(map);
+    assert_eq!(assets.collect(), 4);
+    assert!(assets.is_empty());
+
+    let map = assets.load::<Map>("map.txt");
+    assert!(map.is_err(), "grass.txt is still missing");
+    std::fs::write(dir.join("tiles/grass.txt"), "grass").unwrap();
+    let map = assets.load::<Map>("map.txt").unwrap();
+    assert_eq!(assets.unload(&"tiles/set.txt".into()), 2, "tileset and map");
+    assert!(Asset::ptr_eq(&map.get().tileset.get().tiles[0], &assets.load("tiles/grass.txt").unwrap()));
+    assert!(!Asset::ptr_eq(&map, &assets.load("map.txt").unwrap()));
+
+    let err = assets.load::<Chain>("cycle.txt").map(|_| ()).unwrap_err();
+    assert!(err.to_string().contains("dependency cycle"), "{}", err);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test] fn static_identity() {
+    static A : StaticFile = StaticFile { path: "a.txt", data: b"same", _non_exhaustive_init_via_macros_only: () };
+    static B : StaticFile = StaticFile { path: "b.txt", data: b"same", _non_exhaustive_init_via_macros_only: () };
+    let assets = AssetManager::with_vfs(Arc::new(Vfs::new()));
+    let a = assets.load::<String>(&A).unwrap();
+    let b = assets.load::<String>(&B).unwrap();
+    assert!(Asset::ptr_eq(&a, &b), "identical contents are the same asset");
+    assert_eq!(assets.len(), 1);
+    assert!(assets.reload_changed().is_empty());
+}
@@ -0,0 +1,213 @@
+use crate::io::static_image::png_dimensions;
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io;
+use std::path::*;
+
+
+
+/// Generates a Rust module of typed constants for every file in an asset directory, from a build script.
+///
+/// Each file becomes a `pub static` [`StaticFile`](crate::io::StaticFile) (or [`StaticImage`](crate::io::StaticImage) for PNGs, including their dimensions),
+/// named after the file in `SCREAMING_SNAKE_CASE` (`d3d-16x9.png` → `D3D_16X9_PNG`.)  Subdirectories become `snake_case` submodules.
+/// Renaming or deleting an asset then breaks the build where it's used, instead of at runtime.
+///
+/// ### Example
+///
+/// ```no_run
+/// // build.rs (with kakistocracy in [build-dependencies]), in fn main:
+/// kakistocracy::io::AssetManifest::new("assets").write_to_out_dir("assets.rs").unwrap();
+/// ```
+///
+/// ```ignore
+/// // src/main.rs
+/// #[allow(dead_code)] mod assets { include!(concat!(env!("OUT_DIR"), "/assets.rs")); }
+///
+/// sprite::render1(target, &assets::sprites::PLAYER_PNG, ...);
+/// assert_eq!(assets::sprites::PLAYER_PNG.dimensions(), (16, 16));
+/// ```
+pub struct AssetManifest {
+    dir:        PathBuf,
+    crate_path: String,
+    filter:     Option<Filter>,
+}
+
+impl AssetManifest {
+    /// Generate constants for every file in `dir` (relative to `CARGO_MANIFEST_DIR`, if set.)
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into(), crate_path: "::kakistocracy".into(), filter: None }
+    }
+
+    /// The path generated code uses to refer to this crate (default `::kakistocracy`), in case it's been renamed or re-exported.
+    pub fn set_crate_path(&mut self, path: impl Into<String>) { self.crate_path = path.into(); }
+
+    /// Only generate constants for files for which `filter` returns `true`, given their `/`-separated path relative to the asset directory.
+    ///
+    /// Files and directories starting with `.` are always skipped.
+    pub fn set_filter(&mut self, filter: impl Fn(&str) -> bool + 'static) { self.filter = Some(Box::new(filter)); }
+
+    /// Generate the module's source code.
+    ///
+    /// Fails if the directory can't be read, or if two files or directories would generate the same name.
+    pub fn generate(&self) -> io::Result<String> {
+        let root = self.root();
+        let mut out = String::new();
+        writeln!(out, "// Generated by kakistocracy::io::AssetManifest from {:?}.  Do not edit.", root.display().to_string()).unwrap();
+        self.generate_dir(&root, "", 0, &mut out)?;
+        Ok(out)
+    }
+
+    /// Generate the module, and write it to `path` (unless it's unchanged, to avoid needless rebuilds.)
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let code = self.generate()?;
+        let path = path.as_ref();
+        if std::fs::read_to_string(path).ok().as_deref() == Some(code.as_str()) { return Ok(()) }
+        std::fs::write(path, code)
+    }
+
+    /// Generate the module into `$OUT_DIR/{file_name}`, and tell cargo to rerun the build script when the asset directory changes.
+    pub fn write_to_out_dir(&self, file_name: &str) -> io::Result<PathBuf> {
+        let out_dir = std::env::var_os("OUT_DIR").ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "OUT_DIR not set - AssetManifest::write_to_out_dir should be called from a build script"))?;
+        let path = Path::new(&out_dir).join(file_name);
+        self.write(&path)?;
+        println!("cargo:rerun-if-changed={}", self.root().display());
+        Ok(path)
+    }
+
+    fn root(&self) -> PathBuf {
+        match std::env::var_os("CARGO_MANIFEST_DIR") {
+            Some(manifest_dir) if self.dir.is_relative() => Path::new(&manifest_dir).join(&self.dir),
+            _ => self.dir.clone(),
+        }
+    }
+
+    fn generate_dir(&self, dir: &Path, rel: &str, depth: usize, out: &mut String) -> io::Result<()> {
+        let mut files = BTreeMap::new();
+        let mut dirs = BTreeMap::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') { continue }
+            let rel = if rel.is_empty() { name.clone() } else { format!("{}/{}", rel, name) };
+            if entry.file_type()?.is_dir() {
+                insert_unique(&mut dirs, module_ident(&name), (entry.path(), rel))?;
+            } else if self.filter.as_ref().is_none_or(|f| f(&rel)) {
+                insert_unique(&mut files, const_ident(&name), (entry.path(), rel))?;
+            }
+        }
+
+        let indent = "    ".repeat(depth);
+        let krate = &self.crate_path;
+        let dir_str = self.dir.to_string_lossy().replace('\\', "/");
+        for (ident, (path, rel)) in files {
+            let abs = path.canonicalize()?.to_string_lossy().into_owned();
+            let file = format!("{k}::io::StaticFile {{ path: {p:?}, data: ::std::include_bytes!({a:?}), _non_exhaustive_init_via_macros_only: () }}", k = krate, p = format!("{}/{}", dir_str, rel), a = abs);
+            match png_dimensions(&std::fs::read(&path)?) {
+                Some((w, h)) => {
+                    writeln!(out, "{}/// `{}` ({}x{})", indent, rel, w, h).unwrap();
+                    writeln!(out, "{}pub static {} : {}::io::StaticImage = {}::io::StaticImage {{ file: {}, width: {}, height: {}, _non_exhaustive_init_via_macros_only: () }};", indent, ident, krate, krate, file, w, h).unwrap();
+                },
+                None => {
+                    writeln!(out, "{}/// `{}`", indent, rel).unwrap();
+                    writeln!(out, "{}pub static {} : {}::io::StaticFile = {};", indent, ident, krate, file).unwrap();
+                },
+            }
+        }
+        for (ident, (path, rel)) in dirs {
+            writeln!(out, "{}/// `{}/`", indent, rel).unwrap();
+            writeln!(out, "{}pub mod {} {{", indent, ident).unwrap();
+            self.generate_dir(&path, &rel, depth + 1, out)?;
+            writeln!(out, "{}}}", indent).unwrap();
+        }
+        Ok(())
+    }
+}
+
+type Filter = Box<dyn Fn(&str) -> bool>;
+
+fn insert_unique(map: &mut BTreeMap<String, (PathBuf, String)>, ident: String, value: (PathBuf, String)) -> io::Result<()> {
+    if let Some((_, prev)) = map.get(&ident) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("AssetManifest: `{}` and `{}` would both be named `{}`", prev, value.1, ident)));
+    }
+    map.insert(ident, value);
+    Ok(())
+}
+
+/// `d3d-16x9.png` → `D3D_16X9_PNG`
+fn const_ident(name: &str) -> String {
+    let ident = identifier(name).to_ascii_uppercase();
+    if ident == "_" { "_UNNAMED".into() } else { ident }
+}
+
+/// `UI Sprites` → `ui_sprites`
+fn module_ident(name: &str) -> String {
+    const KEYWORDS : &[&str] = &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false", "fn", "for", "if", "impl", "in",
+        "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true", "type",
+        "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "gen", "macro", "override", "priv", "try", "typeof",
+        "unsized", "virtual", "yield", "_",
+    ];
+    let ident = identifier(name).to_ascii_lowercase();
+    if KEYWORDS.contains(&ident.as_str()) { format!("{}_", ident) } else { ident }
+}
+
+/// Replace runs of anything but ASCII letters and digits with `_`, and prefix a leading digit with `_`.
+fn identifier(name: &str) -> String {
+    let mut ident = String::new();
+    for ch in name.chars() {
+        if ch.is_ascii_alphanumeric() {
+            if ident.is_empty() && ch.is_ascii_digit() { ident.push('_'); }
+            ident.push(ch);
+        } else if !ident.ends_with('_') {
+            ident.push('_');
+        }
+    }
+    if ident.len() > 1 { ident = ident.trim_end_matches('_').into(); }
+    if ident.is_empty() { ident.push('_'); }
+    ident
+}
+
+
+
+#[test] fn generate() {
+    let code = AssetManifest::new(Path::new(env!("CARGO_MANIFEST_DIR")).join("examples")).generate().unwrap();
+    let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples").canonicalize().unwrap();
+    assert!(code.contains("pub static D3D_16X9_PNG : ::kakistocracy::io::StaticImage = "), "{}", code);
+    assert!(code.contains("width: 16, height: 9,"), "{}", code);
+    assert!(code.contains("pub static D3D_RS : ::kakistocracy::io::StaticFile = "), "{}", code);
+    assert!(code.contains(&format!("{:?}", examples.join("d3d.rs").to_string_lossy())), "{}", code);
+
+    let mut manifest = AssetManifest::new(Path::new(env!("CARGO_MANIFEST_DIR")).join("examples"));
+    manifest.set_crate_path("crate");
+    manifest.set_filter(|path| path.ends_with(".png"));
+    let code = manifest.generate().unwrap();
+    assert!(code.contains("pub static D3D_16X16_PNG : crate::io::StaticImage = crate::io::StaticImage"), "{}", code);
+    assert!(!code.contains("D3D_RS"), "{}", code);
+
+    assert_eq!(const_ident("d3d-16x9.png"), "D3D_16X9_PNG");
+    assert_eq!(const_ident("9 lives.txt"), "_9_LIVES_TXT");
+    assert_eq!(const_ident("--"), "_UNNAMED");
+    assert_eq!(module_ident("UI Sprites"), "ui_sprites");
+    assert_eq!(module_ident("type"), "type_");
+}
+
+#[test] fn collisions() {
+    let dir = std::env::temp_dir().join(format!("kakistocracy-asset-manifest-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("sub dir")).unwrap();
+    std::fs::write(dir.join("a-b.txt"), "").unwrap();
+    std::fs::write(dir.join("sub dir/x.txt"), "").unwrap();
+    std::fs::write(dir.join(".hidden"), "").unwrap();
+    let code = AssetManifest::new(&dir).generate().unwrap();
+    assert!(code.contains("pub static A_B_TXT "), "{}", code);
+    assert!(code.contains("pub mod sub_dir {"), "{}", code);
+    assert!(!code.contains("HIDDEN"), "{}", code);
+
+    std::fs::write(dir.join("a_b.txt"), "").unwrap();
+    let err = AssetManifest::new(&dir).generate().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(err.to_string().contains("A_B_TXT"), "{}", err);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
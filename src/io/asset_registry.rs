@@ -0,0 +1,220 @@
+use crate::image::Image;
+use crate::io::{AssetError, StaticFile};
+
+use std::any::{Any, TypeId};
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::sync::{Arc, RwLock};
+
+
+
+/// Decoders for asset types, chosen by sniffing magic bytes and file extensions.
+///
+/// Any number of [`AssetFormat`]s can be registered per asset type `T` (images, fonts, atlases, tilemaps, audio, data, ...)
+/// and [`load::<T>`](Self::load) picks the right one for a file:
+///
+/// 1.  The most recently registered format whose [`magic`](AssetFormat::magic) bytes prefix the file.
+/// 2.  Otherwise, the most recently registered format with a matching [`extension`](AssetFormat::extensions).
+/// 3.  Otherwise, the most recently registered fallback format (one with neither magic bytes nor extensions.)
+///
+/// Errors are always [`AssetLoadError`]s, naming the file and the format (if any) that failed.
+///
+/// [`global`](Self::global) comes with PNG → [`Image`], plus fallbacks for raw `Vec<u8>` data and UTF-8 `String`s,
+/// and is what [`TextureCache`](crate::texture::TextureCache)s and [`AssetLoader`](crate::io::AssetLoader)s decode with.
+///
+/// ```
+/// use kakistocracy::io::*;
+///
+/// struct Csv(Vec<Vec<String>>);
+///
+/// AssetRegistry::global().register(AssetFormat {
+///     name:       "CSV",
+///     extensions: &["csv"],
+///     magic:      &[],
+///     decode:     |bytes| Ok(Csv(std::str::from_utf8(bytes)?.lines().map(|l| l.split(',').map(String::from).collect()).collect())),
+/// });
+///
+/// let csv = AssetRegistry::global().load::<Csv>(AssetSource::new("scores.csv", b"a,1\nb,2")).unwrap();
+/// assert_eq!(csv.0[1], ["b", "2"]);
+/// ```
+pub struct AssetRegistry {
+    formats: RwLock<Vec<Registered>>,
+}
+
+/// How to recognize and decode one file format into an asset of type `T`.
+pub struct AssetFormat<T> {
+    /// A short human readable name for the format, e.g. `"PNG"`
+    pub name:       &'static str,
+    /// File extensions (without the leading `.`, case insensitive), e.g. `&["png"]`.  May include dots, e.g. `&["tar.gz"]`.
+    pub extensions: &'static [&'static str],
+    /// Byte sequences that files of this format always start with, e.g. `&[b"\x89PNG\r\n\x1A\n"]`.
+    pub magic:      &'static [&'static [u8]],
+    pub decode:     DecodeFn<T>,
+}
+
+/// Decodes the contents of a file.  Errors are wrapped in an [`AssetLoadError`] by [`AssetRegistry::load`].
+pub type DecodeFn<T> = fn(&[u8]) -> Result<T, Box<dyn Error + Send + Sync>>;
+
+/// A file to be decoded by an [`AssetRegistry`]: its path (for extensions and error messages) and contents.
+#[derive(Clone, Copy, Debug)]
+pub struct AssetSource<'a> {
+    pub path:   &'a str,
+    pub bytes:  &'a [u8],
+}
+
+/// An asset that couldn't be decoded by an [`AssetRegistry`].
+#[derive(Clone, Debug)]
+pub struct AssetLoadError {
+    path:       String,
+    type_name:  &'static str,
+    format:     Option<&'static str>,
+    error:      Option<AssetError>,
+}
+
+struct Registered {
+    type_id:    TypeId,
+    format:     Box<dyn Any + Send + Sync>,
+}
+
+impl AssetRegistry {
+    /// An empty registry.
+    pub fn new() -> Self { Self { formats: Default::default() } }
+
+    /// The registry used by [`TextureCache`](crate::texture::TextureCache)s and [`AssetLoader`](crate::io::AssetLoader)s, including the built-in formats.
+    pub fn global() -> &'static Arc<AssetRegistry> {
+        lazy_static::lazy_static! {
+            static ref GLOBAL : Arc<AssetRegistry> = {
+                let registry = AssetRegistry::new();
+                registry.register(AssetFormat::<Vec<u8>> { name: "raw", extensions: &[], magic: &[], decode: |bytes| Ok(bytes.to_vec()) });
+                registry.register(AssetFormat::<String> { name: "UTF-8", extensions: &[], magic: &[], decode: |bytes| Ok(String::from_utf8(bytes.to_vec())?) });
+                registry.register(AssetFormat::<Image> { name: "PNG", extensions: &["png"], magic: &[b"\x89PNG\r\n\x1A\n"], decode: |bytes| Ok(Image::from_png_bytes(bytes)?) });
+                Arc::new(registry)
+            };
+        }
+        &GLOBAL
+    }
+
+    /// Register `format`, taking priority over formats already registered for `T`.
+    pub fn register<T: 'static>(&self, format: AssetFormat<T>) {
+        self.formats.write().unwrap().push(Registered { type_id: TypeId::of::<T>(), format: Box::new(format) });
+    }
+
+    /// Every format registered for `T`, highest priority first.
+    pub fn formats<T: 'static>(&self) -> Vec<AssetFormat<T>> {
+        self.formats.read().unwrap().iter().rev()
+            .filter(|r| r.type_id == TypeId::of::<T>())
+            .filter_map(|r| r.format.downcast_ref::<AssetFormat<T>>().copied())
+            .collect()
+    }
+
+    /// The format [`load`](Self::load) would decode `source` with, if any.
+    pub fn sniff<'s, T: 'static>(&self, source: impl Into<AssetSource<'s>>) -> Option<AssetFormat<T>> {
+        let source = source.into();
+        let formats = self.formats::<T>();
+        let by_magic        = || formats.iter().find(|f| f.magic.iter().any(|m| source.bytes.starts_with(m)));
+        let by_extension    = || formats.iter().find(|f| f.extensions.iter().any(|ext| has_extension(source.path, ext)));
+        let fallback        = || formats.iter().find(|f| f.magic.is_empty() && f.extensions.is_empty());
+        by_magic().or_else(by_extension).or_else(fallback).copied()
+    }
+
+    /// Decode `source` as a `T`, with the format chosen by [`sniff`](Self::sniff).
+    pub fn load<'s, T: 'static>(&self, source: impl Into<AssetSource<'s>>) -> Result<T, AssetLoadError> {
+        let source = source.into();
+        let error = |format, error| AssetLoadError { path: source.path.into(), type_name: std::any::type_name::<T>(), format, error };
+        let format = self.sniff::<T>(source).ok_or_else(|| error(None, None))?;
+        (format.decode)(source.bytes).map_err(|err| error(Some(format.name), Some(AssetError::from(err))))
+    }
+}
+
+impl Default for AssetRegistry {
+    fn default() -> Self { Self::new() }
+}
+
+impl Debug for AssetRegistry {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "AssetRegistry({} formats)", self.formats.read().unwrap().len())
+    }
+}
+
+impl<T> Clone for AssetFormat<T> { fn clone(&self) -> Self { *self } }
+impl<T> Copy  for AssetFormat<T> {}
+
+impl<T> Debug for AssetFormat<T> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("AssetFormat").field("name", &self.name).field("extensions", &self.extensions).finish()
+    }
+}
+
+impl<'a> AssetSource<'a> {
+    pub fn new(path: &'a str, bytes: &'a [u8]) -> Self { Self { path, bytes } }
+}
+
+impl<'a> From<&'a StaticFile> for AssetSource<'a> { fn from(file: &'a StaticFile) -> Self { Self::new(file.path_str(), file.as_bytes()) } }
+
+impl AssetLoadError {
+    /// The path of the file that failed to load.
+    pub fn path(&self) -> &str { &self.path }
+
+    /// The name of the format that failed to decode the file, or `None` if no registered format recognized it.
+    pub fn format(&self) -> Option<&'static str> { self.format }
+
+    /// `true` if no format registered for the asset type recognized the file.
+    pub fn is_unsupported(&self) -> bool { self.format.is_none() }
+}
+
+impl Display for AssetLoadError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match (self.format, self.error.as_ref()) {
+            (Some(format), Some(err))   => write!(fmt, "{}: unable to decode {} as {}: {}", self.path, format, self.type_name, err),
+            _                           => write!(fmt, "{}: no format registered for {} recognizes this file", self.path, self.type_name),
+        }
+    }
+}
+
+impl Error for AssetLoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> { self.error.as_ref().map(|err| &**err as &(dyn Error + 'static)) }
+}
+
+fn has_extension(path: &str, ext: &str) -> bool {
+    let (path, ext) = (path.as_bytes(), ext.as_bytes());
+    path.len() > ext.len() && path[path.len() - ext.len() - 1] == b'.' && path[path.len() - ext.len() ..].eq_ignore_ascii_case(ext)
+}
+
+
+
+#[test] fn sniffing() {
+    let registry = AssetRegistry::new();
+    registry.register(AssetFormat::<&'static str> { name: "fallback", extensions: &[],              magic: &[],         decode: |_| Ok("fallback") });
+    registry.register(AssetFormat::<&'static str> { name: "ext",      extensions: &["txt", "TAR.GZ"], magic: &[],       decode: |_| Ok("ext") });
+    registry.register(AssetFormat::<&'static str> { name: "magic",    extensions: &[],              magic: &[b"MAGIC"], decode: |_| Ok("magic") });
+    registry.register(AssetFormat::<&'static str> { name: "broken",   extensions: &["broken"],      magic: &[],         decode: |_| Err("nope".into()) });
+
+    assert_eq!(registry.load::<&str>(AssetSource::new("a.txt", b"MAGIC...")).unwrap(), "magic", "magic bytes win over extensions");
+    assert_eq!(registry.load::<&str>(AssetSource::new("a.TXT", b"text")).unwrap(), "ext");
+    assert_eq!(registry.load::<&str>(AssetSource::new("a.tar.gz", b"")).unwrap(), "ext");
+    assert_eq!(registry.load::<&str>(AssetSource::new("txt", b"")).unwrap(), "fallback");
+    assert_eq!(registry.load::<&str>(AssetSource::new("a.bin", b"")).unwrap(), "fallback");
+    assert_eq!(registry.formats::<&str>().iter().map(|f| f.name).collect::<Vec<_>>(), ["broken", "magic", "ext", "fallback"]);
+
+    let err = registry.load::<&str>(AssetSource::new("a.broken", b"")).unwrap_err();
+    assert_eq!(err.format(), Some("broken"));
+    assert_eq!(err.source().unwrap().to_string(), "nope");
+    assert!(err.to_string().starts_with("a.broken: unable to decode broken as &str: nope"), "{}", err);
+
+    let err = registry.load::<u32>(AssetSource::new("a.txt", b"MAGIC")).unwrap_err();
+    assert!(err.is_unsupported());
+    assert!(err.source().is_none());
+}
+
+#[test] fn builtin() {
+    let png = crate::include_file!("../../examples/d3d-16x9.png");
+    let renamed = AssetSource::new("d3d-16x9.dat", png.as_bytes());
+    let registry = AssetRegistry::global();
+    assert_eq!(registry.load::<Image>(&png).unwrap().dimensions(), (16, 9));
+    assert_eq!(registry.load::<Image>(renamed).unwrap().dimensions(), (16, 9), "sniffed by magic bytes");
+    assert_eq!(registry.load::<Vec<u8>>(&png).unwrap(), png.as_bytes());
+    assert_eq!(registry.load::<String>(AssetSource::new("a.txt", b"text")).unwrap(), "text");
+    assert_eq!(registry.load::<String>(&png).unwrap_err().format(), Some("UTF-8"));
+    assert!(registry.load::<Image>(AssetSource::new("a.txt", b"text")).unwrap_err().is_unsupported());
+    assert_eq!(registry.load::<Image>(AssetSource::new("a.png", b"not a png")).unwrap_err().format(), Some("PNG"));
+}
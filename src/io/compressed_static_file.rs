@@ -0,0 +1,88 @@
+use crate::io::StaticFile;
+
+use std::fmt::{self, Debug, Formatter};
+use std::sync::OnceLock;
+
+
+
+/// The result of [`include_file_compressed!`](crate::include_file_compressed).  A [`StaticFile`] that's stored compressed, and decompressed on first use.
+///
+/// The decompressed bytes are kept for the rest of the program, and always live at the same address,
+/// so [`static_file`](Self::static_file) can be used anywhere a [`StaticFile`] can - including as a [`TextureSource`](crate::texture::TextureSource).
+pub struct CompressedStaticFile {
+    #[doc(hidden)] pub path: &'static str,
+    #[doc(hidden)] pub compressed: &'static [u8],
+    #[doc(hidden)] pub len: usize,
+    #[doc(hidden)] pub decompressed: OnceLock<StaticFile>,
+    #[doc(hidden)] pub _non_exhaustive_init_via_macros_only:   (),
+}
+
+impl CompressedStaticFile {
+    pub fn path_str(&self) -> &'static str { self.path }
+
+    /// The decompressed length, without decompressing.
+    pub fn len(&self) -> usize { self.len }
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// The raw deflate stream embedded in the executable.
+    pub fn compressed_bytes(&self) -> &'static [u8] { self.compressed }
+
+    /// `true` if [`static_file`](Self::static_file) or [`as_bytes`](Self::as_bytes) has already decompressed this file.
+    pub fn is_decompressed(&self) -> bool { self.decompressed.get().is_some() }
+
+    /// The decompressed file, decompressing if this is the first access.
+    pub fn static_file(&self) -> &StaticFile {
+        self.decompressed.get_or_init(|| {
+            let data = miniz_oxide::inflate::decompress_to_vec(self.compressed).unwrap_or_else(|err| panic!("{}: corrupt compressed data: {:?}", self.path, err));
+            assert_eq!(data.len(), self.len, "{}: unexpected decompressed length", self.path);
+            // One allocation per include_file_compressed! call site (which expands to a `static`), never freed, much like the rest of the executable
+            StaticFile { path: self.path, data: Box::leak(data.into_boxed_slice()), _non_exhaustive_init_via_macros_only: () }
+        })
+    }
+
+    /// The decompressed bytes, decompressing if this is the first access.
+    pub fn as_bytes(&self) -> &'static [u8] { self.static_file().as_bytes() }
+}
+
+impl Debug for CompressedStaticFile {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        fmt.debug_tuple("CompressedStaticFile").field(&self.path).finish()
+    }
+}
+
+
+
+/// Include a file, compressed at build time.  Results in a <code>&amp;'static [CompressedStaticFile](crate::io::CompressedStaticFile)</code>.
+///
+/// Accepts the same paths as [`include_file!`](crate::include_file).  The file is decompressed on first access, and kept in memory after that.
+///
+/// ### Example
+///
+/// ```
+/// use kakistocracy::*;
+///
+/// let file = include_file_compressed!(CARGO_MANIFEST_DIR / "Readme.md");
+/// assert!(file.compressed_bytes().len() < file.len());
+/// assert!(file.as_bytes().starts_with(b"# kakistocracy"));
+/// ```
+#[macro_export]
+macro_rules! include_file_compressed {
+    ( $($tt:tt)+ ) => { $crate::io::include_file_compressed_impl!($crate; $($tt)+) };
+}
+
+
+
+#[test] fn roundtrip() {
+    static DIRECT : &CompressedStaticFile = crate::include_file_compressed!("../../examples/d3d-16x9.png");
+    let uncompressed = crate::include_file!("../../examples/d3d-16x9.png");
+    assert!(!DIRECT.is_decompressed());
+    assert_eq!(DIRECT.len(), uncompressed.len());
+    assert_eq!(DIRECT.path_str(), "../../examples/d3d-16x9.png");
+    assert_eq!(DIRECT.as_bytes(), uncompressed.as_bytes());
+    assert!(DIRECT.is_decompressed());
+    assert_eq!(DIRECT.as_bytes().as_ptr(), DIRECT.static_file().as_bytes().as_ptr(), "decompressed once");
+
+    let source = crate::include_file_compressed!(CARGO_MANIFEST_DIR / "src/io/compressed_static_file.rs");
+    assert!(source.compressed_bytes().len() < source.len());
+    assert!(source.as_bytes().starts_with(b"use crate::io::StaticFile;"));
+}
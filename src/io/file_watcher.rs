@@ -0,0 +1,203 @@
+use crate::io::*;
+use crate::time::{Clock, RealClock};
+
+use instant::{Duration, Instant};
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+
+
+/// Polls watched files and directories (through a [`Vfs`]) for changes in modification time or size.
+///
+/// Cross platform, dependency free, and cheap enough for development builds - but polling, so not instant.
+/// Call [`poll`](Self::poll) regularly (e.g. once per frame): it only touches the filesystem once every [`interval`](Self::set_interval).
+///
+/// ```no_run
+/// use kakistocracy::io::*;
+///
+/// let mut watcher = FileWatcher::new();
+/// watcher.watch("levels");
+/// loop {
+///     for change in watcher.poll() {
+///         println!("{:?} {}", change.kind, change.path);
+///     }
+///     // ...
+/// #   break;
+/// }
+/// ```
+pub struct FileWatcher {
+    vfs:        Arc<Vfs>,
+    clock:      Arc<dyn Clock>,
+    interval:   Duration,
+    next_poll:  Option<Instant>,
+    watches:    BTreeMap<String, Snapshot>,
+}
+
+/// A change reported by [`FileWatcher::poll`].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileChange {
+    /// The path of the changed file.  For files within watched directories, the directory path as passed to [`FileWatcher::watch`], then `/`, then the path within the directory.
+    pub path:   String,
+    pub kind:   FileChangeKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FileChangeKind {
+    Created,
+    /// The modification time or size changed.
+    Modified,
+    Removed,
+}
+
+/// The modification time and size of a file, for cheaply detecting changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FileStamp {
+    pub modified:   Option<SystemTime>,
+    pub len:        u64,
+}
+
+/// Every file of a watch, by path.
+type Snapshot = BTreeMap<String, FileStamp>;
+
+impl FileWatcher {
+    /// Watch files in [`Vfs::global`], polling at most every 500ms.
+    pub fn new() -> Self { Self::with_vfs(Vfs::global().clone()) }
+
+    /// Watch files in `vfs`, polling at most every 500ms.
+    pub fn with_vfs(vfs: Arc<Vfs>) -> Self { Self { vfs, clock: Arc::new(RealClock), interval: Duration::from_millis(500), next_poll: None, watches: Default::default() } }
+
+    /// Measure [`interval`](Self::interval)s with `clock` instead of the real time.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Arc::new(clock);
+        self.next_poll = None;
+    }
+
+    pub fn interval(&self) -> Duration { self.interval }
+
+    /// Change how often [`poll`](Self::poll) actually checks for changes.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+        self.next_poll = None;
+    }
+
+    /// Start watching the file or directory (recursively) at `path`.  It doesn't need to exist yet.
+    pub fn watch(&mut self, path: impl AsRef<Path>) {
+        let path = path.as_ref().to_string_lossy().into_owned();
+        let snapshot = self.snapshot(&path);
+        self.watches.insert(path, snapshot);
+    }
+
+    /// Stop watching `path`.  Returns `false` if it wasn't being watched.
+    pub fn unwatch(&mut self, path: impl AsRef<Path>) -> bool {
+        self.watches.remove(&*path.as_ref().to_string_lossy()).is_some()
+    }
+
+    pub fn is_watching(&self, path: impl AsRef<Path>) -> bool {
+        self.watches.contains_key(&*path.as_ref().to_string_lossy())
+    }
+
+    /// If [`interval`](Self::interval) has elapsed since the last poll, check every watched path for changes.
+    pub fn poll(&mut self) -> Vec<FileChange> {
+        let now = self.clock.now();
+        if self.next_poll.is_some_and(|next| now < next) { return Vec::new() }
+        self.next_poll = Some(now + self.interval);
+        self.poll_now()
+    }
+
+    /// Check every watched path for changes immediately, regardless of [`interval`](Self::interval).
+    ///
+    /// Changes are sorted by path, and reported once even if several watches overlap.
+    pub fn poll_now(&mut self) -> Vec<FileChange> {
+        let mut changes = BTreeMap::new();
+        let paths = self.watches.keys().cloned().collect::<Vec<_>>();
+        for path in paths {
+            let after = self.snapshot(&path);
+            let before = self.watches.insert(path, after.clone()).unwrap_or_default();
+            for (file, stamp) in after.iter() {
+                match before.get(file) {
+                    None                        => { changes.insert(file.clone(), FileChangeKind::Created); },
+                    Some(prev) if prev != stamp => { changes.insert(file.clone(), FileChangeKind::Modified); },
+                    Some(_)                     => {},
+                }
+            }
+            for file in before.keys().filter(|f| !after.contains_key(*f)) {
+                changes.insert(file.clone(), FileChangeKind::Removed);
+            }
+        }
+        changes.into_iter().map(|(path, kind)| FileChange { path, kind }).collect()
+    }
+
+    fn snapshot(&self, path: &str) -> Snapshot {
+        let mut snapshot = Snapshot::new();
+        match self.vfs.metadata(path) {
+            Ok(meta) if meta.is_dir => self.snapshot_dir(path, &mut snapshot),
+            Ok(meta) => { snapshot.insert(path.into(), meta.into()); },
+            Err(_) => {},
+        }
+        snapshot
+    }
+
+    fn snapshot_dir(&self, dir: &str, snapshot: &mut Snapshot) {
+        for entry in self.vfs.list(dir).unwrap_or_default() {
+            let path = if dir.is_empty() { entry.name } else { format!("{}/{}", dir.trim_end_matches(['/', '\\']), entry.name) };
+            if entry.is_dir {
+                self.snapshot_dir(&path, snapshot);
+            } else if let Ok(meta) = self.vfs.metadata(&path) {
+                snapshot.insert(path, meta.into());
+            }
+        }
+    }
+}
+
+impl Default for FileWatcher {
+    fn default() -> Self { Self::new() }
+}
+
+impl From<VfsMetadata> for FileStamp {
+    fn from(meta: VfsMetadata) -> Self { Self { modified: meta.modified, len: meta.len } }
+}
+
+
+
+#[test] fn changes() {
+    let dir = std::env::temp_dir().join(format!("kakistocracy-file-watcher-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("a.txt"), "a").unwrap();
+
+    let vfs = Arc::new(Vfs::new());
+    vfs.mount("", 0, DiskDir::new(&dir));
+    let clock = Arc::new(crate::time::ManualClock::new());
+    let mut watcher = FileWatcher::with_vfs(vfs);
+    watcher.set_clock(clock.clone());
+    watcher.set_interval(Duration::from_secs(3600));
+    watcher.watch("");
+    watcher.watch("a.txt");
+    watcher.watch("later.txt");
+    assert!(watcher.poll().is_empty());
+    assert!(watcher.poll_now().is_empty());
+
+    std::fs::write(dir.join("a.txt"), "aa").unwrap(); // size changes even if mtime resolution is coarse
+    std::fs::write(dir.join("sub/b.txt"), "b").unwrap();
+    std::fs::write(dir.join("later.txt"), "later").unwrap();
+    assert!(watcher.poll().is_empty(), "interval hasn't elapsed");
+    let change = |path: &str, kind| FileChange { path: path.into(), kind };
+    assert_eq!(watcher.poll_now(), [
+        change("a.txt", FileChangeKind::Modified),
+        change("later.txt", FileChangeKind::Created),
+        change("sub/b.txt", FileChangeKind::Created),
+    ]);
+    assert!(watcher.poll_now().is_empty());
+
+    std::fs::remove_file(dir.join("sub/b.txt")).unwrap();
+    assert!(watcher.unwatch("a.txt"));
+    assert!(!watcher.unwatch("a.txt"));
+    assert!(watcher.poll().is_empty(), "interval hasn't elapsed");
+    clock.advance(Duration::from_secs(3600));
+    assert_eq!(watcher.poll(), [change("sub/b.txt", FileChangeKind::Removed)]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
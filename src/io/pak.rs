@@ -0,0 +1,385 @@
+//! The `.pak` archive format: many files bundled into one, with a path index.
+//!
+//! All integers are little endian.  Offsets are relative to the start of the archive.
+//!
+//! | Offset    | Size  | Header field                                                          |
+//! | --------- | ----- | --------------------------------------------------------------------- |
+//! | 0         | 8     | Magic: `b"KAKIPAK\0"`                                                 |
+//! | 8         | 4     | Version: `1`                                                          |
+//! | 12        | 4     | Alignment of entry data (a power of two)                              |
+//! | 16        | 8     | Offset of the index                                                   |
+//! | 24        | 4     | Number of entries in the index                                        |
+//! | 28        | 4     | Reserved (`0`)                                                        |
+//!
+//! Entry data follows the header, each entry starting at a multiple of the alignment, so uncompressed entries of a
+//! memory mapped (or [`include_bytes!`]ed and suitably aligned) archive can be used in place.
+//! Entries with identical contents share the same data.
+//!
+//! The index follows the data, with one record per entry, sorted by path:
+//!
+//! | Size      | Index record field                                                                |
+//! | --------- | --------------------------------------------------------------------------------- |
+//! | 2         | Length of the path in bytes                                                       |
+//! | *         | The path: UTF-8, [normalized](super::normalize_path), `/` separated               |
+//! | 1         | [`PakCompression`]: `0` = none, `1` = raw deflate                                 |
+//! | 8         | Offset of the data                                                                |
+//! | 8         | Size of the data as stored                                                        |
+//! | 8         | Size of the data when decompressed                                                |
+//! | 8         | [Content hash](super::StaticFile::content_hash) (64-bit FNV-1a) of the decompressed data |
+
+use crate::io::*;
+use super::static_file::content_hash;
+
+use std::borrow::Cow;
+use std::collections::*;
+use std::convert::*;
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, Write};
+use std::path::Path;
+
+
+
+const MAGIC     : [u8; 8] = *b"KAKIPAK\0";
+const VERSION   : u32 = 1;
+const HEADER    : usize = 32;
+
+/// How a [`PakEntry`] is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PakCompression {
+    None,
+    /// Raw deflate.  [`PakWriter`] stores entries that don't shrink when deflated as [`None`](Self::None) instead.
+    Deflate,
+}
+
+/// Builds a `.pak` archive.  See the [`pak`](self) format.
+///
+/// ```
+/// use kakistocracy::io::*;
+///
+/// let mut writer = PakWriter::new();
+/// writer.add("levels/1.txt", b"hello, world".to_vec(), PakCompression::Deflate).unwrap();
+/// let mut pak = Vec::new();
+/// writer.write(&mut pak).unwrap();
+///
+/// let archive = PakArchive::from_bytes(pak).unwrap();
+/// assert_eq!(&archive.read("levels/1.txt").unwrap()[..], b"hello, world");
+/// ```
+pub struct PakWriter {
+    alignment:  u32,
+    entries:    BTreeMap<String, (Vec<u8>, PakCompression)>,
+}
+
+/// A `.pak` archive, read into memory (or embedded in the executable.)  Can be [`Vfs::mount`]ed.
+pub struct PakArchive {
+    name:       String,
+    data:       PakData,
+    entries:    Vec<PakEntry>,
+}
+
+/// A single file within a [`PakArchive`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PakEntry {
+    pub path:           String,
+    pub compression:    PakCompression,
+    pub offset:         u64,
+    pub stored_len:     u64,
+    pub len:            u64,
+    pub content_hash:   u64,
+}
+
+enum PakData {
+    Static(&'static [u8]),
+    Owned(Box<[u8]>),
+}
+
+impl PakWriter {
+    /// Create an empty archive, with entries aligned to 16 bytes.
+    pub fn new() -> Self { Self { alignment: 16, entries: Default::default() } }
+
+    /// Align entry data to `alignment` bytes (a power of two) instead.  Use e.g. the page size when memory mapping.
+    pub fn set_alignment(&mut self, alignment: u32) {
+        assert!(alignment.is_power_of_two(), "PakWriter::set_alignment: {} is not a power of two", alignment);
+        self.alignment = alignment;
+    }
+
+    /// Add (or replace) a file at `path`.
+    pub fn add(&mut self, path: &str, data: impl Into<Vec<u8>>, compression: PakCompression) -> io::Result<()> {
+        let path = normalize_path(path)?;
+        if path.is_empty() || path.len() > usize::from(u16::MAX) { return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid pak entry path {:?}", path))) }
+        self.entries.insert(path, (data.into(), compression));
+        Ok(())
+    }
+
+    /// Add every file of `dir` (recursively), at `prefix` + their relative paths.
+    pub fn add_static_dir(&mut self, prefix: &str, dir: &StaticDir, compression: PakCompression) -> io::Result<()> {
+        for file in dir.iter() {
+            let rel = dir.relative_path(file).unwrap_or_else(|| file.path_str());
+            self.add(&format!("{}/{}", prefix, rel), file.as_bytes(), compression)?;
+        }
+        Ok(())
+    }
+
+    /// Add every file of the directory `disk` (recursively), at `prefix` + their relative paths.
+    pub fn add_disk_dir(&mut self, prefix: &str, disk: impl AsRef<Path>, compression: PakCompression) -> io::Result<()> {
+        let disk = disk.as_ref();
+        for entry in std::fs::read_dir(disk)? {
+            let entry = entry?;
+            let name = entry.file_name().into_string().map_err(|name| io::Error::new(io::ErrorKind::InvalidData, format!("{:?} is not valid UTF-8", name)))?;
+            let path = format!("{}/{}", prefix, name);
+            if entry.file_type()?.is_dir() {
+                self.add_disk_dir(&path, entry.path(), compression)?;
+            } else {
+                self.add(&path, std::fs::read(entry.path())?, compression)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize { self.entries.len() }
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    /// Write the archive to `out`.
+    pub fn write(&self, mut out: impl Write) -> io::Result<()> {
+        let align = |n: usize| (n + self.alignment as usize - 1) & !(self.alignment as usize - 1);
+
+        let mut data = Vec::new();
+        let mut index = Vec::new();
+        let mut shared = HashMap::<(u64, PakCompression, &[u8]), (u64, u64, PakCompression)>::new();
+        for (path, (contents, compression)) in self.entries.iter() {
+            let hash = content_hash(contents);
+            let (offset, stored_len, compression) = match shared.get(&(hash, *compression, &contents[..])) {
+                Some(&existing) => existing,
+                None => {
+                    let deflated = match compression {
+                        PakCompression::None    => None,
+                        PakCompression::Deflate => Some(miniz_oxide::deflate::compress_to_vec(contents, 10)).filter(|d| d.len() < contents.len()),
+                    };
+                    let (stored, stored_compression) = match deflated.as_ref() {
+                        Some(deflated)  => (&deflated[..], PakCompression::Deflate),
+                        None            => (&contents[..], PakCompression::None),
+                    };
+                    data.resize(align(HEADER + data.len()) - HEADER, 0);
+                    let result = ((HEADER + data.len()) as u64, stored.len() as u64, stored_compression);
+                    data.extend_from_slice(stored);
+                    shared.insert((hash, *compression, &contents[..]), result);
+                    result
+                },
+            };
+
+            index.extend_from_slice(&(path.len() as u16).to_le_bytes());
+            index.extend_from_slice(path.as_bytes());
+            index.push(match compression { PakCompression::None => 0, PakCompression::Deflate => 1 });
+            index.extend_from_slice(&offset.to_le_bytes());
+            index.extend_from_slice(&stored_len.to_le_bytes());
+            index.extend_from_slice(&(contents.len() as u64).to_le_bytes());
+            index.extend_from_slice(&hash.to_le_bytes());
+        }
+
+        let mut header = Vec::with_capacity(HEADER);
+        header.extend_from_slice(&MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&self.alignment.to_le_bytes());
+        header.extend_from_slice(&((HEADER + data.len()) as u64).to_le_bytes());
+        header.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        debug_assert_eq!(header.len(), HEADER);
+
+        out.write_all(&header)?;
+        out.write_all(&data)?;
+        out.write_all(&index)?;
+        out.flush()
+    }
+
+    /// Write the archive to a file at `path`.
+    pub fn write_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write(io::BufWriter::new(std::fs::File::create(path)?))
+    }
+}
+
+impl Default for PakWriter {
+    fn default() -> Self { Self::new() }
+}
+
+impl PakArchive {
+    /// Read an entire `.pak` file into memory.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        Self::parse(path.display().to_string(), PakData::Owned(std::fs::read(path)?.into_boxed_slice()))
+    }
+
+    /// Parse an archive from memory.
+    pub fn from_bytes(data: impl Into<Vec<u8>>) -> io::Result<Self> {
+        Self::parse("memory".into(), PakData::Owned(data.into().into_boxed_slice()))
+    }
+
+    /// Parse an archive embedded in the executable (e.g. with [`include_file!`](crate::include_file).)  Uncompressed entries are never copied.
+    pub fn from_static(file: &'static StaticFile) -> io::Result<Self> {
+        Self::parse(file.path_str().into(), PakData::Static(file.as_bytes()))
+    }
+
+    /// Every entry, sorted by path.
+    pub fn entries(&self) -> &[PakEntry] { &self.entries }
+
+    /// Find the entry at `path`.
+    pub fn entry(&self, path: &str) -> Option<&PakEntry> {
+        let path = normalize_path(path).ok()?;
+        self.entries.binary_search_by(|e| e.path.as_str().cmp(&path)).ok().map(|i| &self.entries[i])
+    }
+
+    /// Read (and decompress, if necessary) the file at `path`, verifying its content hash.
+    pub fn read(&self, path: &str) -> io::Result<Cow<'_, [u8]>> {
+        let entry = self.entry(path).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{:?} not found in {}", path, self.name)))?;
+        self.read_entry(entry)
+    }
+
+    /// Read (and decompress, if necessary) `entry`, verifying its content hash.
+    pub fn read_entry(&self, entry: &PakEntry) -> io::Result<Cow<'_, [u8]>> {
+        self.read_from(self.data(), entry)
+    }
+
+    /// `data` must be [`Self::data`], but possibly with a longer lifetime.
+    fn read_from<'d>(&self, data: &'d [u8], entry: &PakEntry) -> io::Result<Cow<'d, [u8]>> {
+        let stored = &data[entry.offset as usize .. (entry.offset + entry.stored_len) as usize]; // bounds checked by parse
+        let contents = match entry.compression {
+            PakCompression::None    => Cow::Borrowed(stored),
+            PakCompression::Deflate => Cow::Owned(miniz_oxide::inflate::decompress_to_vec(stored).map_err(|err| self.invalid(format!("{}: unable to decompress: {:?}", entry.path, err)))?),
+        };
+        if contents.len() as u64 != entry.len || content_hash(&contents) != entry.content_hash {
+            return Err(self.invalid(format!("{}: content hash mismatch", entry.path)));
+        }
+        Ok(contents)
+    }
+
+    fn data(&self) -> &[u8] {
+        match &self.data {
+            PakData::Static(data)   => data,
+            PakData::Owned(data)    => data,
+        }
+    }
+
+    fn invalid(&self, message: String) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", self.name, message)) }
+
+    fn parse(name: String, data: PakData) -> io::Result<Self> {
+        let mut archive = Self { name, data, entries: Vec::new() };
+        let data = archive.data();
+        let invalid = |message: &str| archive.invalid(message.into());
+
+        if data.len() < HEADER || data[..8] != MAGIC { return Err(invalid("not a pak archive")) }
+        let u32_at = |o: usize| u32::from_le_bytes(data[o..o+4].try_into().unwrap());
+        let u64_at = |o: usize| u64::from_le_bytes(data[o..o+8].try_into().unwrap());
+        if u32_at(8) != VERSION { return Err(invalid("unsupported pak version")) }
+        let index = usize::try_from(u64_at(16)).ok().filter(|&i| i <= data.len()).ok_or_else(|| invalid("index out of bounds"))?;
+        let count = u32_at(24);
+
+        let mut index = &data[index..];
+        let mut take = |n: usize| -> io::Result<&[u8]> {
+            if index.len() < n { return Err(invalid("truncated index")) }
+            let (taken, rest) = index.split_at(n);
+            index = rest;
+            Ok(taken)
+        };
+        let mut entries = Vec::with_capacity(count.min(1 << 16) as usize);
+        for _ in 0 .. count {
+            let path_len = u16::from_le_bytes(take(2)?.try_into().unwrap());
+            let path = std::str::from_utf8(take(path_len.into())?).map_err(|_| invalid("entry path is not UTF-8"))?.to_string();
+            let compression = match take(1)?[0] { 0 => PakCompression::None, 1 => PakCompression::Deflate, _ => return Err(invalid("unknown compression")) };
+            let mut next_u64 = || take(8).map(|b| u64::from_le_bytes(b.try_into().unwrap()));
+            let (offset, stored_len, len, content_hash) = (next_u64()?, next_u64()?, next_u64()?, next_u64()?);
+            if offset.checked_add(stored_len).is_none_or(|end| end > data.len() as u64) { return Err(invalid("entry data out of bounds")) }
+            entries.push(PakEntry { path, compression, offset, stored_len, len, content_hash });
+        }
+        if entries.windows(2).any(|w| w[0].path >= w[1].path) { return Err(invalid("index isn't sorted")) }
+
+        archive.entries = entries;
+        Ok(archive)
+    }
+}
+
+impl Debug for PakArchive {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "PakArchive({:?}, {} entries)", self.name, self.entries.len())
+    }
+}
+
+impl VfsSource for PakArchive {
+    fn debug_name(&self) -> String { format!("pak:{}", self.name) }
+
+    fn read(&self, path: &str) -> io::Result<VfsFile> {
+        let entry = self.entry(path).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{:?} not found in {}", path, self.name)))?;
+        let data = match &self.data {
+            PakData::Static(data)   => self.read_from(data, entry)?,
+            PakData::Owned(data)    => Cow::Owned(self.read_from(data, entry)?.into_owned()),
+        };
+        Ok(VfsFile { data, modified: None })
+    }
+
+    fn metadata(&self, path: &str) -> io::Result<VfsMetadata> {
+        if let Some(entry) = self.entry(path) { return Ok(VfsMetadata { is_dir: false, len: entry.len, modified: None }) }
+        let prefix = format!("{}/", path);
+        if path.is_empty() || self.entries.iter().any(|e| e.path.starts_with(&prefix)) { return Ok(VfsMetadata { is_dir: true, len: 0, modified: None }) }
+        Err(io::Error::new(io::ErrorKind::NotFound, format!("{:?} not found in {}", path, self.name)))
+    }
+
+    fn list(&self, path: &str) -> io::Result<Vec<VfsEntry>> {
+        let prefix = if path.is_empty() { String::new() } else { format!("{}/", path) };
+        let mut entries = BTreeSet::new();
+        for entry in self.entries.iter() {
+            let rest = match entry.path.strip_prefix(&prefix) { Some(rest) => rest, None => continue };
+            match rest.split_once('/') {
+                Some((dir, _)) => entries.insert(VfsEntry { name: dir.into(), is_dir: true }),
+                None => entries.insert(VfsEntry { name: rest.into(), is_dir: false }),
+            };
+        }
+        if entries.is_empty() && !path.is_empty() { return Err(io::Error::new(io::ErrorKind::NotFound, format!("{:?} not found in {}", path, self.name))) }
+        Ok(entries.into_iter().collect())
+    }
+}
+
+
+
+#[test] fn roundtrip() {
+    static EXAMPLES : StaticDir = crate::include_dir!(CARGO_MANIFEST_DIR / "examples");
+    let readme = crate::include_file!(CARGO_MANIFEST_DIR / "Readme.md");
+
+    let mut writer = PakWriter::new();
+    writer.set_alignment(64);
+    writer.add_static_dir("examples", &EXAMPLES, PakCompression::Deflate).unwrap();
+    writer.add("docs\\readme.md", readme.as_bytes(), PakCompression::Deflate).unwrap();
+    writer.add("docs/copy.md", readme.as_bytes(), PakCompression::Deflate).unwrap();
+    writer.add("raw.bin", vec![1, 2, 3], PakCompression::None).unwrap();
+    assert_eq!(writer.len(), EXAMPLES.len() + 3);
+    let mut bytes = Vec::new();
+    writer.write(&mut bytes).unwrap();
+
+    let pak = PakArchive::from_bytes(bytes.clone()).unwrap();
+    let paths = pak.entries().iter().map(|e| e.path.as_str()).collect::<Vec<_>>();
+    assert_eq!(paths[..3], ["docs/copy.md", "docs/readme.md", "examples/d3d-16x16.png"]);
+
+    let readme_entry = pak.entry("docs/readme.md").unwrap();
+    assert_eq!(readme_entry.compression, PakCompression::Deflate);
+    assert_eq!(readme_entry.content_hash, readme.content_hash());
+    assert_eq!(readme_entry.offset % 64, 0);
+    assert_eq!(pak.entry("docs/copy.md").unwrap().offset, readme_entry.offset, "identical contents are shared");
+    assert_eq!(pak.entry("raw.bin").unwrap().compression, PakCompression::None);
+    assert_eq!(&pak.read("docs/readme.md").unwrap()[..], readme.as_bytes());
+    assert_eq!(&pak.read("./raw.bin").unwrap()[..], [1, 2, 3]);
+    for file in EXAMPLES.iter() {
+        assert_eq!(&pak.read(&format!("examples/{}", EXAMPLES.relative_path(file).unwrap())).unwrap()[..], file.as_bytes());
+    }
+    assert_eq!(pak.read("nope").unwrap_err().kind(), io::ErrorKind::NotFound);
+
+    // corruption is detected
+    let mut corrupt = bytes.clone();
+    corrupt[pak.entry("raw.bin").unwrap().offset as usize] ^= 0xFF;
+    assert_eq!(PakArchive::from_bytes(corrupt).unwrap().read("raw.bin").unwrap_err().kind(), io::ErrorKind::InvalidData);
+    assert!(PakArchive::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    assert!(PakArchive::from_bytes(&b"not a pak"[..]).is_err());
+
+    // vfs
+    let vfs = Vfs::new();
+    vfs.mount("assets", 0, pak);
+    assert_eq!(&vfs.read("assets/docs/copy.md").unwrap().data[..], readme.as_bytes());
+    assert!(vfs.metadata("assets/examples").unwrap().is_dir);
+    let root = vfs.list("assets").unwrap().into_iter().map(|e| (e.name, e.is_dir)).collect::<Vec<_>>();
+    assert_eq!(root, [("docs".into(), true), ("examples".into(), true), ("raw.bin".into(), false)]);
+}
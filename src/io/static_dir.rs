@@ -0,0 +1,177 @@
+use crate::io::StaticFile;
+
+use std::fmt::{self, Debug, Formatter};
+
+
+
+/// The result of [`include_dir!`](crate::include_dir).  A directory of [`StaticFile`]s, and nested `StaticDir`s.
+///
+/// Files and subdirectories are sorted by name.
+/// Empty subdirectories (including those emptied by glob filters) are omitted.
+pub struct StaticDir {
+    #[doc(hidden)] pub path: &'static str,
+    #[doc(hidden)] pub name: &'static str,
+    #[doc(hidden)] pub files: &'static [StaticFile],
+    #[doc(hidden)] pub dirs: &'static [StaticDir],
+    #[doc(hidden)] pub _non_exhaustive_init_via_macros_only:   (),
+}
+
+impl StaticDir {
+    /// The path of this directory, as passed to [`include_dir!`](crate::include_dir) (plus any subdirectories.)
+    pub fn path_str(&self) -> &'static str { self.path }
+
+    /// The last component of [`path_str`](Self::path_str).
+    pub fn name(&self) -> &'static str { self.name }
+
+    /// Files directly within this directory.
+    pub fn files(&self) -> &'static [StaticFile] { self.files }
+
+    /// Subdirectories directly within this directory.
+    pub fn dirs(&self) -> &'static [StaticDir] { self.dirs }
+
+    /// Find a file by `path` relative to this directory (e.g. `"sprites/player.png"`.)  `\` is treated like `/`.
+    pub fn get(&self, path: &str) -> Option<&'static StaticFile> {
+        let path = normalize(path);
+        let (files, name) = match path.rsplit_once('/') {
+            Some((dir, name))   => (self.get_dir(dir)?.files, name),
+            None                => (self.files, path.as_str()),
+        };
+        files.iter().find(|f| f.path.rsplit('/').next() == Some(name))
+    }
+
+    /// Find a subdirectory by `path` relative to this directory (e.g. `"sprites/ui"`.)  `\` is treated like `/`.
+    pub fn get_dir(&self, path: &str) -> Option<&'static StaticDir> {
+        let path = normalize(path);
+        let mut dirs = self.dirs;
+        let mut dir = None;
+        for name in path.split('/') {
+            let d = dirs.iter().find(|d| d.name == name)?;
+            dirs = d.dirs;
+            dir = Some(d);
+        }
+        dir
+    }
+
+    /// The path of `file` relative to this directory, if it's within this directory.
+    pub fn relative_path(&self, file: &StaticFile) -> Option<&'static str> {
+        file.path.strip_prefix(self.path)?.strip_prefix('/')
+    }
+
+    /// Every file within this directory, recursively: each directory's files, then its subdirectories'.
+    pub fn iter(&self) -> StaticDirIter {
+        StaticDirIter { files: self.files.iter(), dirs: vec![self.dirs.iter()] }
+    }
+
+    /// The number of files within this directory, recursively.
+    pub fn len(&self) -> usize { self.files.len() + self.dirs.iter().map(|d| d.len()).sum::<usize>() }
+
+    pub fn is_empty(&self) -> bool { self.files.is_empty() && self.dirs.is_empty() }
+}
+
+impl Debug for StaticDir {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        fmt.debug_tuple("StaticDir").field(&self.path).finish()
+    }
+}
+
+impl IntoIterator for &'_ StaticDir {
+    type Item = &'static StaticFile;
+    type IntoIter = StaticDirIter;
+    fn into_iter(self) -> Self::IntoIter { self.iter() }
+}
+
+/// Recursively iterates the [`StaticFile`]s of a [`StaticDir`].
+#[derive(Clone, Debug)]
+pub struct StaticDirIter {
+    files:  std::slice::Iter<'static, StaticFile>,
+    dirs:   Vec<std::slice::Iter<'static, StaticDir>>,
+}
+
+impl Iterator for StaticDirIter {
+    type Item = &'static StaticFile;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(file) = self.files.next() { return Some(file) }
+            match self.dirs.last_mut()?.next() {
+                Some(dir) => {
+                    self.files = dir.files.iter();
+                    self.dirs.push(dir.dirs.iter());
+                },
+                None => { self.dirs.pop(); },
+            }
+        }
+    }
+}
+
+fn normalize(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    path.trim_start_matches("./").trim_matches('/').to_string()
+}
+
+
+
+/// Include a directory of files, recursively.  Results in a [`StaticDir`](crate::io::StaticDir).
+///
+/// Like [`include_file!`](crate::include_file), paths are relative to the current source file, or to the crate root with the `CARGO_MANIFEST_DIR /` prefix.
+/// Optional glob patterns filter which files are included:
+///
+/// | Pattern               | Includes                                                  |
+/// | --------------------- | --------------------------------------------------------- |
+/// | *(none)*              | Every file                                                |
+/// | `"*.png"`             | PNGs in any subdirectory (patterns without `/` match file names) |
+/// | `"sprites/*.png"`     | PNGs directly within `sprites`                            |
+/// | `"sprites/**/*.png"`  | PNGs anywhere within `sprites`                            |
+/// | `"!unused/**"`        | Excludes everything within `unused`                       |
+///
+/// Note that adding files to the directory won't automatically trigger a rebuild, although modifying included files will.
+///
+/// ### Example
+///
+/// ```
+/// use kakistocracy::*;
+///
+/// static EXAMPLES : io::StaticDir = include_dir!(CARGO_MANIFEST_DIR / "examples", "*.png");
+///
+/// assert_eq!(EXAMPLES.len(), 2);
+/// assert_eq!(EXAMPLES.get("d3d-16x9.png").unwrap().path_str(), "examples/d3d-16x9.png");
+/// for file in EXAMPLES.iter() {
+///     assert!(file.path_str().ends_with(".png"));
+/// }
+/// ```
+#[macro_export]
+macro_rules! include_dir {
+    ( $($tt:tt)+ ) => { $crate::io::include_dir_impl!($crate; $($tt)+) };
+}
+
+
+
+#[test] fn nested() {
+    static SRC : StaticDir = crate::include_dir!(CARGO_MANIFEST_DIR / "src", "*.rs", "!windows/**");
+
+    assert_eq!(SRC.path_str(), "src");
+    assert_eq!(SRC.get("lib.rs").unwrap().path_str(), "src/lib.rs");
+    assert_eq!(SRC.get("./texture\\cache.rs").unwrap().path_str(), "src/texture/cache.rs");
+    assert!(SRC.get("texture").is_none());
+    assert!(SRC.get("windows/_windows.rs").is_none());
+    assert!(SRC.get_dir("windows").is_none());
+
+    let bcn = SRC.get_dir("image/bcn").unwrap();
+    assert_eq!(bcn.name(), "bcn");
+    assert_eq!(bcn.path_str(), "src/image/bcn");
+    assert!(bcn.get("bc7.rs").unwrap().as_bytes().starts_with(b"//!"));
+    assert_eq!(SRC.relative_path(bcn.get("bc7.rs").unwrap()), Some("image/bcn/bc7.rs"));
+    assert_eq!(bcn.relative_path(SRC.get("lib.rs").unwrap()), None);
+
+    let paths = SRC.iter().map(|f| f.path_str()).collect::<Vec<_>>();
+    assert_eq!(paths.len(), SRC.len());
+    assert_eq!(paths[0], "src/lib.rs");
+    assert!(paths.contains(&"src/io/static_dir.rs"));
+    assert!(paths.iter().all(|p| p.ends_with(".rs") && !p.starts_with("src/windows")));
+    assert!(paths.iter().position(|p| *p == "src/image/_image.rs") < paths.iter().position(|p| *p == "src/image/bcn/_bcn.rs"), "files before subdirectories");
+}
+
+#[test] fn relative() {
+    let examples = crate::include_dir!("../../examples", "*.png");
+    assert_eq!(examples.len(), 2);
+    assert_eq!(examples.get("d3d-16x9.png").unwrap().as_bytes(), crate::include_file!("../../examples/d3d-16x9.png").as_bytes());
+}
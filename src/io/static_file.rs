@@ -1,8 +1,23 @@
+use crate::utility::StaticBytesRef;
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt::{self, Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
 
 
 
-/// The result of [`include_file!`].
+/// The result of [`include_file!`](crate::include_file).
+///
+/// ### Identity
+///
+/// `StaticFile`s are compared by *contents*: the same bytes included from two different paths (or two different crates)
+/// are equal, hash identically, and share a [`StaticFileKey`] - so caches keyed on them decode and upload the data only once.
+/// [`path_str`](Self::path_str) is purely informational.
+///
+/// `==` compares addresses, then bytes.  `Ord` and `Hash` need the [`content_hash`](Self::content_hash), which takes a global lock each call -
+/// when looking up or comparing the same file repeatedly, compute its [`key`](Self::key) once and reuse that instead.
 pub struct StaticFile {
     #[doc(hidden)] pub path: &'static str,
     #[doc(hidden)] pub data: &'static [u8],
@@ -13,6 +28,24 @@ impl StaticFile {
     pub fn path_str(&self) -> &'static str { self.path }
     pub fn as_bytes(&self) -> &'static [u8] { self.data }
     pub fn len(&self) -> usize { self.data.len() }
+    pub fn is_empty(&self) -> bool { self.data.is_empty() }
+
+    /// A stable 64-bit hash of the contents (FNV-1a), identical across builds, platforms, and include sites.
+    ///
+    /// Computed on first use, and cached for the rest of the program (behind a global lock, taken on every call.)
+    pub fn content_hash(&self) -> u64 {
+        lazy_static::lazy_static! { static ref HASHES : Mutex<HashMap<StaticBytesRef, u64>> = Default::default(); }
+        let key = StaticBytesRef(self.data);
+        if let Some(hash) = HASHES.lock().unwrap().get(&key).copied() { return hash }
+        let hash = content_hash(self.data); // outside the lock
+        HASHES.lock().unwrap().insert(key, hash);
+        hash
+    }
+
+    /// A key identifying the contents of this file.  See [`StaticFileKey`].
+    ///
+    /// Creating one looks up the [`content_hash`](Self::content_hash), but comparing or hashing it afterwards is lock free.
+    pub fn key(&self) -> StaticFileKey { StaticFileKey { hash: self.content_hash(), bytes: StaticBytesRef(self.data) } }
 }
 
 impl Debug for StaticFile {
@@ -21,7 +54,46 @@ impl Debug for StaticFile {
     }
 }
 
-// TODO: comparison traits?
+impl PartialEq  for StaticFile { fn eq(&self, other: &Self) -> bool { StaticBytesRef(self.data) == StaticBytesRef(other.data) || self.data == other.data } }
+impl Eq         for StaticFile {}
+impl PartialOrd for StaticFile { fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) } }
+impl Ord        for StaticFile { fn cmp(&self, other: &Self) -> Ordering { self.key().cmp(&other.key()) } }
+impl Hash       for StaticFile { fn hash<H: Hasher>(&self, state: &mut H) { self.content_hash().hash(state) } }
+
+
+
+/// Identifies the contents of a [`StaticFile`], for use as a cache key.
+///
+/// Equal contents are equal keys, regardless of which [`include_file!`](crate::include_file) they came from.
+/// Comparisons check the [`content_hash`](StaticFile::content_hash) first, then the address, and only compare bytes if the hashes match but the addresses differ.
+/// Ordering is by hash, then contents: consistent with `Eq`, but otherwise meaningless.
+#[derive(Clone, Copy)]
+pub struct StaticFileKey {
+    hash:   u64,
+    bytes:  StaticBytesRef,
+}
+
+impl StaticFileKey {
+    pub fn content_hash(&self) -> u64 { self.hash }
+    pub fn as_bytes(&self) -> &'static [u8] { self.bytes.0 }
+}
+
+impl Debug for StaticFileKey {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "StaticFileKey({:016x}, {} bytes)", self.hash, self.bytes.len())
+    }
+}
+
+impl PartialEq  for StaticFileKey { fn eq(&self, other: &Self) -> bool { self.hash == other.hash && (self.bytes == other.bytes || *self.bytes == *other.bytes) } }
+impl Eq         for StaticFileKey {}
+impl PartialOrd for StaticFileKey { fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) } }
+impl Ord        for StaticFileKey { fn cmp(&self, other: &Self) -> Ordering { self.hash.cmp(&other.hash).then_with(|| (*self.bytes).cmp(&*other.bytes)) } }
+impl Hash       for StaticFileKey { fn hash<H: Hasher>(&self, state: &mut H) { self.hash.hash(state) } }
+
+/// 64-bit FNV-1a
+pub(crate) fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| (hash ^ u64::from(b)).wrapping_mul(0x100000001b3))
+}
 
 
 
@@ -43,3 +115,29 @@ macro_rules! include_file {
         }
     };
 }
+
+
+
+#[test] fn identity() {
+    use std::collections::HashSet;
+
+    let a = crate::include_file!("../../examples/d3d-16x9.png");
+    let b = crate::include_file!(CARGO_MANIFEST_DIR / "examples/d3d-16x9.png");
+    let c = crate::include_file!("../../examples/d3d-16x16.png");
+    let d = StaticFile { path: "copy.png", data: a.data.to_vec().leak(), _non_exhaustive_init_via_macros_only: () };
+
+    assert_eq!(a, b);
+    assert_eq!(a, d);
+    assert_ne!(a, c);
+    assert_eq!(a.content_hash(), d.content_hash());
+    assert_eq!(a.key(), d.key());
+    assert_ne!(a.key(), c.key());
+    assert_eq!(a.cmp(&d), std::cmp::Ordering::Equal);
+    assert_eq!(a.cmp(&c), a.content_hash().cmp(&c.content_hash()));
+
+    let set = [&a, &b, &c, &d].iter().map(|f| f.key()).collect::<HashSet<_>>();
+    assert_eq!(set.len(), 2);
+
+    assert_eq!(content_hash(b""), 0xcbf29ce484222325);
+    assert_eq!(content_hash(b"a"), 0xaf63dc4c8601ec8c); // stable across builds and platforms
+}
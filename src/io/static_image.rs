@@ -0,0 +1,52 @@
+use crate::io::StaticFile;
+
+use std::convert::TryInto;
+use std::fmt::{self, Debug, Formatter};
+use std::ops::Deref;
+
+
+
+/// An embedded PNG [`StaticFile`], with dimensions read at build time.  Generated by [`AssetManifest`](crate::io::AssetManifest).
+///
+/// Derefs to the [`StaticFile`], and can be used anywhere one can - including as a [`TextureSource`](crate::texture::TextureSource).
+pub struct StaticImage {
+    #[doc(hidden)] pub file: StaticFile,
+    #[doc(hidden)] pub width: u32,
+    #[doc(hidden)] pub height: u32,
+    #[doc(hidden)] pub _non_exhaustive_init_via_macros_only:   (),
+}
+
+impl StaticImage {
+    pub fn file(&self) -> &StaticFile { &self.file }
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+    pub fn dimensions(&self) -> (u32, u32) { (self.width, self.height) }
+}
+
+impl Deref for StaticImage {
+    type Target = StaticFile;
+    fn deref(&self) -> &StaticFile { &self.file }
+}
+
+impl Debug for StaticImage {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "StaticImage({:?}, {}x{})", self.file.path, self.width, self.height)
+    }
+}
+
+/// Read the width and height from the `IHDR` chunk of a PNG, without decoding it.
+pub(crate) fn png_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    const SIGNATURE : &[u8] = b"\x89PNG\r\n\x1A\n";
+    if !bytes.starts_with(SIGNATURE) || bytes.get(12..16) != Some(b"IHDR") { return None }
+    let be = |i: usize| Some(u32::from_be_bytes(bytes.get(i..i+4)?.try_into().ok()?));
+    Some((be(16)?, be(20)?))
+}
+
+
+
+#[test] fn dimensions() {
+    assert_eq!(png_dimensions(include_bytes!("../../examples/d3d-16x9.png")), Some((16, 9)));
+    assert_eq!(png_dimensions(include_bytes!("../../examples/d3d-16x16.png")), Some((16, 16)));
+    assert_eq!(png_dimensions(b"\x89PNG\r\n\x1A\n"), None);
+    assert_eq!(png_dimensions(b"not a png"), None);
+}
@@ -0,0 +1,373 @@
+use crate::io::{StaticDir, StaticFile};
+
+use std::borrow::Cow;
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+
+
+/// A virtual filesystem, stacking [`VfsSource`]s (embedded [`StaticDir`]s, [`DiskDir`]s, archives, ...) mounted at virtual paths.
+///
+/// ### Paths
+///
+/// Virtual paths are relative, `/`-separated, and [normalized](normalize_path): `\` is treated as `/`, and `.` / `..` are resolved,
+/// so `"sprites\\ui/../player.png"` and `"sprites/player.png"` are the same file.
+/// Native paths that aren't valid virtual paths - absolute (e.g. `C:\foo.png` or `/tmp/foo.png`), escaping the root (e.g. `../foo.png`),
+/// or not UTF-8 - bypass the mounts entirely, and are read straight from disk (relative to the current directory, like [`std::fs`].)
+///
+/// ### Priority
+///
+/// When several mounts contain the same path, the mount with the highest priority wins (ties go to the most recently mounted.)
+/// For example, to let modders override embedded assets:
+///
+/// ```
+/// use kakistocracy::io::*;
+/// # use kakistocracy::include_dir;
+///
+/// static ASSETS : StaticDir = include_dir!(CARGO_MANIFEST_DIR / "examples", "*.png");
+///
+/// let vfs = Vfs::new();
+/// vfs.mount("", 0, &ASSETS);
+/// vfs.mount("", 10, DiskDir::new("mods"));
+///
+/// let file = vfs.read("d3d-16x9.png").unwrap(); // mods/d3d-16x9.png if it exists, otherwise embedded
+/// assert!(file.data.starts_with(b"\x89PNG"));
+/// assert!(vfs.list("").unwrap().iter().any(|e| e.name == "d3d-16x16.png"));
+/// ```
+pub struct Vfs {
+    mounts: RwLock<Vec<Mount>>,
+}
+
+/// Identifies a [`Vfs::mount`], for [`Vfs::unmount`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MountId(u64);
+
+/// Something that can be [`Vfs::mount`]ed.  All paths passed to a source are normalized, and relative to its mount point.
+pub trait VfsSource : Send + Sync {
+    /// Describe the source for diagnostics (e.g. `"disk:mods"`).
+    fn debug_name(&self) -> String;
+
+    /// Read the file at `path`.  Should return [`io::ErrorKind::NotFound`] if it doesn't exist, so lower priority mounts are checked.
+    fn read(&self, path: &str) -> io::Result<VfsFile>;
+
+    /// Get the metadata of the file or directory at `path` (`""` is the root of the source.)
+    fn metadata(&self, path: &str) -> io::Result<VfsMetadata>;
+
+    /// List the immediate children of the directory at `path` (`""` is the root of the source.)
+    fn list(&self, path: &str) -> io::Result<Vec<VfsEntry>>;
+}
+
+/// The result of [`Vfs::read`].
+#[derive(Clone, Debug)]
+pub struct VfsFile {
+    /// The contents of the file.  Borrowed for embedded files, owned otherwise.
+    pub data:       Cow<'static, [u8]>,
+    /// The last modification time, if known.  Embedded files don't have one.
+    pub modified:   Option<SystemTime>,
+}
+
+/// The result of [`Vfs::metadata`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VfsMetadata {
+    pub is_dir:     bool,
+    /// The size of the file in bytes (`0` for directories.)
+    pub len:        u64,
+    pub modified:   Option<SystemTime>,
+}
+
+/// A single entry of [`Vfs::list`].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VfsEntry {
+    /// The name of the file or directory, without any parent directories.
+    pub name:   String,
+    pub is_dir: bool,
+}
+
+/// A directory on disk, as a [`VfsSource`].
+#[derive(Clone, Debug)]
+pub struct DiskDir {
+    root: PathBuf,
+}
+
+struct Mount {
+    id:         MountId,
+    at:         String,
+    priority:   i32,
+    source:     Arc<dyn VfsSource>,
+}
+
+impl Vfs {
+    /// Create an empty VFS, without any mounts.
+    pub fn new() -> Self { Self { mounts: Default::default() } }
+
+    /// The VFS used by default, e.g. by [`TextureCache`](crate::texture::TextureCache)s.
+    ///
+    /// Starts with the current directory mounted at `""` with priority `0`, so relative paths behave like [`std::fs`] until you mount something else.
+    pub fn global() -> &'static Arc<Vfs> {
+        lazy_static::lazy_static! {
+            static ref GLOBAL : Arc<Vfs> = {
+                let vfs = Vfs::new();
+                vfs.mount("", 0, DiskDir::new("."));
+                Arc::new(vfs)
+            };
+        }
+        &GLOBAL
+    }
+
+    /// Mount `source` at the virtual directory `at` (`""` for the root), with `priority`.  Higher priorities take precedence.
+    ///
+    /// ### Panics
+    /// If `at` is invalid (e.g. escapes the root with `..`.)
+    pub fn mount(&self, at: &str, priority: i32, source: impl VfsSource + 'static) -> MountId {
+        static NEXT_ID : AtomicU64 = AtomicU64::new(1);
+        let at = normalize_path(at).unwrap_or_else(|err| panic!("Vfs::mount: invalid mount point {:?}: {}", at, err));
+        let id = MountId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+        let mut mounts = self.mounts.write().unwrap();
+        mounts.push(Mount { id, at, priority, source: Arc::new(source) });
+        mounts.sort_by_key(|m| std::cmp::Reverse((m.priority, m.id))); // highest priority, then most recent, first
+        id
+    }
+
+    /// Remove a mount.  Returns `false` if it was already unmounted.
+    pub fn unmount(&self, id: MountId) -> bool {
+        let mut mounts = self.mounts.write().unwrap();
+        let before = mounts.len();
+        mounts.retain(|m| m.id != id);
+        mounts.len() != before
+    }
+
+    /// Read the file at `path` from the highest priority mount containing it.
+    pub fn read(&self, path: impl AsRef<Path>) -> io::Result<VfsFile> {
+        let path = path.as_ref();
+        if is_native(path) { return DiskDir::read_path(path) }
+        self.first(path, |source, rel| source.read(rel))
+    }
+
+    /// Get the metadata of the file or directory at `path`, from the highest priority mount containing it.
+    pub fn metadata(&self, path: impl AsRef<Path>) -> io::Result<VfsMetadata> {
+        let path = path.as_ref();
+        if is_native(path) { return DiskDir::metadata_path(path) }
+        let normalized = normalize_path(to_str(path)?)?;
+        match self.first(path, |source, rel| source.metadata(rel)) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound && self.is_mount_parent(&normalized) => Ok(VfsMetadata { is_dir: true, len: 0, modified: None }),
+            result => result,
+        }
+    }
+
+    /// `true` if `path` is a file or directory.
+    pub fn exists(&self, path: impl AsRef<Path>) -> bool { self.metadata(path).is_ok() }
+
+    /// List the immediate children of the virtual directory `path`, merged from every mount, sorted by name.
+    ///
+    /// If a name is a directory in any mount, it's listed as a directory.
+    pub fn list(&self, path: impl AsRef<Path>) -> io::Result<Vec<VfsEntry>> {
+        let path = path.as_ref();
+        if is_native(path) {
+            let mut entries = DiskDir::new(path).list("")?;
+            entries.sort();
+            return Ok(entries);
+        }
+        let path = normalize_path(to_str(path)?)?;
+        let mut found = self.is_mount_parent(&path);
+        let mut entries = Vec::<VfsEntry>::new();
+        for (source, rel) in self.candidates(&path) {
+            match source.list(&rel) {
+                Ok(list) => { found = true; entries.extend(list); },
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+                Err(err) => return Err(err),
+            }
+        }
+        // mount points themselves are directories
+        for mount in self.mounts.read().unwrap().iter() {
+            if let Some(name) = child_of(&path, &mount.at) { entries.push(VfsEntry { name: name.into(), is_dir: true }); }
+        }
+        if !found { return Err(not_found(&path)) }
+
+        entries.sort_by(|a, b| a.name.cmp(&b.name).then(b.is_dir.cmp(&a.is_dir))); // directories first, so dedup keeps them
+        entries.dedup_by(|b, a| a.name == b.name);
+        Ok(entries)
+    }
+
+    /// Call `f` for each mount that could contain `path`, in priority order, until one doesn't return [`io::ErrorKind::NotFound`].
+    fn first<R>(&self, path: &Path, f: impl Fn(&dyn VfsSource, &str) -> io::Result<R>) -> io::Result<R> {
+        let path = normalize_path(to_str(path)?)?;
+        for (source, rel) in self.candidates(&path) {
+            match f(&*source, &rel) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                result => return result,
+            }
+        }
+        Err(not_found(&path))
+    }
+
+    /// Every mount that could contain the normalized `path`, in priority order, and `path` relative to that mount.
+    fn candidates(&self, path: &str) -> Vec<(Arc<dyn VfsSource>, String)> {
+        self.mounts.read().unwrap().iter().filter_map(|m| {
+            let rel = if m.at.is_empty() { path } else if path == m.at { "" } else { path.strip_prefix(&m.at)?.strip_prefix('/')? };
+            Some((m.source.clone(), rel.to_string()))
+        }).collect()
+    }
+
+    /// `true` if `path` is a (strict) parent directory of any mount point.
+    fn is_mount_parent(&self, path: &str) -> bool {
+        self.mounts.read().unwrap().iter().any(|m| child_of(path, &m.at).is_some())
+    }
+}
+
+impl Default for Vfs {
+    fn default() -> Self { Self::new() }
+}
+
+impl Debug for Vfs {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        let mounts = self.mounts.read().unwrap();
+        let mut list = fmt.debug_list();
+        for m in mounts.iter() { list.entry(&format_args!("{:?} @ {:?} (priority {})", m.source.debug_name(), m.at, m.priority)); }
+        list.finish()
+    }
+}
+
+impl DiskDir {
+    pub fn new(root: impl Into<PathBuf>) -> Self { Self { root: root.into() } }
+
+    pub fn root(&self) -> &Path { &self.root }
+
+    fn read_path(path: &Path) -> io::Result<VfsFile> {
+        let data = std::fs::read(path)?;
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        Ok(VfsFile { data: Cow::Owned(data), modified })
+    }
+
+    fn metadata_path(path: &Path) -> io::Result<VfsMetadata> {
+        let meta = std::fs::metadata(path)?;
+        Ok(VfsMetadata { is_dir: meta.is_dir(), len: if meta.is_dir() { 0 } else { meta.len() }, modified: meta.modified().ok() })
+    }
+}
+
+impl VfsSource for DiskDir {
+    fn debug_name(&self) -> String { format!("disk:{}", self.root.display()) }
+    fn read(&self, path: &str) -> io::Result<VfsFile> { DiskDir::read_path(&self.root.join(path)) }
+    fn metadata(&self, path: &str) -> io::Result<VfsMetadata> { DiskDir::metadata_path(&self.root.join(path)) }
+
+    fn list(&self, path: &str) -> io::Result<Vec<VfsEntry>> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(self.root.join(path))? {
+            let entry = entry?;
+            if let Ok(name) = entry.file_name().into_string() { // non-UTF8 names aren't addressable by virtual paths anyways
+                entries.push(VfsEntry { name, is_dir: entry.file_type()?.is_dir() });
+            }
+        }
+        Ok(entries)
+    }
+}
+
+impl VfsSource for &'static StaticDir {
+    fn debug_name(&self) -> String { format!("static:{}", self.path_str()) }
+
+    fn read(&self, path: &str) -> io::Result<VfsFile> {
+        let file : &'static StaticFile = self.get(path).ok_or_else(|| not_found(path))?;
+        Ok(VfsFile { data: Cow::Borrowed(file.as_bytes()), modified: None })
+    }
+
+    fn metadata(&self, path: &str) -> io::Result<VfsMetadata> {
+        if let Some(file) = self.get(path) { return Ok(VfsMetadata { is_dir: false, len: file.len() as u64, modified: None }) }
+        if path.is_empty() || self.get_dir(path).is_some() { return Ok(VfsMetadata { is_dir: true, len: 0, modified: None }) }
+        Err(not_found(path))
+    }
+
+    fn list(&self, path: &str) -> io::Result<Vec<VfsEntry>> {
+        let (files, dirs) = if path.is_empty() { (self.files(), self.dirs()) } else {
+            let dir = self.get_dir(path).ok_or_else(|| not_found(path))?;
+            (dir.files(), dir.dirs())
+        };
+        let files = files.iter().map(|f| VfsEntry { name: f.path_str().rsplit('/').next().unwrap_or_default().into(), is_dir: false });
+        let dirs  = dirs .iter().map(|d| VfsEntry { name: d.name().into(), is_dir: true });
+        Ok(files.chain(dirs).collect())
+    }
+}
+
+/// Normalize a virtual path: `\` becomes `/`, empty and `.` components are removed, and `..` removes the previous component.
+///
+/// Leading `/`s are ignored (virtual paths are always relative to the root of the [`Vfs`].)
+/// Returns [`io::ErrorKind::InvalidInput`] if `..` would escape the root.
+///
+/// ```
+/// # use kakistocracy::io::normalize_path;
+/// assert_eq!(normalize_path("./sprites\\ui//../player.png").unwrap(), "sprites/player.png");
+/// assert!(normalize_path("../secrets.txt").is_err());
+/// ```
+pub fn normalize_path(path: &str) -> io::Result<String> {
+    let mut components = Vec::new();
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "."    => {},
+            ".."        => if components.pop().is_none() { return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{:?} escapes the root directory", path))) },
+            other       => components.push(other),
+        }
+    }
+    Ok(components.join("/"))
+}
+
+/// If `mount` is within the directory `dir`, the name of the child of `dir` leading to `mount`.
+fn child_of<'m>(dir: &str, mount: &'m str) -> Option<&'m str> {
+    let rest = if dir.is_empty() { mount } else { mount.strip_prefix(dir)?.strip_prefix('/')? };
+    if rest.is_empty() { return None }
+    rest.split('/').next()
+}
+
+/// `true` if `path` should bypass the mounts, and be read straight from disk (see [`Vfs`]'s "Paths".)
+fn is_native(path: &Path) -> bool {
+    path.is_absolute() || path.to_str().is_none_or(|path| normalize_path(path).is_err())
+}
+
+fn to_str(path: &Path) -> io::Result<&str> {
+    path.to_str().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not valid UTF-8", path.display())))
+}
+
+fn not_found(path: &str) -> io::Error { io::Error::new(io::ErrorKind::NotFound, format!("{:?} not found", path)) }
+
+
+
+#[test] fn overlay() {
+    static EXAMPLES : StaticDir = crate::include_dir!(CARGO_MANIFEST_DIR / "examples", "*.png");
+    static SRC : StaticDir = crate::include_dir!(CARGO_MANIFEST_DIR / "src", "image/**");
+
+    let vfs = Vfs::new();
+    let low  = vfs.mount("", 0, &EXAMPLES);
+    let _src = vfs.mount("code", 0, &SRC);
+    let high = vfs.mount("", 10, DiskDir::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src")));
+
+    // disk overrides embedded
+    assert!(vfs.read("lib.rs").unwrap().modified.is_some());
+    assert!(vfs.read("d3d-16x9.png").unwrap().modified.is_none(), "only embedded");
+    assert_eq!(vfs.read(".\\image/..//lib.rs").unwrap().data, vfs.read("lib.rs").unwrap().data);
+    assert_eq!(vfs.read("nope.png").unwrap_err().kind(), io::ErrorKind::NotFound);
+    assert_eq!(vfs.read("../escape").unwrap_err().kind(), io::ErrorKind::NotFound, "escaping paths are read from disk, bypassing mounts");
+
+    // nested mounts
+    assert!(vfs.read("code/image/bcn/bc1.rs").unwrap().data.starts_with(b"//!"));
+    assert!(vfs.read("code/lib.rs").is_err(), "filtered out by glob");
+    assert!(vfs.metadata("code").unwrap().is_dir);
+    assert!(vfs.metadata("code/image/bcn").unwrap().is_dir);
+    assert_eq!(vfs.metadata("d3d-16x9.png").unwrap().len, EXAMPLES.get("d3d-16x9.png").unwrap().len() as u64);
+
+    // merged listings
+    let root = vfs.list("").unwrap();
+    let names = root.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+    assert!(names.contains(&"d3d-16x9.png"));
+    assert!(names.contains(&"lib.rs"));
+    assert!(root.contains(&VfsEntry { name: "code".into(), is_dir: true }));
+    assert!(root.contains(&VfsEntry { name: "image".into(), is_dir: true }));
+    assert_eq!(vfs.list("code").unwrap(), [VfsEntry { name: "image".into(), is_dir: true }]);
+    assert_eq!(vfs.list("missing").unwrap_err().kind(), io::ErrorKind::NotFound);
+
+    assert!(vfs.unmount(high));
+    assert!(!vfs.unmount(high));
+    assert!(vfs.read("lib.rs").is_err());
+    assert!(vfs.unmount(low));
+    assert!(vfs.read("d3d-16x9.png").is_err());
+}
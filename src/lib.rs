@@ -1,6 +1,12 @@
 #![deny(unreachable_patterns)]
 
+#[path = "app/_app.rs"          ] pub mod app;
+#[path = "frame/_frame.rs"      ] pub mod frame;
+#[path = "headless/_headless.rs"] pub mod headless;
+#[path = "image/_image.rs"      ] pub mod image;
 #[path = "io/_io.rs"            ] pub mod io;
+#[path = "texture/_texture.rs"  ] pub mod texture;
+#[path = "time/_time.rs"        ] pub mod time;
 #[path = "utility/_utility.rs"  ] pub(crate) mod utility;
 #[path = "windows/_windows.rs"  ] pub mod windows;
 
@@ -2,7 +2,7 @@
 
 #![cfg_attr(not(all(windows, any(feature = "d3d9", feature = "d3d11"))), allow(dead_code))]
 
-use crate::io::StaticFile;
+use crate::texture::TextureSource;
 
 use std::ops::*;
 
@@ -10,13 +10,16 @@ use std::ops::*;
 
 /// Render `instances` of `texture` to `target`
 ///
+/// `texture` can be anything that converts into a [`TextureSource`]: a `&`[`StaticFile`](crate::io::StaticFile),
+/// a `&`[`Path`](std::path::Path), or a `&`[`RuntimeTexture`](crate::texture::RuntimeTexture).
+///
 /// ### Safety
 /// * `target` is expected to be "valid"
 ///     * render target 0 is expected to be valid/bound
 ///     * viewport is expected to be valid/bound
-pub unsafe fn render1<RT: RenderTarget>(mut target: RT, texture: &StaticFile, instances: &[Instance]) {
+pub unsafe fn render1<'t, RT: RenderTarget>(mut target: RT, texture: impl Into<TextureSource<'t>>, instances: &[Instance]) {
     target.begin();
-    target.render1(texture, instances);
+    target.render1(texture.into(), instances);
     target.end();
 }
 
@@ -77,7 +80,7 @@ pub(crate) mod private {
 
     pub trait RenderTarget {
         unsafe fn begin(&mut self) {}
-        unsafe fn render1(&mut self, texture: &StaticFile, instances: &[Instance]);
+        unsafe fn render1(&mut self, texture: TextureSource, instances: &[Instance]);
         unsafe fn end(&mut self) {}
     }
 }
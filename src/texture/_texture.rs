@@ -0,0 +1,6 @@
+//! [`TextureSource`]s that can be rendered, [`RuntimeTexture`]s generated at runtime, and the [`TextureCache`] that uploads them
+
+mod cache;                      pub use cache::*;
+mod cpu;                        pub use cpu::*;
+mod runtime;                    pub use runtime::*;
+mod source;                     pub use source::*;
@@ -0,0 +1,795 @@
+use crate::image::{Image, Placeholder};
+use crate::io::{AssetError, AssetLoadError, AssetRegistry, AssetSource, FileStamp, StaticFileKey, Vfs, WeakAssetHandle};
+use crate::texture::*;
+use crate::time::{Clock, RealClock};
+
+use instant::Instant;
+
+use std::cell::{Cell, RefCell};
+use std::collections::*;
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::io;
+use std::path::*;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+
+
+/// A graphics device that a [`TextureCache`] can create textures with.
+pub trait TextureDevice {
+    type Texture : Clone;
+
+    /// The format textures are created in, for diagnostics (e.g. `"D3DFMT_A8R8G8B8"`)
+    fn texture_format(&self) -> &'static str;
+
+    /// Create a texture containing `image`.
+    fn create_texture_2d(&self, image: &Image, debug_name: &str) -> Result<Self::Texture, Box<dyn Error>>;
+}
+
+/// Loads, decodes, and uploads [`TextureSource`]s on demand, remembering the results.
+///
+/// [`TextureSource::Path`]s are read through a [`Vfs`] ([`Vfs::global`] unless [`set_vfs`](Self::set_vfs) is called.)
+/// With [`set_hot_reload`](Self::set_hot_reload), they're reloaded when their files change on disk.
+/// Textures that fail to load are replaced with [`Placeholder`]s, distinguishing missing, undecodable, and unsupported files.
+/// [`TextureSource::Async`] textures use the [`Loading`](Placeholder::Loading) placeholder until their [`AssetHandle`](crate::io::AssetHandle) is ready.
+/// Failures are remembered, and can be inspected via [`failures`](Self::failures) or [`set_on_failure`](Self::set_on_failure).
+///
+/// ### Eviction
+///
+/// By default, textures are kept forever.  To limit memory use:
+/// *   [`set_budget`](Self::set_budget) evicts the least recently used textures whenever the cache exceeds a byte budget.
+/// *   [`evict_unused_for`](Self::evict_unused_for) evicts textures that haven't been used for a number of [`next_frame`](Self::next_frame)s.
+///
+/// [`pin`](Self::pin)ned textures are never evicted.
+/// Textures that failed to load are never evicted either, so they remain visible to [`failures`](Self::failures).
+/// Evicted textures are simply reloaded if used again.
+pub struct TextureCache<D: TextureDevice> {
+    device:                 D,
+    placeholder_missing:    D::Texture,
+    placeholder_decode:     D::Texture,
+    placeholder_format:     D::Texture,
+    placeholder_loading:    D::Texture,
+    vfs:                    RefCell<Arc<Vfs>>,
+    entries:                RefCell<HashMap<Key, Entry<D::Texture>>>,
+    on_failure:             RefCell<Option<FailureHook>>,
+    budget:                 Cell<Option<usize>>,
+    frame:                  Cell<u64>,
+    tick:                   Cell<u64>,
+    clock:                  RefCell<Arc<dyn Clock>>,
+    hot_reload:             Cell<Option<Duration>>,
+    next_reload_check:      Cell<Option<Instant>>,
+    counters:               Cell<TextureCacheStats>,
+}
+
+/// What kind of [`TextureSource`] a cached texture came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TextureSourceKind {
+    StaticFile,
+    Path,
+    Runtime,
+    Async,
+}
+
+/// The size and format of a successfully created texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureInfo {
+    pub width:  u32,
+    pub height: u32,
+    pub format: &'static str,
+    /// The (approximate) amount of memory used by the texture.
+    pub bytes:  usize,
+}
+
+/// A texture that failed to load, and was replaced with a placeholder.
+#[derive(Clone, Debug)]
+pub struct TextureFailure {
+    /// The path or debug name of the texture.
+    pub path:   String,
+    pub kind:   TextureSourceKind,
+    pub error:  Rc<dyn Error>,
+    /// The placeholder used in place of the texture.
+    pub placeholder: Placeholder,
+}
+
+/// Usage statistics for a [`TextureCache`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextureCacheStats {
+    /// Lookups that found an up-to-date texture already in the cache.
+    pub hits:           u64,
+    /// Lookups that had to load (or reload) a texture.
+    pub misses:         u64,
+    /// Textures reloaded because their files changed.
+    pub reloads:        u64,
+    /// Textures evicted, whether to stay within budget or by [`TextureCache::evict_unused_for`].
+    pub evictions:      u64,
+    /// Textures currently in the cache, including failures.
+    pub textures:       usize,
+    /// Textures currently [`pin`](TextureCache::pin)ned.
+    pub pinned:         usize,
+    /// The sum of [`TextureInfo::bytes`] for every texture currently in the cache.
+    pub resident_bytes: usize,
+    /// The highest `resident_bytes` has been.
+    pub peak_bytes:     usize,
+    pub budget:         Option<usize>,
+}
+
+/// A snapshot of everything in a [`TextureCache`], sorted by path.  [`Display`] this for a human readable table.
+#[derive(Clone, Debug)]
+pub struct TextureCacheSummary {
+    pub textures: Vec<TextureSummary>,
+}
+
+/// A single entry of a [`TextureCacheSummary`].
+#[derive(Clone, Debug)]
+pub struct TextureSummary {
+    /// The path or debug name of the texture.
+    pub path:   String,
+    pub kind:   TextureSourceKind,
+    /// `None` if the texture failed to load.
+    pub info:   Option<TextureInfo>,
+    pub error:  Option<String>,
+    pub pinned: bool,
+}
+
+impl<D: TextureDevice> TextureCache<D> {
+    /// Create a new cache, creating placeholder textures with `device`.
+    pub fn new(device: D) -> Result<Self, Box<dyn Error>> {
+        let placeholder = |p: Placeholder, name| device.create_texture_2d(&p.default_image(), name);
+        let placeholder_missing     = placeholder(Placeholder::Missing,     "kakistocracy::texture::TextureCache::placeholder_missing")?;
+        let placeholder_decode      = placeholder(Placeholder::DecodeError, "kakistocracy::texture::TextureCache::placeholder_decode_error")?;
+        let placeholder_format      = placeholder(Placeholder::Unsupported, "kakistocracy::texture::TextureCache::placeholder_unsupported")?;
+        let placeholder_loading     = placeholder(Placeholder::Loading,     "kakistocracy::texture::TextureCache::placeholder_loading")?;
+        Ok(Self {
+            device,
+            placeholder_missing,
+            placeholder_decode,
+            placeholder_format,
+            placeholder_loading,
+            vfs:        RefCell::new(Vfs::global().clone()),
+            entries:    Default::default(),
+            on_failure: Default::default(),
+            budget:     Default::default(),
+            frame:      Default::default(),
+            tick:       Default::default(),
+            clock:      RefCell::new(Arc::new(RealClock)),
+            hot_reload: Default::default(),
+            next_reload_check: Default::default(),
+            counters:   Default::default(),
+        })
+    }
+
+    pub fn device(&self) -> &D { &self.device }
+
+    /// The [`Vfs`] [`TextureSource::Path`]s are read from.
+    pub fn vfs(&self) -> Arc<Vfs> { self.vfs.borrow().clone() }
+
+    /// Read [`TextureSource::Path`]s from `vfs` instead.  Textures already loaded from paths are discarded, and reloaded from `vfs` when next used.
+    pub fn set_vfs(&self, vfs: Arc<Vfs>) {
+        *self.vfs.borrow_mut() = vfs;
+        self.entries.borrow_mut().retain(|key, _| !matches!(key, Key::Path(_)));
+    }
+
+    /// The texture used in place of textures that failed to load with `placeholder` (or are still [`Loading`](Placeholder::Loading).)
+    pub fn placeholder(&self, placeholder: Placeholder) -> D::Texture {
+        match placeholder {
+            Placeholder::Missing        => self.placeholder_missing.clone(),
+            Placeholder::DecodeError    => self.placeholder_decode.clone(),
+            Placeholder::Unsupported    => self.placeholder_format.clone(),
+            Placeholder::Loading        => self.placeholder_loading.clone(),
+        }
+    }
+
+    /// Get (loading if necessary) the texture for `source`, or a placeholder if it failed to load.
+    pub fn get_texture_2d<'s>(&self, source: impl Into<TextureSource<'s>>) -> D::Texture {
+        self.get(source.into(), false)
+    }
+
+    /// Get (loading if necessary) the texture for `source`, and keep it resident until [`unpin`](Self::unpin)ned.
+    pub fn pin<'s>(&self, source: impl Into<TextureSource<'s>>) -> D::Texture {
+        self.get(source.into(), true)
+    }
+
+    /// Allow the texture for `source` to be evicted again.
+    pub fn unpin<'s>(&self, source: impl Into<TextureSource<'s>>) {
+        let mut entries = self.entries.borrow_mut();
+        if let Some(entry) = entries.get_mut(&Key::new(source.into())) { entry.pinned = false; }
+        self.evict_to_budget(&mut entries, None);
+    }
+
+    /// Limit the cache to `bytes` (or unlimited if `None`), evicting least recently used textures immediately if necessary.
+    ///
+    /// The budget is soft: pinned textures, and the texture currently being loaded, are kept even if over budget.
+    pub fn set_budget(&self, bytes: Option<usize>) {
+        self.budget.set(bytes);
+        self.evict_to_budget(&mut self.entries.borrow_mut(), None);
+    }
+
+    pub fn budget(&self) -> Option<usize> { self.budget.get() }
+
+    /// Advance the frame counter used by [`evict_unused_for`](Self::evict_unused_for), and [`reload_changed`](Self::reload_changed) textures if hot reloading is due.
+    pub fn next_frame(&self) {
+        self.frame.set(self.frame.get() + 1);
+        if let Some(interval) = self.hot_reload.get() {
+            let now = self.clock.borrow().now();
+            if self.next_reload_check.get().is_none_or(|next| now >= next) {
+                self.next_reload_check.set(Some(now + interval));
+                self.reload_changed();
+            }
+        }
+    }
+
+    /// Check the files of [`TextureSource::Path`] textures for changes every `interval` (during [`next_frame`](Self::next_frame)), or never if `None`.
+    ///
+    /// Intended for development: each check reads the metadata of every such file.
+    pub fn set_hot_reload(&self, interval: Option<Duration>) {
+        self.hot_reload.set(interval);
+        self.next_reload_check.set(None);
+    }
+
+    pub fn hot_reload(&self) -> Option<Duration> { self.hot_reload.get() }
+
+    /// Measure [`set_hot_reload`](Self::set_hot_reload) intervals with `clock` instead of the real time.
+    pub fn set_clock(&self, clock: impl Clock + 'static) {
+        *self.clock.borrow_mut() = Arc::new(clock);
+        self.next_reload_check.set(None);
+    }
+
+    /// Immediately reload every [`TextureSource::Path`] texture whose file's modification time or size has changed,
+    /// or that has been created or deleted, since it was loaded.  Returns the number of textures reloaded.
+    pub fn reload_changed(&self) -> usize {
+        let vfs = self.vfs();
+        let stale = self.entries.borrow().iter().filter_map(|(key, entry)| match key {
+            Key::Path(path) => {
+                let stamp = vfs.metadata(path).ok().filter(|m| !m.is_dir).map(FileStamp::from);
+                if stamp != entry.stamp { Some(path.clone()) } else { None }
+            },
+            _ => None,
+        }).collect::<Vec<_>>();
+
+        for path in stale.iter() {
+            let mut entry = self.create_entry(TextureSource::Path(path));
+            let failure = entry.failure();
+            let key = Key::Path(path.clone());
+            let mut entries = self.entries.borrow_mut();
+            if let Some(prev) = entries.get(&key) {
+                entry.last_used        = prev.last_used;
+                entry.last_used_frame  = prev.last_used_frame;
+                entry.pinned           = prev.pinned;
+            }
+            entries.insert(key.clone(), entry);
+            self.evict_to_budget(&mut entries, Some(&key));
+            drop(entries);
+            self.count(|c| c.reloads += 1);
+            if let Some(failure) = failure { self.report_failure(&failure); }
+        }
+        stale.len()
+    }
+
+    /// The number of times [`next_frame`](Self::next_frame) has been called.
+    pub fn frame(&self) -> u64 { self.frame.get() }
+
+    /// Evict every unpinned texture that hasn't been used in the last `frames` frames.  Returns the number of textures evicted.
+    pub fn evict_unused_for(&self, frames: u64) -> usize {
+        let frame = self.frame.get();
+        let mut entries = self.entries.borrow_mut();
+        let before = entries.len();
+        entries.retain(|_, e| !e.evictable() || frame - e.last_used_frame < frames);
+        let evicted = before - entries.len();
+        self.count(|c| c.evictions += evicted as u64);
+        evicted
+    }
+
+    pub fn stats(&self) -> TextureCacheStats {
+        let entries = self.entries.borrow();
+        TextureCacheStats {
+            textures:       entries.len(),
+            pinned:         entries.values().filter(|e| e.pinned).count(),
+            resident_bytes: resident_bytes(&entries),
+            budget:         self.budget.get(),
+            .. self.counters.get()
+        }
+    }
+
+    /// All textures that failed to load, sorted by path.
+    pub fn failures(&self) -> Vec<TextureFailure> {
+        let mut failures = self.entries.borrow().values().filter_map(|e| e.failure()).collect::<Vec<_>>();
+        failures.sort_by(|a, b| a.path.cmp(&b.path));
+        failures
+    }
+
+    /// Call `hook` whenever a texture fails to load (once per failure, not every time the placeholder is used.)
+    ///
+    /// Replaces any previous hook.
+    pub fn set_on_failure(&self, hook: impl FnMut(&TextureFailure) + 'static) {
+        *self.on_failure.borrow_mut() = Some(Box::new(hook));
+    }
+
+    /// A snapshot of everything in the cache, for debugging.
+    pub fn summary(&self) -> TextureCacheSummary {
+        let mut textures = self.entries.borrow().values().map(|e| TextureSummary {
+            path:   e.path.clone(),
+            kind:   e.kind,
+            info:   e.info,
+            error:  e.error.as_ref().map(|err| err.to_string()),
+            pinned: e.pinned,
+        }).collect::<Vec<_>>();
+        textures.sort_by(|a, b| a.path.cmp(&b.path));
+        TextureCacheSummary { textures }
+    }
+}
+
+impl<D: TextureDevice> TextureCache<D> {
+    fn get(&self, source: TextureSource, pin: bool) -> D::Texture {
+        if let TextureSource::Async(handle) = source {
+            if handle.is_pending() { return self.placeholder(Placeholder::Loading) } // nothing to cache (or pin) yet
+        }
+
+        let key = Key::new(source);
+        let tick = self.tick.get() + 1;
+        self.tick.set(tick);
+
+        let mut entries = self.entries.borrow_mut();
+        let mut pinned = pin;
+        if let Some(entry) = entries.get_mut(&key) {
+            let stale = matches!((source, entry.runtime.as_ref()), (TextureSource::Runtime(rt), Some(&(generation, _))) if rt.generation() != generation);
+            if !stale {
+                entry.last_used = tick;
+                entry.last_used_frame = self.frame.get();
+                entry.pinned |= pin;
+                self.count(|c| c.hits += 1);
+                return entry.texture.clone();
+            }
+            pinned |= entry.pinned;
+        }
+        self.count(|c| c.misses += 1);
+
+        let mut entry = self.create_entry(source);
+        entry.last_used = tick;
+        entry.last_used_frame = self.frame.get();
+        entry.pinned = pinned;
+        if let TextureSource::Runtime(_) | TextureSource::Async(_) = source {
+            entries.retain(|_, entry| entry.is_alive()); // release textures of dropped `RuntimeTexture`s and `AssetHandle`s
+        }
+        let texture = entry.texture.clone();
+        let failure = entry.failure();
+        entries.insert(key.clone(), entry);
+        self.evict_to_budget(&mut entries, Some(&key));
+        drop(entries);
+
+        if let Some(failure) = failure { self.report_failure(&failure); }
+        texture
+    }
+
+    /// Evict least recently used textures (except `keep`) until within budget, or out of evictable textures.
+    fn evict_to_budget(&self, entries: &mut HashMap<Key, Entry<D::Texture>>, keep: Option<&Key>) {
+        let resident = resident_bytes(entries);
+        self.count(|c| c.peak_bytes = c.peak_bytes.max(resident));
+        let budget = match self.budget.get() { Some(b) => b, None => return };
+        if resident <= budget { return }
+
+        let mut lru = entries.iter().filter(|(k, e)| e.evictable() && Some(*k) != keep).map(|(k, e)| (e.last_used, k.clone())).collect::<Vec<_>>();
+        lru.sort_by_key(|(last_used, _)| *last_used);
+        let mut resident = resident;
+        for (_, key) in lru {
+            if resident <= budget { break }
+            if let Some(entry) = entries.remove(&key) {
+                resident -= entry.bytes();
+                self.count(|c| c.evictions += 1);
+            }
+        }
+    }
+
+    fn count(&self, f: impl FnOnce(&mut TextureCacheStats)) {
+        let mut counters = self.counters.get();
+        f(&mut counters);
+        self.counters.set(counters);
+    }
+
+    fn create_entry(&self, source: TextureSource) -> Entry<D::Texture> {
+        let path = source.debug_name().into_owned();
+        match source {
+            TextureSource::StaticFile(file) => {
+                let result = self.create_bytes(file.as_bytes(), &path);
+                self.entry(path, TextureSourceKind::StaticFile, result)
+            },
+            TextureSource::Path(vfs_path) => {
+                let mut stamp = None;
+                let result = match self.vfs().read(vfs_path) {
+                    Ok(file)    => {
+                        stamp = Some(FileStamp { modified: file.modified, len: file.data.len() as u64 });
+                        self.create_bytes(&file.data, &path)
+                    },
+                    Err(err)    => Err(Box::new(err) as Box<dyn Error>),
+                };
+                let mut entry = self.entry(path, TextureSourceKind::Path, result);
+                entry.stamp = stamp;
+                entry
+            },
+            TextureSource::Runtime(rt) => {
+                let (result, generation) = rt.with_image_generation(|image, generation| (self.create_image(image, &path), generation));
+                let mut entry = self.entry(path, TextureSourceKind::Runtime, result);
+                entry.runtime = Some((generation, rt.downgrade()));
+                entry
+            },
+            TextureSource::Async(handle) => {
+                let result = match handle.get() {
+                    Some(image) => self.create_image(&image, &path),
+                    None        => Err(Box::new(handle.error().expect("pending AssetHandles aren't cached")) as Box<dyn Error>),
+                };
+                let mut entry = self.entry(path, TextureSourceKind::Async, result);
+                entry.asset = Some(handle.downgrade());
+                entry
+            },
+        }
+    }
+
+    fn create_bytes(&self, bytes: &[u8], debug_name: &str) -> Result<(D::Texture, TextureInfo), Box<dyn Error>> {
+        let image = AssetRegistry::global().load::<Image>(AssetSource::new(debug_name, bytes))?;
+        self.create_image(&image, debug_name)
+    }
+
+    fn create_image(&self, image: &Image, debug_name: &str) -> Result<(D::Texture, TextureInfo), Box<dyn Error>> {
+        let texture = self.device.create_texture_2d(image, debug_name)?;
+        let (width, height) = image.dimensions();
+        Ok((texture, TextureInfo { width, height, format: self.device.texture_format(), bytes: image.byte_size() }))
+    }
+
+    fn entry(&self, path: String, kind: TextureSourceKind, result: Result<(D::Texture, TextureInfo), Box<dyn Error>>) -> Entry<D::Texture> {
+        let (texture, info, error) = match result {
+            Ok((texture, info)) => (texture, Some(info), None),
+            Err(err) => (self.placeholder(placeholder_for(&*err)), None, Some(Rc::from(err))),
+        };
+        Entry { texture, path, kind, info, error, stamp: None, runtime: None, asset: None, last_used: 0, last_used_frame: 0, pinned: false }
+    }
+
+    fn report_failure(&self, failure: &TextureFailure) {
+        let hook = self.on_failure.borrow_mut().take();
+        if let Some(mut hook) = hook {
+            hook(failure);
+            let mut on_failure = self.on_failure.borrow_mut();
+            if on_failure.is_none() { *on_failure = Some(hook); } // unless the hook replaced itself
+        }
+    }
+}
+
+impl<D: TextureDevice> Debug for TextureCache<D> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "TextureCache {{\n{}}}", self.summary())
+    }
+}
+
+impl TextureCacheSummary {
+    /// The total [`TextureInfo::bytes`] of all successfully loaded textures.
+    pub fn total_bytes(&self) -> usize { self.textures.iter().filter_map(|t| t.info).map(|i| i.bytes).sum() }
+
+    /// The number of textures that failed to load.
+    pub fn failed(&self) -> usize { self.textures.iter().filter(|t| t.error.is_some()).count() }
+}
+
+impl Display for TextureCacheSummary {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        for t in self.textures.iter() {
+            let kind = match t.kind {
+                TextureSourceKind::StaticFile   => "static",
+                TextureSourceKind::Path         => "path",
+                TextureSourceKind::Runtime      => "runtime",
+                TextureSourceKind::Async        => "async",
+            };
+            match (t.info, t.error.as_ref()) {
+                (Some(i), _)        => writeln!(fmt, "    {:<7} {:>5}x{:<5} {:>10} B  {:<32} {}", kind, i.width, i.height, i.bytes, i.format, t.path)?,
+                (None, Some(err))   => writeln!(fmt, "    {:<7} FAILED {}: {}", kind, t.path, err)?,
+                (None, None)        => writeln!(fmt, "    {:<7} ??? {}", kind, t.path)?,
+            }
+        }
+        writeln!(fmt, "    {} texture(s), {} byte(s), {} failure(s)", self.textures.len(), self.total_bytes(), self.failed())
+    }
+}
+
+
+
+type FailureHook = Box<dyn FnMut(&TextureFailure)>;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    StaticFile(StaticFileKey),
+    Path(PathBuf),
+    Runtime(u64),
+    Async(u64),
+}
+
+impl Key {
+    fn new(source: TextureSource) -> Self {
+        match source {
+            TextureSource::StaticFile(file) => Key::StaticFile(file.key()),
+            TextureSource::Path(path)       => Key::Path(path.to_path_buf()),
+            TextureSource::Runtime(rt)      => Key::Runtime(rt.id()),
+            TextureSource::Async(handle)    => Key::Async(handle.id()),
+        }
+    }
+}
+
+struct Entry<T> {
+    texture:        T,
+    path:           String,
+    kind:           TextureSourceKind,
+    info:           Option<TextureInfo>,
+    error:          Option<Rc<dyn Error>>,
+    /// For [`Key::Path`]s, the file as of when it was loaded (`None` if it couldn't be read)
+    stamp:          Option<FileStamp>,
+    runtime:        Option<(u64, WeakRuntimeTexture)>,
+    asset:          Option<WeakAssetHandle<Image>>,
+    last_used:      u64,
+    last_used_frame: u64,
+    pinned:         bool,
+}
+
+impl<T> Entry<T> {
+    fn bytes(&self) -> usize { self.info.map_or(0, |i| i.bytes) }
+
+    /// Failures are kept around for diagnostics, and only use shared placeholders anyways.
+    fn evictable(&self) -> bool { !self.pinned && self.info.is_some() }
+
+    /// `false` if the `RuntimeTexture` or `AssetHandle` this texture came from has been dropped.
+    fn is_alive(&self) -> bool {
+        self.runtime.as_ref().is_none_or(|(_, rt)| rt.is_alive()) && self.asset.as_ref().is_none_or(|a| a.is_alive())
+    }
+
+    fn failure(&self) -> Option<TextureFailure> {
+        let error = self.error.clone()?;
+        let placeholder = placeholder_for(&*error);
+        Some(TextureFailure { path: self.path.clone(), kind: self.kind, error, placeholder })
+    }
+}
+
+/// The placeholder best describing why a texture failed to load with `error`.
+///
+/// [`io::ErrorKind::NotFound`] is `Missing`, a file no registered format recognizes is `Unsupported`,
+/// and anything else - including a recognized format with corrupt data, like a `.png` with a damaged signature - is a `DecodeError`.
+/// [`AssetError`]s and [`AssetLoadError`]s are looked through to the error they wrap.
+fn placeholder_for(error: &(dyn Error + 'static)) -> Placeholder {
+    if let Some(err) = error.downcast_ref::<AssetError>() { return placeholder_for(&**err) }
+    if let Some(err) = error.downcast_ref::<AssetLoadError>() { return err.source().map_or(Placeholder::Unsupported, placeholder_for) }
+    if let Some(err) = error.downcast_ref::<io::Error>() {
+        if err.kind() == io::ErrorKind::NotFound { return Placeholder::Missing }
+    }
+    Placeholder::DecodeError
+}
+
+fn resident_bytes<T>(entries: &HashMap<Key, Entry<T>>) -> usize {
+    entries.values().map(|e| e.bytes()).sum()
+}
+
+
+
+
+/// Records every texture created, for testing caches without a real graphics device.
+#[cfg(test)] #[derive(Default)] struct FakeDevice {
+    created: RefCell<Vec<String>>,
+}
+
+#[cfg(test)] impl TextureDevice for FakeDevice {
+    type Texture = (u32, u32);
+    fn texture_format(&self) -> &'static str { "FAKE_R8G8B8A8" }
+    fn create_texture_2d(&self, image: &Image, debug_name: &str) -> Result<Self::Texture, Box<dyn Error>> {
+        if image.width() == 0 || image.height() == 0 { return Err("zero sized texture".into()) }
+        self.created.borrow_mut().push(debug_name.into());
+        Ok(image.dimensions())
+    }
+}
+
+#[test] fn failures() {
+    let cache = TextureCache::new(FakeDevice::default()).unwrap();
+    let reported = Rc::new(RefCell::new(Vec::new()));
+    let r = reported.clone();
+    cache.set_on_failure(move |failure| r.borrow_mut().push(failure.path.clone()));
+
+    let good = crate::include_file!("../../examples/d3d-16x9.png");
+    let bad = crate::io::StaticFile { path: "bad.png", data: b"not a png", _non_exhaustive_init_via_macros_only: () };
+    let missing = Path::new("definitely/does/not/exist.png");
+    let empty = RuntimeTexture::new("empty", Image::new(0, 0));
+
+    for _ in 0 .. 3 {
+        assert_eq!(cache.get_texture_2d(&good), (16, 9));
+        assert_eq!(cache.get_texture_2d(&bad), (16, 16));
+        assert_eq!(cache.get_texture_2d(missing), (16, 16));
+        assert_eq!(cache.get_texture_2d(&empty), (16, 16));
+    }
+
+    let failures = cache.failures();
+    let paths = failures.iter().map(|f| f.path.as_str()).collect::<Vec<_>>();
+    assert_eq!(paths, ["bad.png", "definitely/does/not/exist.png", "empty"]);
+    assert_eq!(failures[1].error.downcast_ref::<std::io::Error>().map(|e| e.kind()), Some(std::io::ErrorKind::NotFound));
+    let placeholders = failures.iter().map(|f| f.placeholder).collect::<Vec<_>>();
+    assert_eq!(placeholders, [Placeholder::DecodeError, Placeholder::Missing, Placeholder::DecodeError], "a .png that isn't is corrupt, not unsupported");
+    assert_eq!(*reported.borrow(), ["bad.png", "definitely/does/not/exist.png", "empty"], "hook should fire once per failure");
+
+    let summary = cache.summary();
+    assert_eq!(summary.textures.len(), 4);
+    assert_eq!(summary.failed(), 3);
+    assert_eq!(summary.total_bytes(), 16 * 9 * 4);
+    let text = summary.to_string();
+    assert!(text.contains("16x9"), "{}", text);
+    assert!(text.contains("FAKE_R8G8B8A8"), "{}", text);
+    assert!(text.contains("FAILED bad.png"), "{}", text);
+}
+
+#[test] fn placeholders_for_errors() {
+    let missing = io::Error::new(io::ErrorKind::NotFound, "nope");
+    let denied = io::Error::new(io::ErrorKind::PermissionDenied, "nope");
+    assert_eq!(placeholder_for(&missing), Placeholder::Missing);
+    assert_eq!(placeholder_for(&denied), Placeholder::DecodeError);
+    assert_eq!(placeholder_for(&Image::from_png_bytes(b"not a png").unwrap_err()), Placeholder::DecodeError);
+    assert_eq!(placeholder_for(&Image::from_png_bytes(b"\x89PNG\r\n\x1A\n truncated").unwrap_err()), Placeholder::DecodeError);
+
+    let registry = AssetRegistry::global();
+    assert_eq!(placeholder_for(&registry.load::<Image>(AssetSource::new("a.txt", b"text")).unwrap_err()), Placeholder::Unsupported);
+    assert_eq!(placeholder_for(&registry.load::<Image>(AssetSource::new("a.png", b"not a png")).unwrap_err()), Placeholder::DecodeError);
+    assert_eq!(placeholder_for(&registry.load::<Image>(AssetSource::new("a.png", b"\x89PNG\r\n\x1A\n truncated")).unwrap_err()), Placeholder::DecodeError);
+    assert_eq!(placeholder_for(&AssetError::from(Box::<dyn Error + Send + Sync>::from(missing))), Placeholder::Missing);
+}
+
+#[test] fn runtime_textures() {
+    let cache = TextureCache::new(FakeDevice::default()).unwrap();
+    let rt = RuntimeTexture::new("minimap", Image::new(4, 4));
+    assert_eq!(cache.get_texture_2d(&rt), (4, 4));
+    assert_eq!(cache.get_texture_2d(&rt), (4, 4));
+    rt.update(Image::new(8, 8));
+    assert_eq!(cache.get_texture_2d(&rt), (8, 8));
+    rt.invalidate();
+    assert_eq!(cache.get_texture_2d(&rt), (8, 8));
+    assert_eq!(cache.device().created.borrow().iter().filter(|n| *n == "minimap").count(), 3);
+
+    drop(rt);
+    let other = RuntimeTexture::new("other", Image::new(2, 2));
+    cache.get_texture_2d(&other);
+    assert_eq!(cache.summary().textures.iter().map(|t| t.path.as_str()).collect::<Vec<_>>(), ["other"], "dropped runtime textures should be released");
+}
+
+#[test] fn budget_lru() {
+    let cache = TextureCache::new(FakeDevice::default()).unwrap();
+    let textures = (0 .. 4).map(|i| RuntimeTexture::new(format!("tex{}", i), Image::new(4, 4))).collect::<Vec<_>>(); // 64 bytes each
+    let resident = |cache: &TextureCache<FakeDevice>| cache.summary().textures.into_iter().map(|t| t.path).collect::<Vec<_>>();
+
+    cache.set_budget(Some(128));
+    cache.get_texture_2d(&textures[0]);
+    cache.get_texture_2d(&textures[1]);
+    cache.get_texture_2d(&textures[0]); // tex1 is now least recently used
+    cache.get_texture_2d(&textures[2]);
+    assert_eq!(resident(&cache), ["tex0", "tex2"]);
+
+    cache.pin(&textures[3]);
+    assert_eq!(resident(&cache), ["tex2", "tex3"]);
+    cache.get_texture_2d(&textures[1]);
+    assert_eq!(resident(&cache), ["tex1", "tex3"], "pinned textures shouldn't be evicted");
+
+    cache.set_budget(Some(0));
+    assert_eq!(resident(&cache), ["tex3"], "pinned textures are kept even over budget");
+    cache.unpin(&textures[3]);
+    assert_eq!(resident(&cache), Vec::<String>::new());
+
+    let stats = cache.stats();
+    assert_eq!(stats.hits, 1);
+    assert_eq!(stats.misses, 5);
+    assert_eq!(stats.evictions, 5);
+    assert_eq!(stats.resident_bytes, 0);
+    assert_eq!(stats.peak_bytes, 192);
+    assert_eq!(stats.budget, Some(0));
+}
+
+#[test] fn frame_age() {
+    let cache = TextureCache::new(FakeDevice::default()).unwrap();
+    let a = RuntimeTexture::new("a", Image::new(1, 1));
+    let b = RuntimeTexture::new("b", Image::new(1, 1));
+    let c = RuntimeTexture::new("c", Image::new(1, 1));
+    let broken = RuntimeTexture::new("broken", Image::new(0, 0));
+    cache.pin(&c);
+    cache.get_texture_2d(&broken);
+    for _ in 0 .. 10 {
+        cache.get_texture_2d(&a);
+        cache.next_frame();
+    }
+    cache.get_texture_2d(&b);
+    cache.next_frame();
+    cache.next_frame();
+    assert_eq!(cache.frame(), 12);
+
+    assert_eq!(cache.evict_unused_for(3), 1);
+    assert_eq!(cache.summary().textures.into_iter().map(|t| t.path).collect::<Vec<_>>(), ["b", "broken", "c"]);
+    assert_eq!(cache.evict_unused_for(0), 1);
+    assert_eq!(cache.summary().textures.into_iter().map(|t| t.path).collect::<Vec<_>>(), ["broken", "c"]);
+    assert_eq!(cache.stats().pinned, 1);
+}
+
+#[test] fn compressed_files() {
+    let cache = TextureCache::new(FakeDevice::default()).unwrap();
+    let file = crate::include_file_compressed!("../../examples/d3d-16x9.png");
+    assert_eq!(cache.get_texture_2d(file), (16, 9));
+    assert_eq!(cache.get_texture_2d(file), (16, 9));
+    assert_eq!(cache.stats().misses, 1);
+    assert!(cache.failures().is_empty());
+
+    // identical contents share a texture, regardless of include site or compression
+    assert_eq!(cache.get_texture_2d(&crate::include_file!("../../examples/d3d-16x9.png")), (16, 9));
+    assert_eq!(cache.get_texture_2d(&crate::include_file!(CARGO_MANIFEST_DIR / "examples/d3d-16x9.png")), (16, 9));
+    assert_eq!(cache.stats().misses, 1);
+    assert_eq!(cache.stats().textures, 1);
+}
+
+#[test] fn vfs_paths() {
+    static EXAMPLES : crate::io::StaticDir = crate::include_dir!(CARGO_MANIFEST_DIR / "examples", "*.png");
+    let cache = TextureCache::new(FakeDevice::default()).unwrap();
+    assert_eq!(cache.get_texture_2d(Path::new("sprites/d3d-16x9.png")), (16, 16), "missing placeholder");
+
+    // relative paths escaping the current directory are read straight from disk, like std::fs
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR")).file_name().unwrap().to_str().unwrap();
+    assert_eq!(cache.get_texture_2d(Path::new(&format!("../{}/examples/d3d-16x9.png", crate_dir))), (16, 9));
+
+    let vfs = Arc::new(Vfs::new());
+    vfs.mount("sprites", 0, &EXAMPLES);
+    cache.set_vfs(vfs);
+    assert_eq!(cache.get_texture_2d(Path::new("sprites/d3d-16x9.png")), (16, 9));
+    assert_eq!(cache.get_texture_2d(Path::new("sprites\\d3d-16x16.png")), (16, 16));
+    assert!(cache.failures().is_empty());
+}
+
+#[test] fn hot_reload() {
+    let dir = std::env::temp_dir().join(format!("kakistocracy-texture-hot-reload-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let png = |name: &str| crate::io::Vfs::global().read(Path::new(env!("CARGO_MANIFEST_DIR")).join("examples").join(name)).unwrap().data.into_owned();
+
+    let vfs = Arc::new(Vfs::new());
+    vfs.mount("", 0, crate::io::DiskDir::new(&dir));
+    let cache = TextureCache::new(FakeDevice::default()).unwrap();
+    cache.set_vfs(vfs);
+    let sprite = Path::new("sprite.png");
+
+    assert_eq!(cache.get_texture_2d(sprite), (16, 16), "missing placeholder");
+    assert_eq!(cache.reload_changed(), 0);
+
+    std::fs::write(dir.join("sprite.png"), png("d3d-16x9.png")).unwrap();
+    assert_eq!(cache.reload_changed(), 1, "created");
+    assert!(cache.failures().is_empty());
+    assert_eq!(cache.get_texture_2d(sprite), (16, 9));
+
+    cache.set_hot_reload(Some(Duration::from_secs(3600)));
+    std::fs::write(dir.join("sprite.png"), png("d3d-16x16.png")).unwrap();
+    cache.next_frame(); // first check is immediate
+    assert_eq!(cache.get_texture_2d(sprite), (16, 16));
+    assert_eq!(cache.stats().reloads, 2);
+
+    std::fs::remove_file(dir.join("sprite.png")).unwrap();
+    cache.next_frame(); // not due yet
+    assert_eq!(cache.stats().reloads, 2);
+    assert_eq!(cache.reload_changed(), 1, "deleted");
+    assert_eq!(cache.failures().len(), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test] fn async_textures() {
+    use crate::io::{AssetHandle, AssetLoader};
+
+    let cache = TextureCache::new(FakeDevice::default()).unwrap();
+    let loader = AssetLoader::new(1);
+    let (go, wait) = std::sync::mpsc::channel::<()>();
+    let handle = loader.spawn("async", move || { wait.recv().unwrap(); Ok(Image::new(4, 4)) });
+    assert_eq!(cache.get_texture_2d(&handle), (16, 16));
+    assert_eq!(cache.stats().textures, 0, "loading placeholders aren't cached");
+    go.send(()).unwrap();
+    futures::executor::block_on(handle.clone()).unwrap();
+    assert_eq!(cache.get_texture_2d(&handle), (4, 4));
+    assert_eq!(cache.get_texture_2d(&handle.clone()), (4, 4));
+    assert_eq!(cache.device().created.borrow().iter().filter(|n| *n == "async").count(), 1);
+
+    let missing = AssetHandle::failed("missing.png", std::sync::Arc::new(std::io::Error::new(std::io::ErrorKind::NotFound, "nope")));
+    assert_eq!(cache.get_texture_2d(&missing), (16, 16));
+    assert_eq!(cache.failures().iter().map(|f| (f.kind, f.placeholder)).collect::<Vec<_>>(), [(TextureSourceKind::Async, Placeholder::Missing)]);
+
+    drop((handle, missing, loader)); // joins the loader thread, releasing its reference too
+    let other = AssetHandle::ready("other", Image::new(2, 2));
+    assert_eq!(cache.get_texture_2d(&other), (2, 2));
+    assert_eq!(cache.summary().textures.iter().map(|t| t.path.as_str()).collect::<Vec<_>>(), ["other"], "dropped asset handles should be released");
+}
@@ -0,0 +1,29 @@
+use crate::image::Image;
+use crate::texture::TextureDevice;
+
+use std::error::Error;
+use std::rc::Rc;
+
+
+
+/// A [`TextureDevice`] that keeps textures as CPU-side [`Image`]s, for software rendering and headless runs.
+///
+/// ```
+/// use kakistocracy::texture::*;
+///
+/// let cache = TextureCache::new(CpuTextureDevice).unwrap();
+/// let image = cache.get_texture_2d(&kakistocracy::include_file!(CARGO_MANIFEST_DIR / "examples/d3d-16x9.png"));
+/// assert_eq!(image.dimensions(), (16, 9));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CpuTextureDevice;
+
+impl TextureDevice for CpuTextureDevice {
+    type Texture = Rc<Image>;
+
+    fn texture_format(&self) -> &'static str { "R8G8B8A8_UNORM" }
+
+    fn create_texture_2d(&self, image: &Image, _debug_name: &str) -> Result<Self::Texture, Box<dyn Error>> {
+        Ok(Rc::new(image.clone()))
+    }
+}
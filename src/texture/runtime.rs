@@ -0,0 +1,127 @@
+use crate::image::Image;
+
+use std::fmt::{self, Debug, Formatter};
+use std::sync::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+
+
+/// A shared, mutable, CPU-side [`Image`] that can be rendered like any other texture.
+///
+/// Texture caches upload the image the first time it's rendered, and again whenever it's been
+/// [`update`](Self::update)d, [`modify`](Self::modify)d, or [`invalidate`](Self::invalidate)d since.
+/// Clones share the same image.  Once the last clone is dropped, caches release their copies.
+///
+/// ```
+/// use kakistocracy::image::Image;
+/// use kakistocracy::texture::RuntimeTexture;
+///
+/// let minimap = RuntimeTexture::new("minimap", Image::new(64, 64));
+/// minimap.modify(|image| image.set(32, 32, [255, 0, 0, 255]));
+/// assert_eq!(minimap.with_image(|image| image.get(32, 32)), Some([255, 0, 0, 255]));
+/// ```
+#[derive(Clone)]
+pub struct RuntimeTexture(Arc<Inner>);
+
+/// A non-owning reference to a [`RuntimeTexture`], used by caches to notice when it's been dropped.
+#[derive(Clone)]
+pub(crate) struct WeakRuntimeTexture(Weak<Inner>);
+
+struct Inner {
+    id:         u64,
+    debug_name: String,
+    state:      Mutex<State>,
+}
+
+struct State {
+    image:      Image,
+    generation: u64,
+}
+
+impl RuntimeTexture {
+    /// Create a new runtime texture.  `debug_name` is used for graphics debuggers and error messages.
+    pub fn new(debug_name: impl Into<String>, image: Image) -> Self {
+        static NEXT_ID : AtomicU64 = AtomicU64::new(1);
+        Self(Arc::new(Inner {
+            id:         NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            debug_name: debug_name.into(),
+            state:      Mutex::new(State { image, generation: 0 }),
+        }))
+    }
+
+    /// A process-unique identifier for this texture (shared by clones.)
+    pub fn id(&self) -> u64 { self.0.id }
+
+    pub fn debug_name(&self) -> String { self.0.debug_name.clone() }
+
+    /// Incremented every time the texture is updated, modified, or invalidated.
+    pub fn generation(&self) -> u64 { self.state().generation }
+
+    /// `(width, height)` of the current image.
+    pub fn dimensions(&self) -> (u32, u32) { self.state().image.dimensions() }
+
+    /// Replace the image.
+    pub fn update(&self, image: Image) {
+        let mut state = self.state();
+        state.image = image;
+        state.generation += 1;
+    }
+
+    /// Modify the image in place.
+    pub fn modify<R>(&self, f: impl FnOnce(&mut Image) -> R) -> R {
+        let mut state = self.state();
+        state.generation += 1;
+        f(&mut state.image)
+    }
+
+    /// Force caches to re-upload the image on next use, without changing it.
+    pub fn invalidate(&self) { self.state().generation += 1; }
+
+    /// Access the current image.
+    pub fn with_image<R>(&self, f: impl FnOnce(&Image) -> R) -> R { f(&self.state().image) }
+
+    /// Access the current image and the [`generation`](Self::generation) it belongs to.
+    pub(crate) fn with_image_generation<R>(&self, f: impl FnOnce(&Image, u64) -> R) -> R {
+        let state = self.state();
+        f(&state.image, state.generation)
+    }
+
+    pub(crate) fn downgrade(&self) -> WeakRuntimeTexture { WeakRuntimeTexture(Arc::downgrade(&self.0)) }
+
+    fn state(&self) -> MutexGuard<'_, State> { self.0.state.lock().unwrap_or_else(|poison| poison.into_inner()) }
+}
+
+impl WeakRuntimeTexture {
+    /// `true` if any [`RuntimeTexture`] clones still exist.
+    pub fn is_alive(&self) -> bool { self.0.strong_count() > 0 }
+}
+
+impl Debug for RuntimeTexture {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "RuntimeTexture({:?}, {:?})", self.0.debug_name, self.state().image)
+    }
+}
+
+
+
+#[test] fn generations() {
+    let tex = RuntimeTexture::new("test", Image::new(2, 2));
+    let clone = tex.clone();
+    assert_eq!(tex.id(), clone.id());
+    assert_ne!(tex.id(), RuntimeTexture::new("test", Image::new(2, 2)).id());
+
+    assert_eq!(tex.generation(), 0);
+    clone.update(Image::new(4, 4));
+    assert_eq!(tex.generation(), 1);
+    assert_eq!(tex.dimensions(), (4, 4));
+    tex.modify(|image| image.set(0, 0, [1, 2, 3, 4]));
+    tex.invalidate();
+    assert_eq!(clone.generation(), 3);
+    assert_eq!(clone.with_image(|image| image.get(0, 0)), Some([1, 2, 3, 4]));
+
+    let weak = tex.downgrade();
+    drop(tex);
+    assert!(weak.is_alive());
+    drop(clone);
+    assert!(!weak.is_alive());
+}
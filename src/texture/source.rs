@@ -0,0 +1,55 @@
+use crate::image::Image;
+use crate::io::{AssetHandle, CompressedStaticFile, StaticFile, StaticImage};
+use crate::texture::RuntimeTexture;
+
+use std::borrow::Cow;
+use std::path::*;
+
+
+
+/// Where a texture's pixels come from.
+///
+/// Anything that converts into a `TextureSource` can be passed to [`sprite::render1`](crate::sprite::render1):
+///
+/// | Source                | Identity                  | Reloaded when                                 |
+/// | --------------------- | ------------------------- | --------------------------------------------- |
+/// | `&`[`StaticFile`]     | the embedded bytes        | never                                         |
+/// | `&`[`CompressedStaticFile`] | the decompressed bytes | never (decompressed on first use)       |
+/// | `&`[`StaticImage`]    | the embedded bytes        | never                                         |
+/// | `&`[`Path`]           | the path                  | its file changes, with [`TextureCache::set_hot_reload`](crate::texture::TextureCache::set_hot_reload) |
+/// | `&`[`RuntimeTexture`] | the `RuntimeTexture`      | [`update`](RuntimeTexture::update)d or [`invalidate`](RuntimeTexture::invalidate)d |
+/// | `&`[`AssetHandle`]`<Image>` | the `AssetHandle`   | never (a loading placeholder is used until it's ready) |
+#[derive(Clone, Copy, Debug)]
+pub enum TextureSource<'a> {
+    /// An embedded (typically PNG) file, e.g. from [`include_file!`](crate::include_file).
+    StaticFile(&'a StaticFile),
+
+    /// A (typically PNG) file, read through the [`Vfs`](crate::io::Vfs) of the [`TextureCache`](crate::texture::TextureCache) (typically from disk.)
+    Path(&'a Path),
+
+    /// A runtime generated or downloaded image.
+    Runtime(&'a RuntimeTexture),
+
+    /// An image being loaded in the background by an [`AssetLoader`](crate::io::AssetLoader).
+    Async(&'a AssetHandle<Image>),
+}
+
+impl TextureSource<'_> {
+    /// A human readable name for the texture, suitable for debug names and error messages.
+    pub fn debug_name(&self) -> Cow<'_, str> {
+        match self {
+            TextureSource::StaticFile(file) => Cow::Borrowed(file.path_str()),
+            TextureSource::Path(path)       => path.to_string_lossy(),
+            TextureSource::Runtime(rt)      => Cow::Owned(rt.debug_name()),
+            TextureSource::Async(handle)    => Cow::Borrowed(handle.debug_name()),
+        }
+    }
+}
+
+impl<'a> From<&'a StaticFile    > for TextureSource<'a> { fn from(file: &'a StaticFile      ) -> Self { TextureSource::StaticFile(file) } }
+impl<'a> From<&'a CompressedStaticFile> for TextureSource<'a> { fn from(file: &'a CompressedStaticFile) -> Self { TextureSource::StaticFile(file.static_file()) } }
+impl<'a> From<&'a StaticImage   > for TextureSource<'a> { fn from(image: &'a StaticImage    ) -> Self { TextureSource::StaticFile(image.file()) } }
+impl<'a> From<&'a Path          > for TextureSource<'a> { fn from(path: &'a Path            ) -> Self { TextureSource::Path(path) } }
+impl<'a> From<&'a PathBuf       > for TextureSource<'a> { fn from(path: &'a PathBuf         ) -> Self { TextureSource::Path(path) } }
+impl<'a> From<&'a RuntimeTexture> for TextureSource<'a> { fn from(tex:  &'a RuntimeTexture  ) -> Self { TextureSource::Runtime(tex) } }
+impl<'a> From<&'a AssetHandle<Image>> for TextureSource<'a> { fn from(handle: &'a AssetHandle<Image>) -> Self { TextureSource::Async(handle) } }
@@ -0,0 +1,7 @@
+//! Frame timing: [`Clock`]s, [`FrameRateCounter`]s and the [`FrameStats`] they report, [`FixedTimestep`]s for simulation updates, and [`FramePacer`]s to hit a target frame rate
+
+mod clock;                      pub use clock::*;
+mod fixed_timestep;             pub use fixed_timestep::*;
+mod frame_pacer;                pub use frame_pacer::*;
+mod frame_rate_counter;         pub use frame_rate_counter::*;
+mod frame_stats;                pub use frame_stats::*;
@@ -0,0 +1,145 @@
+use instant::{Duration, Instant};
+
+use std::sync::{Arc, Mutex};
+
+
+
+/// A source of the current time.  Timing code takes a `Clock` instead of calling [`Instant::now`] directly, so tests can control time.
+///
+/// | Clock             | Time                                                  |
+/// | ----------------- | ----------------------------------------------------- |
+/// | [`RealClock`]     | [`Instant::now`]                                      |
+/// | [`ManualClock`]   | only advances when told to                            |
+/// | [`ScaledClock`]   | another clock, sped up, slowed down, or paused        |
+///
+/// Clocks are typically shared via [`Arc`]: `Arc<ManualClock>` is itself a `Clock`.
+pub trait Clock : Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The real, monotonic time, via [`Instant::now`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RealClock;
+
+/// A clock that only advances when [`advance`](Self::advance)d.  Starts at the real time it was created.
+///
+/// ```
+/// # use kakistocracy::time::*;
+/// # use std::time::Duration;
+/// let clock = ManualClock::new();
+/// let start = clock.now();
+/// clock.advance(Duration::from_millis(16));
+/// assert_eq!(clock.now() - start, Duration::from_millis(16));
+/// ```
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<Instant>,
+}
+
+/// Another clock, scaled by a time scale, and optionally paused.
+///
+/// Changing the scale or pausing only affects time from then on: the clock never jumps, and never runs backwards.
+#[derive(Debug)]
+pub struct ScaledClock<C: Clock = RealClock> {
+    source: C,
+    state:  Mutex<ScaledState>,
+}
+
+#[derive(Debug)]
+struct ScaledState {
+    /// The source's time as of the last update
+    source: Instant,
+    /// This clock's time as of the last update
+    now:    Instant,
+    scale:  f64,
+    paused: bool,
+}
+
+impl Clock for RealClock {
+    fn now(&self) -> Instant { Instant::now() }
+}
+
+impl ManualClock {
+    pub fn new() -> Self { Self { now: Mutex::new(Instant::now()) } }
+
+    /// Move time forward by `dt`.
+    pub fn advance(&self, dt: Duration) { *self.now.lock().unwrap() += dt; }
+
+    /// Set the current time.  Unlike real time, this can go backwards.
+    pub fn set(&self, now: Instant) { *self.now.lock().unwrap() = now; }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self { Self::new() }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant { *self.now.lock().unwrap() }
+}
+
+impl<C: Clock> ScaledClock<C> {
+    /// Follow `source` at a time scale of 1.0.
+    pub fn new(source: C) -> Self {
+        let now = source.now();
+        Self { source, state: Mutex::new(ScaledState { source: now, now, scale: 1.0, paused: false }) }
+    }
+
+    pub fn source(&self) -> &C { &self.source }
+
+    /// Run at `scale` times the speed of the source clock (e.g. `0.5` for slow motion.)  Negative scales are treated as `0.0`.
+    pub fn set_scale(&self, scale: f64) { self.update(|s| s.scale = scale.max(0.0)); }
+    pub fn scale(&self) -> f64 { self.state.lock().unwrap().scale }
+
+    pub fn set_paused(&self, paused: bool) { self.update(|s| s.paused = paused); }
+    pub fn pause(&self) { self.set_paused(true) }
+    pub fn resume(&self) { self.set_paused(false) }
+    pub fn is_paused(&self) -> bool { self.state.lock().unwrap().paused }
+
+    /// Catch up with the source clock, then apply `f`.
+    fn update<R>(&self, f: impl FnOnce(&mut ScaledState) -> R) -> R {
+        let source = self.source.now();
+        let state = &mut *self.state.lock().unwrap();
+        let elapsed = source.saturating_duration_since(state.source);
+        if !state.paused { state.now += elapsed.mul_f64(state.scale); }
+        state.source = source;
+        f(state)
+    }
+}
+
+impl Default for ScaledClock {
+    fn default() -> Self { Self::new(RealClock) }
+}
+
+impl<C: Clock> Clock for ScaledClock<C> {
+    fn now(&self) -> Instant { self.update(|s| s.now) }
+}
+
+impl<C: Clock + ?Sized> Clock for &C     { fn now(&self) -> Instant { (**self).now() } }
+impl<C: Clock + ?Sized> Clock for Box<C> { fn now(&self) -> Instant { (**self).now() } }
+impl<C: Clock + ?Sized> Clock for Arc<C> { fn now(&self) -> Instant { (**self).now() } }
+
+
+
+#[test] fn scaled() {
+    let ms = Duration::from_millis;
+    let manual = Arc::new(ManualClock::new());
+    let scaled = ScaledClock::new(manual.clone());
+    let start = scaled.now();
+
+    manual.advance(ms(100));
+    assert_eq!(scaled.now() - start, ms(100));
+    scaled.set_scale(0.5);
+    manual.advance(ms(100));
+    assert_eq!(scaled.now() - start, ms(150));
+    scaled.pause();
+    manual.advance(ms(100));
+    assert_eq!(scaled.now() - start, ms(150));
+    assert!(scaled.is_paused());
+    scaled.resume();
+    scaled.set_scale(2.0);
+    manual.advance(ms(100));
+    assert_eq!(scaled.now() - start, ms(350));
+    scaled.set_scale(-1.0);
+    manual.advance(ms(100));
+    assert_eq!(scaled.now() - start, ms(350), "never runs backwards");
+}
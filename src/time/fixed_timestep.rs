@@ -0,0 +1,155 @@
+use instant::{Duration, Instant};
+
+
+
+/// Runs simulation updates at a fixed rate, independent of the frame rate, with an interpolation factor for rendering in between.
+///
+/// Each frame, the time elapsed since the last frame is added to an accumulator, and one update is run per whole `step` accumulated.
+/// To avoid a "spiral of death" (updates taking longer than the time they simulate, requiring ever more updates to catch up),
+/// frame times are clamped to [`max_frame_time`](Self::set_max_frame_time), and at most [`max_steps`](Self::set_max_steps) updates are run per frame.
+/// Time that couldn't be caught up on is dropped: the simulation runs slower than real time instead of freezing.
+///
+/// Time is whatever the caller passes to [`frame`](Self::frame) or [`advance`](Self::advance) - typically a [`Clock`](crate::time::Clock)'s `now()` - so tests can drive it deterministically.
+///
+/// ```
+/// # use kakistocracy::time::*;
+/// # use std::time::Duration;
+/// let mut fixed = FixedTimestep::from_hz(60.0);
+/// let (mut position, mut previous, speed) = (0.0, 0.0, 120.0);
+/// let frame = fixed.advance(Duration::from_millis(40)); // e.g. a 25 fps frame
+/// for _ in 0 .. frame.steps {
+///     previous = position;
+///     position += speed * fixed.step().as_secs_f64();
+/// }
+/// let rendered = previous + (position - previous) * frame.alpha;
+/// assert_eq!(frame.steps, 2);
+/// assert!((rendered - 2.8).abs() < 1e-6);
+/// ```
+#[derive(Clone, Debug)]
+pub struct FixedTimestep {
+    step:           Duration,
+    max_steps:      u32,
+    max_frame_time: Duration,
+    accumulator:    Duration,
+    last:           Option<Instant>,
+    steps:          u64,
+    dropped:        Duration,
+}
+
+/// What to do this frame, as determined by [`FixedTimestep::advance`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FixedFrame {
+    /// The number of fixed updates to run.
+    pub steps:      u32,
+    /// How far between the last two updates to interpolate rendering, from `0.0` (the previous update) to `1.0` (the latest.)
+    pub alpha:      f64,
+    /// Time discarded this frame to avoid falling further behind.
+    pub dropped:    Duration,
+}
+
+impl FixedTimestep {
+    /// Update every `step`.  Defaults to at most 5 updates per frame, and frames of at most 250ms.
+    pub fn new(step: Duration) -> Self {
+        assert!(!step.is_zero(), "FixedTimestep::new: step must be nonzero");
+        Self { step, max_steps: 5, max_frame_time: Duration::from_millis(250), accumulator: Duration::ZERO, last: None, steps: 0, dropped: Duration::ZERO }
+    }
+
+    /// Update `hz` times per second.
+    pub fn from_hz(hz: f64) -> Self { Self::new(Duration::from_secs_f64(1.0 / hz)) }
+
+    pub fn step(&self) -> Duration { self.step }
+
+    /// Run at most `steps` (at least 1) updates per frame.
+    pub fn set_max_steps(&mut self, steps: u32) { self.max_steps = steps.max(1); }
+    pub fn max_steps(&self) -> u32 { self.max_steps }
+
+    /// Treat frames longer than `max` (e.g. after a breakpoint or a window drag) as if they only took `max`.
+    pub fn set_max_frame_time(&mut self, max: Duration) { self.max_frame_time = max; }
+    pub fn max_frame_time(&self) -> Duration { self.max_frame_time }
+
+    /// The total number of updates run so far.
+    pub fn total_steps(&self) -> u64 { self.steps }
+
+    /// The total time dropped so far.
+    pub fn total_dropped(&self) -> Duration { self.dropped }
+
+    /// The current interpolation factor (see [`FixedFrame::alpha`].)
+    pub fn alpha(&self) -> f64 { self.accumulator.as_secs_f64() / self.step.as_secs_f64() }
+
+    /// Advance by `elapsed` time, returning how many updates to run.
+    pub fn advance(&mut self, elapsed: Duration) -> FixedFrame {
+        let clamped = elapsed.min(self.max_frame_time);
+        let mut dropped = elapsed - clamped;
+        self.accumulator += clamped;
+
+        let mut steps = 0;
+        while self.accumulator >= self.step && steps < self.max_steps {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        if self.accumulator >= self.step {
+            // still behind: drop whole steps, but keep the fractional step so alpha stays continuous
+            let remainder = Duration::from_nanos((self.accumulator.as_nanos() % self.step.as_nanos()) as u64);
+            dropped += self.accumulator - remainder;
+            self.accumulator = remainder;
+        }
+
+        self.steps += u64::from(steps);
+        self.dropped += dropped;
+        FixedFrame { steps, alpha: self.alpha(), dropped }
+    }
+
+    /// Advance to `now`, measuring elapsed time from the previous call (the first call advances by nothing.)
+    pub fn advance_to(&mut self, now: Instant) -> FixedFrame {
+        let elapsed = self.last.map_or(Duration::ZERO, |last| now.saturating_duration_since(last));
+        self.last = Some(now);
+        self.advance(elapsed)
+    }
+
+    /// [`advance_to`](Self::advance_to) `now`, then call `update(step)` for each update, then `render(alpha)` once.
+    pub fn frame(&mut self, now: Instant, mut update: impl FnMut(Duration), render: impl FnOnce(f64)) -> FixedFrame {
+        let frame = self.advance_to(now);
+        for _ in 0 .. frame.steps { update(self.step); }
+        render(frame.alpha);
+        frame
+    }
+}
+
+
+
+#[test] fn accumulate() {
+    let ms = Duration::from_millis;
+    let mut fixed = FixedTimestep::new(ms(10));
+    assert_eq!(fixed.advance(ms(5)), FixedFrame { steps: 0, alpha: 0.5, dropped: ms(0) });
+    assert_eq!(fixed.advance(ms(5)), FixedFrame { steps: 1, alpha: 0.0, dropped: ms(0) });
+    assert_eq!(fixed.advance(ms(27)).steps, 2);
+    assert!((fixed.alpha() - 0.7).abs() < 1e-9);
+    assert_eq!(fixed.total_steps(), 3);
+}
+
+#[test] fn spiral_of_death() {
+    let ms = Duration::from_millis;
+    let mut fixed = FixedTimestep::new(ms(10));
+    fixed.set_max_steps(3);
+    fixed.set_max_frame_time(ms(105));
+
+    // a 1s hitch is clamped to 105ms, of which only 3 steps are run, and the rest (minus the fractional step) dropped
+    let frame = fixed.advance(ms(1004));
+    assert_eq!(frame.steps, 3);
+    assert_eq!(frame.dropped, ms(899) + ms(70));
+    assert!((frame.alpha - 0.5).abs() < 1e-9, "{}", frame.alpha);
+    assert_eq!(fixed.advance(ms(6)).steps, 1, "recovered");
+    assert_eq!(fixed.total_dropped(), ms(969));
+}
+
+#[test] fn driven_by_instants() {
+    let ms = Duration::from_millis;
+    let start = Instant::now();
+    let mut fixed = FixedTimestep::new(ms(10));
+    let (mut updates, mut renders) = (0, Vec::new());
+    for t in [0, 16, 33, 50].iter() {
+        fixed.frame(start + ms(*t), |dt| { assert_eq!(dt, ms(10)); updates += 1; }, |alpha| renders.push((alpha * 10.0).round() as u32));
+    }
+    assert_eq!(updates, 5);
+    assert_eq!(renders, [0, 6, 3, 0]);
+}
@@ -0,0 +1,212 @@
+use crate::time::{Clock, ManualClock, RealClock};
+
+use instant::{Duration, Instant};
+
+use std::fmt::{self, Display, Formatter};
+use std::sync::Arc;
+
+
+
+/// Waits out the remainder of each frame to hit a target frame rate, instead of spinning flat out.
+///
+/// OS sleeps are coarse (often overshooting by a millisecond or more), so the pacer sleeps until
+/// [`spin_threshold`](Self::set_spin_threshold) before the deadline, then spins the rest of the way.
+/// Frames that finish late don't wait at all, and the pacer doesn't try to "catch up" after falling more than a frame behind.
+///
+/// The [`Clock`] and [`Sleeper`] are injectable, so pacing can be tested without actually waiting:
+///
+/// ```
+/// # use kakistocracy::time::*;
+/// # use std::{sync::Arc, time::Duration};
+/// let ms = Duration::from_millis;
+/// let clock = Arc::new(ManualClock::new());
+/// let mut pacer = FramePacer::new(Some(ms(10)));
+/// pacer.set_clock(clock.clone());
+/// pacer.set_sleeper(clock.clone()); // "sleeping" advances the manual clock
+///
+/// pacer.wait();
+/// clock.advance(ms(3)); // ...render a 3ms frame...
+/// let frame = pacer.wait();
+/// assert_eq!(frame.slept + frame.spun, ms(7));
+/// ```
+pub struct FramePacer {
+    clock:              Arc<dyn Clock>,
+    sleeper:            Arc<dyn Sleeper>,
+    target:             Option<Duration>,
+    spin_threshold:     Duration,
+    next:               Option<Instant>,
+    stats:              PacingStats,
+}
+
+/// Waits for a [`FramePacer`].  [`RealClock`] really sleeps, [`ManualClock`] advances itself instead.
+pub trait Sleeper : Send + Sync {
+    /// Sleep for (at least, approximately) `duration`.
+    fn sleep(&self, duration: Duration);
+
+    /// Busy-wait briefly.  `remaining` is how long until the deadline being waited for.
+    fn spin(&self, _remaining: Duration) { std::hint::spin_loop() }
+}
+
+/// How [`FramePacer::wait`] waited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PacedFrame {
+    /// Time spent sleeping.
+    pub slept:      Duration,
+    /// Time spent spinning after sleeping.
+    pub spun:       Duration,
+    /// How far past the deadline the wait ended.
+    pub overshoot:  Duration,
+    /// The frame was already past its deadline, so didn't wait.
+    pub late:       bool,
+}
+
+/// Totals over every [`FramePacer::wait`] since the pacer was created (or [`reset_stats`](FramePacer::reset_stats).)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PacingStats {
+    pub frames:         u64,
+    /// Frames that were already past their deadline.
+    pub late:           u64,
+    pub slept:          Duration,
+    pub spun:           Duration,
+    /// The average [`PacedFrame::overshoot`] of frames that waited.
+    pub avg_overshoot:  Duration,
+    pub max_overshoot:  Duration,
+}
+
+impl FramePacer {
+    /// Pace frames to `target` apart, or not at all if `None`.  Spins for the last 2ms of each frame by default.
+    pub fn new(target: Option<Duration>) -> Self {
+        Self { clock: Arc::new(RealClock), sleeper: Arc::new(RealClock), target, spin_threshold: Duration::from_millis(2), next: None, stats: PacingStats::default() }
+    }
+
+    /// Pace frames to `hz` frames per second.
+    pub fn from_hz(hz: f64) -> Self { Self::new(Some(Duration::from_secs_f64(1.0 / hz))) }
+
+    /// Don't pace frames at all.
+    pub fn unlimited() -> Self { Self::new(None) }
+
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) { self.clock = Arc::new(clock); self.next = None; }
+    pub fn set_sleeper(&mut self, sleeper: impl Sleeper + 'static) { self.sleeper = Arc::new(sleeper); }
+
+    /// Pace frames to `target` apart, or not at all if `None`.
+    pub fn set_target(&mut self, target: Option<Duration>) { self.target = target; self.next = None; }
+    pub fn target(&self) -> Option<Duration> { self.target }
+
+    /// Spin (instead of sleeping) for the last `threshold` of each frame.  Larger thresholds are more precise, but burn more CPU.
+    pub fn set_spin_threshold(&mut self, threshold: Duration) { self.spin_threshold = threshold; }
+    pub fn spin_threshold(&self) -> Duration { self.spin_threshold }
+
+    pub fn stats(&self) -> PacingStats { self.stats }
+    pub fn reset_stats(&mut self) { self.stats = PacingStats::default(); }
+
+    /// Wait until the next frame should start.  Call once per frame (the first call returns immediately.)
+    pub fn wait(&mut self) -> PacedFrame {
+        let start = self.clock.now();
+        let mut frame = PacedFrame::default();
+        if let Some(target) = self.target {
+            let deadline = *self.next.get_or_insert(start);
+            if start < deadline {
+                let remaining = deadline - start;
+                if remaining > self.spin_threshold { self.sleeper.sleep(remaining - self.spin_threshold); }
+                let slept = self.clock.now();
+                let mut now = slept;
+                while now < deadline {
+                    self.sleeper.spin(deadline - now);
+                    now = self.clock.now();
+                }
+                frame.slept     = slept.saturating_duration_since(start);
+                frame.spun      = now.saturating_duration_since(slept);
+                frame.overshoot = now - deadline;
+            } else {
+                frame.late = start > deadline;
+            }
+
+            let end = start + frame.slept + frame.spun;
+            self.next = Some(if end.saturating_duration_since(deadline) >= target { end + target } else { deadline + target });
+        }
+        self.record(frame);
+        frame
+    }
+
+    fn record(&mut self, frame: PacedFrame) {
+        let stats = &mut self.stats;
+        let waited = stats.frames - stats.late;
+        if !frame.late && self.target.is_some() {
+            stats.avg_overshoot = (stats.avg_overshoot * waited as u32 + frame.overshoot) / (waited + 1) as u32;
+            stats.max_overshoot = stats.max_overshoot.max(frame.overshoot);
+        }
+        stats.frames   += 1;
+        stats.late     += u64::from(frame.late);
+        stats.slept    += frame.slept;
+        stats.spun     += frame.spun;
+    }
+}
+
+impl Default for FramePacer {
+    /// 60 fps
+    fn default() -> Self { Self::from_hz(60.0) }
+}
+
+impl Sleeper for RealClock {
+    fn sleep(&self, duration: Duration) { std::thread::sleep(duration) }
+}
+
+impl Sleeper for ManualClock {
+    fn sleep(&self, duration: Duration) { self.advance(duration) }
+    fn spin(&self, remaining: Duration) { self.advance(remaining) }
+}
+
+impl<S: Sleeper + ?Sized> Sleeper for &S     { fn sleep(&self, d: Duration) { (**self).sleep(d) } fn spin(&self, r: Duration) { (**self).spin(r) } }
+impl<S: Sleeper + ?Sized> Sleeper for Box<S> { fn sleep(&self, d: Duration) { (**self).sleep(d) } fn spin(&self, r: Duration) { (**self).spin(r) } }
+impl<S: Sleeper + ?Sized> Sleeper for Arc<S> { fn sleep(&self, d: Duration) { (**self).sleep(d) } fn spin(&self, r: Duration) { (**self).spin(r) } }
+
+impl Display for PacingStats {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        let ms = |dt: Duration| dt.as_secs_f64() * 1000.0;
+        write!(fmt, "{} frames ({} late), slept {:.1} ms, spun {:.1} ms, overshoot avg {:.3} ms max {:.3} ms",
+            self.frames, self.late, ms(self.slept), ms(self.spun), ms(self.avg_overshoot), ms(self.max_overshoot),
+        )
+    }
+}
+
+
+
+#[test] fn pacing() {
+    /// Oversleeps by 3ms, and spins in 1ms increments, like a coarse OS timer.
+    struct Coarse(Arc<ManualClock>);
+    impl Sleeper for Coarse {
+        fn sleep(&self, d: Duration) { self.0.advance(d + Duration::from_millis(3)) }
+        fn spin(&self, _: Duration) { self.0.advance(Duration::from_millis(1)) }
+    }
+
+    let ms = Duration::from_millis;
+    let clock = Arc::new(ManualClock::new());
+    let mut pacer = FramePacer::new(Some(ms(10)));
+    pacer.set_clock(clock.clone());
+    pacer.set_sleeper(Coarse(clock.clone()));
+    pacer.set_spin_threshold(ms(4));
+
+    assert_eq!(pacer.wait(), PacedFrame::default(), "first frame doesn't wait");
+    clock.advance(ms(2));
+    assert_eq!(pacer.wait(), PacedFrame { slept: ms(7), spun: ms(1), overshoot: ms(0), late: false }); // sleep 4ms + 3ms, spin to 10ms
+    clock.advance(ms(5));
+    assert_eq!(pacer.wait(), PacedFrame { slept: ms(4), spun: ms(1), overshoot: ms(0), late: false }); // sleep 1ms + 3ms
+    clock.advance(ms(8));
+    assert_eq!(pacer.wait(), PacedFrame { slept: ms(0), spun: ms(2), overshoot: ms(0), late: false }); // spin only
+    clock.advance(ms(13));
+    assert_eq!(pacer.wait(), PacedFrame { late: true, ..Default::default() });
+    clock.advance(ms(2));
+    assert_eq!(pacer.wait(), PacedFrame { slept: ms(4), spun: ms(1), overshoot: ms(0), late: false }, "back on schedule: 50ms, not 53ms");
+
+    pacer.set_spin_threshold(ms(0));
+    clock.advance(ms(5));
+    assert_eq!(pacer.wait(), PacedFrame { slept: ms(8), spun: ms(0), overshoot: ms(3), late: false }); // 5ms + 3ms oversleep
+
+    let stats = pacer.stats();
+    assert_eq!((stats.frames, stats.late, stats.max_overshoot), (7, 1, ms(3)));
+    assert_eq!(stats.avg_overshoot, ms(3) / 6);
+
+    pacer.set_target(None);
+    clock.advance(ms(1));
+    assert_eq!(pacer.wait(), PacedFrame::default(), "unlimited");
+}
@@ -0,0 +1,130 @@
+use crate::time::{Clock, FrameStats, RealClock};
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use instant::*;
+
+
+
+/// Tracks a rolling window of frame times, for an average frame rate or more detailed [`FrameStats`].
+///
+/// ```
+/// # use kakistocracy::time::*;
+/// # use std::time::Duration;
+/// let mut counter = FrameRateCounter::new(120);
+/// loop {
+///     // ...update & render...
+///     let avg_dt = counter.frame();
+/// #   break;
+/// }
+/// let stats = counter.stats();
+/// println!("{}", stats); // e.g. "60.0 fps (avg 16.67 ms, min 16.01 ms, max 17.80 ms, p95 ...)"
+/// ```
+pub struct FrameRateCounter {
+    clock:              Arc<dyn Clock>,
+    capacity:           usize,
+    last:               Instant,
+    history:            VecDeque<Duration>,
+    hitch_threshold:    Duration,
+    histogram_bucket:   Duration,
+    histogram_buckets:  usize,
+}
+
+impl FrameRateCounter {
+    /// Track the last `capacity` frames.  Frame times are measured from now.
+    pub fn new(capacity: usize) -> Self { Self::with_clock(capacity, RealClock) }
+
+    /// Track the last `capacity` frames, timed by `clock`.  Frame times are measured from now.
+    pub fn with_clock(capacity: usize, clock: impl Clock + 'static) -> Self {
+        let clock = Arc::new(clock);
+        let mut history = VecDeque::new();
+        history.reserve_exact(capacity);
+        Self {
+            last:               clock.now(),
+            clock,
+            capacity:           capacity.max(1),
+            history,
+            hitch_threshold:    Duration::from_millis(50),
+            histogram_bucket:   Duration::from_millis(2),
+            histogram_buckets:  25,
+        }
+    }
+
+    /// Mark the end of a frame.  Returns the average frame time over the window.
+    pub fn frame(&mut self) -> Duration {
+        let now = self.clock.now();
+        let dt = now.saturating_duration_since(self.last);
+        self.last = now;
+        self.push_frame_time(dt);
+        self.average()
+    }
+
+    /// Record a frame that took `dt`, without consulting the clock.  Useful for feeding in externally measured frame times.
+    pub fn push_frame_time(&mut self, dt: Duration) {
+        if self.history.len() >= self.capacity { self.history.pop_front(); }
+        self.history.push_back(dt);
+    }
+
+    /// The average frame time over the window, or zero if no frames have been recorded.
+    pub fn average(&self) -> Duration {
+        let n = self.history.len() as u32;
+        if n == 0 { Duration::ZERO } else { self.history.iter().sum::<Duration>() / n }
+    }
+
+    /// Recorded frame times, oldest first.
+    pub fn frame_times(&self) -> impl Iterator<Item = Duration> + '_ { self.history.iter().copied() }
+
+    pub fn capacity(&self) -> usize { self.capacity }
+    pub fn len(&self) -> usize { self.history.len() }
+    pub fn is_empty(&self) -> bool { self.history.is_empty() }
+
+    /// Forget all recorded frames.
+    pub fn clear(&mut self) {
+        self.history.clear();
+        self.last = self.clock.now();
+    }
+
+    /// Frames longer than `threshold` are counted as [`FrameStats::hitches`].  Defaults to 50ms.
+    pub fn set_hitch_threshold(&mut self, threshold: Duration) { self.hitch_threshold = threshold; }
+    pub fn hitch_threshold(&self) -> Duration { self.hitch_threshold }
+
+    /// Use `buckets` histogram buckets, each `bucket` wide, for [`FrameStats::histogram`].  Defaults to 25 buckets of 2ms each.
+    ///
+    /// Frames longer than `bucket * buckets` are counted in an additional, final bucket.
+    pub fn set_histogram(&mut self, bucket: Duration, buckets: usize) {
+        self.histogram_bucket = bucket;
+        self.histogram_buckets = buckets;
+    }
+
+    /// Statistics for the frames currently in the window.
+    pub fn stats(&self) -> FrameStats {
+        FrameStats::new(self.history.iter().copied(), self.hitch_threshold, self.histogram_bucket, self.histogram_buckets)
+    }
+}
+
+
+
+#[test] fn rolling_window() {
+    let mut counter = FrameRateCounter::new(4);
+    assert_eq!(counter.average(), Duration::ZERO);
+    for ms in [10, 20, 30, 40, 50].iter() { counter.push_frame_time(Duration::from_millis(*ms)); }
+    assert_eq!(counter.len(), 4);
+    assert_eq!(counter.frame_times().collect::<Vec<_>>(), [20, 30, 40, 50].iter().map(|ms| Duration::from_millis(*ms)).collect::<Vec<_>>());
+    assert_eq!(counter.average(), Duration::from_millis(35));
+    assert_eq!(counter.stats().hitches, 0);
+    counter.set_hitch_threshold(Duration::from_millis(30));
+    assert_eq!(counter.stats().hitches, 2);
+    counter.frame();
+    assert_eq!(counter.len(), 4);
+    counter.clear();
+    assert!(counter.is_empty());
+
+    let clock = Arc::new(crate::time::ManualClock::new());
+    let mut counter = FrameRateCounter::with_clock(2, clock.clone());
+    clock.advance(Duration::from_millis(10));
+    assert_eq!(counter.frame(), Duration::from_millis(10));
+    clock.advance(Duration::from_millis(20));
+    assert_eq!(counter.frame(), Duration::from_millis(15));
+    clock.advance(Duration::from_millis(40));
+    assert_eq!(counter.frame(), Duration::from_millis(30));
+}
@@ -0,0 +1,110 @@
+use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
+
+
+
+/// Statistics about a window of frame times, from [`FrameRateCounter::stats`](crate::time::FrameRateCounter::stats).
+///
+/// All durations are zero if there were no frames.  [`Display`] this for a one line summary suitable for an overlay.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    pub frames:             usize,
+    pub average:            Duration,
+    pub min:                Duration,
+    pub max:                Duration,
+    /// 95% of frames took this long or less.
+    pub p95:                Duration,
+    /// 99% of frames took this long or less.
+    pub p99:                Duration,
+    /// The (population) standard deviation of frame times.
+    pub std_dev:            Duration,
+    /// The number of frames longer than `hitch_threshold`.
+    pub hitches:            usize,
+    pub hitch_threshold:    Duration,
+    /// Frame counts by frame time, shortest first.  The final bucket counts every frame beyond the others.
+    pub histogram:          Vec<HistogramBucket>,
+}
+
+/// A range of frame times in [`FrameStats::histogram`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistogramBucket {
+    /// Inclusive.
+    pub start:  Duration,
+    /// Exclusive.  [`Duration::MAX`] for the final bucket.
+    pub end:    Duration,
+    pub frames: usize,
+}
+
+impl FrameStats {
+    /// Calculate statistics for `frame_times`, with `buckets` histogram buckets of width `bucket` (plus a final bucket for longer frames.)
+    pub fn new(frame_times: impl IntoIterator<Item = Duration>, hitch_threshold: Duration, bucket: Duration, buckets: usize) -> Self {
+        let mut sorted = frame_times.into_iter().collect::<Vec<_>>();
+        sorted.sort_unstable();
+
+        let mut histogram = (0 .. buckets as u32).map(|i| HistogramBucket { start: bucket * i, end: bucket * (i+1), frames: 0 }).collect::<Vec<_>>();
+        histogram.push(HistogramBucket { start: bucket * buckets as u32, end: Duration::MAX, frames: 0 });
+
+        let frames = sorted.len();
+        if frames == 0 { return Self { hitch_threshold, histogram, ..Default::default() } }
+
+        for dt in sorted.iter() {
+            let i = if bucket.is_zero() { buckets } else { ((dt.as_nanos() / bucket.as_nanos()) as usize).min(buckets) };
+            histogram[i].frames += 1;
+        }
+
+        let average = sorted.iter().sum::<Duration>() / frames as u32;
+        let variance = sorted.iter().map(|dt| (dt.as_secs_f64() - average.as_secs_f64()).powi(2)).sum::<f64>() / frames as f64;
+        let percentile = |p: usize| sorted[((frames * p).div_ceil(100)).clamp(1, frames) - 1]; // nearest rank
+        Self {
+            frames,
+            average,
+            min:        sorted[0],
+            max:        sorted[frames - 1],
+            p95:        percentile(95),
+            p99:        percentile(99),
+            std_dev:    Duration::from_secs_f64(variance.sqrt()),
+            hitches:    sorted.iter().filter(|dt| **dt > hitch_threshold).count(),
+            hitch_threshold,
+            histogram,
+        }
+    }
+
+    /// Frames per second, based on the [`average`](Self::average) frame time.  Zero if there were no frames.
+    pub fn fps(&self) -> f64 {
+        if self.average.is_zero() { 0.0 } else { 1.0 / self.average.as_secs_f64() }
+    }
+}
+
+impl Display for FrameStats {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        let ms = |dt: Duration| dt.as_secs_f64() * 1000.0;
+        write!(fmt, "{:.1} fps (avg {:.2} ms, min {:.2} ms, max {:.2} ms, p95 {:.2} ms, p99 {:.2} ms, σ {:.2} ms, {} hitch(es) > {:.0} ms)",
+            self.fps(), ms(self.average), ms(self.min), ms(self.max), ms(self.p95), ms(self.p99), ms(self.std_dev), self.hitches, ms(self.hitch_threshold),
+        )
+    }
+}
+
+
+
+#[test] fn stats() {
+    let ms = Duration::from_millis;
+    let times = (1 ..= 100).map(ms).collect::<Vec<_>>();
+    let stats = FrameStats::new(times, ms(90), ms(25), 2);
+    assert_eq!(stats.frames, 100);
+    assert_eq!((stats.min, stats.max), (ms(1), ms(100)));
+    assert_eq!(stats.average, Duration::from_micros(50_500));
+    assert_eq!((stats.p95, stats.p99), (ms(95), ms(99)));
+    assert_eq!(stats.std_dev.as_micros(), 28_866); // sqrt((100² - 1) / 12) ms
+    assert_eq!(stats.hitches, 10);
+    assert_eq!(stats.histogram.iter().map(|b| b.frames).collect::<Vec<_>>(), [24, 25, 51]);
+    assert_eq!(stats.histogram[2], HistogramBucket { start: ms(50), end: Duration::MAX, frames: 51 });
+    assert!(stats.to_string().starts_with("19.8 fps (avg 50.50 ms, min 1.00 ms, max 100.00 ms, p95 95.00 ms, p99 99.00 ms"), "{}", stats);
+
+    let one = FrameStats::new(Some(ms(16)), ms(50), ms(2), 25);
+    assert_eq!((one.p95, one.p99, one.std_dev), (ms(16), ms(16), Duration::ZERO));
+    assert_eq!(one.histogram[8].frames, 1);
+
+    let none = FrameStats::new(None, ms(50), ms(2), 25);
+    assert_eq!((none.frames, none.average, none.fps()), (0, Duration::ZERO, 0.0));
+    assert_eq!(none.histogram.len(), 26);
+}
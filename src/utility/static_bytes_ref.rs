@@ -12,7 +12,7 @@ impl StaticBytesRef { fn cmp_data(&self) -> (*const u8, usize) { (self.0.as_ptr(
 
 impl PartialEq  for StaticBytesRef { fn eq(&self, other: &Self) -> bool { self.cmp_data() == other.cmp_data() } }
 impl Eq         for StaticBytesRef {}
-impl PartialOrd for StaticBytesRef { fn partial_cmp(&self, other: &Self) -> Option<Ordering> { self.cmp_data().partial_cmp(&other.cmp_data()) } }
+impl PartialOrd for StaticBytesRef { fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) } }
 impl Ord        for StaticBytesRef { fn cmp(&self, other: &Self) -> Ordering { self.cmp_data().cmp(&other.cmp_data()) } }
 impl Hash       for StaticBytesRef { fn hash<H: Hasher>(&self, state: &mut H) { self.cmp_data().hash(state) } }
 
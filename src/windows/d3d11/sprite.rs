@@ -3,7 +3,7 @@
 pub use crate::sprite::*;
 
 use crate::*;
-use crate::io::StaticFile;
+use crate::texture::TextureSource;
 use crate::windows::*;
 use crate::windows::d3d11::{BasicTextureCache, Vertex};
 
@@ -20,7 +20,7 @@ use std::ptr::*;
 
 
 impl private::RenderTarget for &mcom::Rc<ID3D11DeviceContext> {
-    unsafe fn render1(&mut self, texture: &StaticFile, instances: &[Instance]) {
+    unsafe fn render1(&mut self, texture: TextureSource, instances: &[Instance]) {
         SpriteRenderer::new(self).draw(texture, instances)
     }
 }
@@ -39,7 +39,7 @@ impl<'d> SpriteRenderer<'d> {
     pub unsafe fn new(context: &'d mcom::Rc<ID3D11DeviceContext>) -> Self {
         let device = context.get_device();
         let resources   = d3d11::device_private_data_get_or_insert(&device, || Resources::new(&device));
-        let textures    = d3d11::device_private_data_get_or_insert(&device, || BasicTextureCache::new(device.clone()));
+        let textures    = d3d11::basic_texture_cache(&device);
         let mut n_viewports = 1;
         let mut viewport = std::mem::zeroed();
         context.RSGetViewports(&mut n_viewports, &mut viewport);
@@ -52,12 +52,12 @@ impl<'d> SpriteRenderer<'d> {
         Self { device, context, viewport, textures, resources }
     }
 
-    pub unsafe fn draw(&mut self, texture: &StaticFile, instances: &[Instance]) {
+    pub unsafe fn draw(&mut self, texture: TextureSource, instances: &[Instance]) {
         if instances.is_empty() { return } // Early out optimization
 
         // Common state
 
-        let texture = self.textures.get_texture_2d_static_file(texture);
+        let texture = self.textures.get_texture_2d(texture);
 
         self.context.IASetIndexBuffer(self.resources.quads_ib.as_ptr(), DXGI_FORMAT_R16_UINT, 0);
         self.context.IASetInputLayout(self.resources.sprite_vertex_layout.as_ptr());
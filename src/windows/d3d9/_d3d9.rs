@@ -8,6 +8,6 @@ mod errors;                     pub(crate) use errors::*;
 mod render;                     pub use render::*;
 pub(crate) mod prelude;         #[allow(unused_imports)] pub(crate) use prelude::*;
 pub(crate) mod sprite;
-mod texture_cache;              pub(crate) use texture_cache::*;
+mod texture_cache;              pub use texture_cache::*;
 mod thread_local;               pub use thread_local::*;
 mod traits;                     pub use traits::*;
@@ -3,7 +3,7 @@
 pub use crate::sprite::*;
 
 use crate::*;
-use crate::io::StaticFile;
+use crate::texture::TextureSource;
 use crate::windows::*;
 use crate::windows::d3d9::{BasicTextureCache, Vertex};
 
@@ -18,7 +18,7 @@ use std::ptr::*;
 
 
 impl private::RenderTarget for &mcom::Rc<IDirect3DDevice9> {
-    unsafe fn render1(&mut self, texture: &StaticFile, instances: &[Instance]) {
+    unsafe fn render1(&mut self, texture: TextureSource, instances: &[Instance]) {
         SpriteRenderer::new(self).draw(texture, instances)
     }
 }
@@ -35,7 +35,7 @@ struct SpriteRenderer<'d> {
 impl<'d> SpriteRenderer<'d> {
     pub unsafe fn new(device: &'d mcom::Rc<IDirect3DDevice9>) -> Self {
         let resources   = d3d9::device_private_data_get_or_insert(device, || Resources::new(device));
-        let textures    = d3d9::device_private_data_get_or_insert(device, || BasicTextureCache::new(device.clone()));
+        let textures    = d3d9::basic_texture_cache(device);
         let mut viewport = std::mem::zeroed();
         let _hr = device.GetViewport(&mut viewport);
         let vx = viewport.X as f32 + 0.5;
@@ -47,12 +47,12 @@ impl<'d> SpriteRenderer<'d> {
         Self { device, viewport, textures, resources }
     }
 
-    pub unsafe fn draw(&mut self, texture: &StaticFile, instances: &[Instance]) {
+    pub unsafe fn draw(&mut self, texture: TextureSource, instances: &[Instance]) {
         if instances.is_empty() { return } // Early out optimization
 
         // Common state
 
-        let texture = self.textures.get_texture_2d_static_file(texture);
+        let texture = self.textures.get_texture_2d(texture);
 
         let _hr = self.device.SetRenderState(D3DRS_LIGHTING, false.into());
         let _hr = self.device.SetIndices(self.resources.quads_ib.as_ptr());
@@ -19,6 +19,11 @@ pub fn create_fullscreen_window(monitor: impl monitor::Selector, title: &str, co
 }
 
 pub fn create_window_at(title: &str, area: impl IntoRect, context: impl Render + message::Handler + 'static) -> Result<(), Error> {
+    create_window_hwnd_at(title, area, context).map(|_hwnd| ())
+}
+
+/// [`create_window_at`], returning the created window (e.g. to `DestroyWindow` it later.)
+pub(crate) fn create_window_hwnd_at(title: &str, area: impl IntoRect, context: impl Render + message::Handler + 'static) -> Result<HWND, Error> {
     ThreadLocal::with(|tl| tl.create_window_at(title, area, context))
 }
 
@@ -71,14 +76,14 @@ thread_local! { static TL : ThreadLocal = ThreadLocal::new(); }
 /// Public Methods
 impl ThreadLocal {
     pub fn create_fullscreen_window(&self, monitor: impl monitor::Selector, title: &str, context: impl Render + message::Handler + 'static) -> Result<(), Error> {
-        self.create_window_impl(title, monitor.monitor_area(), WS_POPUP | WS_VISIBLE, context)
+        self.create_window_impl(title, monitor.monitor_area(), WS_POPUP | WS_VISIBLE, context).map(|_hwnd| ())
     }
 
-    pub fn create_window_at(&self, title: &str, area: impl IntoRect, context: impl Render + message::Handler + 'static) -> Result<(), Error> {
+    pub fn create_window_at(&self, title: &str, area: impl IntoRect, context: impl Render + message::Handler + 'static) -> Result<HWND, Error> {
         self.create_window_impl(title, area.into(), WS_OVERLAPPEDWINDOW | WS_VISIBLE, context)
     }
 
-    fn create_window_impl(&self, title: &str, area: RECT, style: DWORD, context: impl Render + message::Handler + 'static) -> Result<(), Error> {
+    fn create_window_impl(&self, title: &str, area: RECT, style: DWORD, context: impl Render + message::Handler + 'static) -> Result<HWND, Error> {
         let hwnd = unsafe { CreateWindowExW(
             0,
             MAKEINTATOMW(*D3D9_MWC_WNDCLASS),
@@ -94,7 +99,7 @@ impl ThreadLocal {
             swap_chain: Default::default(),
         })?;
         self.windows.borrow_mut().push(hwnd);
-        Ok(())
+        Ok(hwnd)
     }
 
     pub fn lock(&self, allow_no_rendered_windows: bool) -> Option<RenderLock> {
@@ -151,6 +156,7 @@ impl ThreadLocal {
                     assoc.context.render(&window);
                 }
             }
+            d3d9::basic_texture_cache(&lock.device).next_frame();
         }
     }
 }
@@ -4,19 +4,23 @@
 //! * [About Messages and Message Queues](https://docs.microsoft.com/en-us/windows/win32/winmsg/about-messages-and-message-queues)
 //! * [Using Messages and Message Queues](https://docs.microsoft.com/en-us/windows/win32/winmsg/using-messages-and-message-queues)
 
+use crate::frame::{FrameScheduler, LoopHandle, TaskHandle};
+pub use crate::frame::EachFrameArgs;
+use crate::time::FramePacer;
 use crate::windows::*;
 
 use futures::Future;
-use futures::executor::*;
 use futures::task::*;
 
 use winapi::ctypes::c_int;
 use winapi::shared::minwindef::*;
 use winapi::shared::windef::*;
+use winapi::um::processthreadsapi::GetCurrentThreadId;
 use winapi::um::winuser::*;
 
 use std::cell::RefCell;
 use std::ptr::null_mut;
+use std::sync::Arc;
 
 
 
@@ -31,7 +35,7 @@ pub fn loop_until_wm_quit() -> c_int {
     }
 }
 
-/// Run a message loop on this thread once.
+/// Run a message loop on this thread once, then wait out the rest of the frame (see [`frame_pacer`].)
 ///
 /// If [`WM_QUIT`](https://docs.microsoft.com/en-us/windows/win32/winmsg/wm-quit) is encountered, returns `Some(nExitCode)` based on what was passed to [`PostQuitMessage`](https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-postquitmessage).
 pub fn loop_one_frame() -> Option<c_int> {
@@ -46,18 +50,12 @@ pub fn loop_one_frame() -> Option<c_int> {
     }
 
     TL.with(|tl|{
-        // TODO: rendering & per-frame tasks
-        if let Ok(mut pool) = tl.local_pool.try_borrow_mut() {
-            pool.run_until_stalled();
-        }
-        // else we're recursively running a message loop within a task? probably an incredibly bad idea, but don't crash
+        // TODO: rendering
+        tl.scheduler.run_frame(); // tasks & each_frame callbacks (no-op if we're recursively running a message loop within either)
 
-        if let Ok(mut each_frame) = tl.each_frame.try_borrow_mut() {
-            each_frame.append(&mut *tl.each_frame_pending.borrow_mut());
-            let efa = EachFrameArgs {};
-            retain_mut(&mut each_frame, |f| f(&efa));
+        if let Ok(mut pacer) = tl.pacer.try_borrow_mut() {
+            pacer.wait();
         }
-        // else we're recursively running a message loop within an each_frame callback? probably an incredibly bad idea, but don't crash
     });
     None
 }
@@ -68,17 +66,50 @@ pub fn post_quit(exit_code: c_int) {
 }
 
 /// Run a future/task in the current thread's message handling loop
+///
+/// Tasks run on the thread's [`FrameScheduler`], so they can await [`next_frame`](crate::frame::next_frame), [`delay`](crate::frame::delay), etc.
 pub fn spawn_local<F: Future<Output = ()> + 'static>(f: F) -> Result<(), SpawnError> {
-    TL.with(|tl| tl.local_spawner.spawn_local(f))
+    TL.with(|tl| tl.scheduler.spawn_local(f))
+}
+
+/// Run a future/task in the current thread's message handling loop, until it finishes or the returned handle is dropped.
+///
+/// Use a [`TaskScope`](crate::frame::TaskScope) to cancel several together (e.g. when closing a window.)
+pub fn spawn<F: Future<Output = ()> + 'static>(f: F) -> Result<TaskHandle, SpawnError> {
+    TL.with(|tl| tl.scheduler.spawn(f))
+}
+
+/// A [`Send`]able handle for posting closures and futures onto the current thread's message handling loop from other threads.
+///
+/// Posting wakes the loop with a [`WM_NULL`](https://docs.microsoft.com/en-us/windows/win32/winmsg/wm-null) thread message.
+pub fn handle() -> LoopHandle {
+    TL.with(|tl| tl.scheduler.handle())
 }
 
 /// Run logic in the current thread's message handling loop "each frame".
 ///
-/// Read "each frame" as: roughly in sync with the refresh rate of one of your monitors, possibly skipping some if falling behind.
+/// Read "each frame" as: paced by [`frame_pacer`] (60 fps by default), possibly skipping some if falling behind.
 ///
 /// If the callback ever returns `false`, it will be unregistered and not called again.
+/// Wrap it with [`TaskScope::bind`](crate::frame::TaskScope::bind) to also unregister it when the scope is cancelled.
 pub fn each_frame(f: impl 'static + FnMut(&EachFrameArgs) -> bool) {
-    TL.with(|tl| tl.each_frame_pending.borrow_mut().push(Box::new(f)));
+    TL.with(|tl| tl.scheduler.each_frame(f));
+}
+
+/// Run `f` with the current thread's [`FrameScheduler`], e.g. to [`set_time_scale`](FrameScheduler::set_time_scale) or [`set_paused`](FrameScheduler::set_paused).
+pub fn scheduler<R>(f: impl FnOnce(&FrameScheduler) -> R) -> R {
+    TL.with(|tl| f(&tl.scheduler))
+}
+
+/// Configure the [`FramePacer`] the current thread's message handling loop waits on each frame (60 fps by default.)
+///
+/// ```no_run
+/// # use kakistocracy::windows::message;
+/// message::frame_pacer(|pacer| pacer.set_target(None)); // unlimited
+/// let stats = message::frame_pacer(|pacer| pacer.stats());
+/// ```
+pub fn frame_pacer<R>(f: impl FnOnce(&mut FramePacer) -> R) -> R {
+    TL.with(|tl| f(&mut tl.pacer.borrow_mut()))
 }
 
 /// A win32 message handler (provides a [`wndproc`](Self::wndproc))
@@ -92,48 +123,25 @@ pub trait Handler {
     unsafe fn wndproc(&self, hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> LRESULT;
 }
 
-/// Arguments to [`each_frame`]'s callbacks.
-#[non_exhaustive]
-pub struct EachFrameArgs {
-    // TODO: time deltas?
-}
-
 
 
 struct ThreadLocal {
-    local_pool:         RefCell<LocalPool>,
-    local_spawner:      LocalSpawner,
-    each_frame:         RefCell<Vec<Box<dyn FnMut(&EachFrameArgs) -> bool>>>,
-    each_frame_pending: RefCell<Vec<Box<dyn FnMut(&EachFrameArgs) -> bool>>>,
+    scheduler:          FrameScheduler,
+    pacer:              RefCell<FramePacer>,
 }
 
 impl Default for ThreadLocal {
     fn default() -> Self {
-        let local_pool          = LocalPool::new();
-        let local_spawner       = local_pool.spawner();
-        let local_pool          = RefCell::new(local_pool);
-        let each_frame          = Default::default();
-        let each_frame_pending  = Default::default();
-        Self { local_pool, local_spawner, each_frame, each_frame_pending }
+        let scheduler           = FrameScheduler::new();
+        let thread_id           = unsafe { GetCurrentThreadId() };
+        scheduler.set_wakeup(Some(Arc::new(move || { unsafe { PostThreadMessageW(thread_id, WM_NULL, 0, 0) }; })));
+        let pacer               = Default::default();
+        Self { scheduler, pacer }
     }
 }
 
 thread_local! { static TL : ThreadLocal = ThreadLocal::default(); }
 
-fn retain_mut<T, F: FnMut(&mut T) -> bool>(vec: &mut Vec<T>, mut f: F) {
-    let len = vec.len();
-    let mut del = 0;
-    let v = &mut **vec;
-    for i in 0..len {
-        if !f(&mut v[i]) {
-            del += 1;
-        } else if del > 0 {
-            v.swap(i - del, i);
-        }
-    }
-    vec.truncate(len - del);
-}
-
 
 
 #[test] fn message_loop_test_1() {